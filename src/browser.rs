@@ -4,18 +4,21 @@ use chromiumoxide::cdp::browser_protocol::page::{
     self, ClientNavigationReason, FrameId, NavigationType,
 };
 use chromiumoxide::cdp::browser_protocol::target::{self, TargetId};
-use chromiumoxide::cdp::browser_protocol::{dom, emulation};
+use chromiumoxide::cdp::browser_protocol::{dom, emulation, overlay, security};
 use chromiumoxide::cdp::js_protocol::debugger::{self, CallFrameId};
 use chromiumoxide::cdp::js_protocol::runtime::{self};
+use chromiumoxide::error::CdpError;
 use chromiumoxide::page::ScreenshotParams;
 use chromiumoxide::{BrowserConfig, Page};
 use futures::{StreamExt, stream};
 use log;
+use serde::Deserialize;
 use serde_json as json;
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::pin::Pin;
 use std::sync::Arc;
-use std::time::{Duration, UNIX_EPOCH};
+use std::time::{Duration, Instant, UNIX_EPOCH};
 use tempfile::TempDir;
 use tokio::sync::broadcast::error::RecvError;
 use tokio::sync::broadcast::{Receiver, Sender, channel};
@@ -23,24 +26,36 @@ use tokio::sync::oneshot;
 use tokio::time::sleep;
 use tokio::{select, spawn};
 use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
 use url::Url;
 
 use crate::browser::actions::BrowserAction;
+use crate::browser::evaluation::{skip_timeout, with_timeout};
 use crate::browser::state::{
-    BrowserState, CallFrame, ConsoleEntry, Exception, Screenshot,
-    ScreenshotFormat,
+    BrowserState, CallFrame, CaptureOptions, ConsoleEntry, CoverageMode,
+    DomChange, DomChanges, Exception, Screenshot, ScreenshotFormat,
 };
+use crate::error::BombadilError;
+use crate::runner::{
+    ActionCandidate, deserialize_millis, deserialize_optional_millis,
+};
+use crate::specification::worker::VerifierWorker;
+use crate::url::UrlScope;
 
 pub mod actions;
 pub mod evaluation;
 pub mod instrumentation;
 pub mod keys;
+mod relay;
 pub mod state;
 
 #[derive(Debug, Clone)]
 #[allow(clippy::large_enum_variant)]
 pub enum BrowserEvent {
     StateChanged(BrowserState),
+    /// Applying an action failed. A new state is captured right away, instead of waiting for the
+    /// action's timeout.
+    ActionFailed(BrowserAction, Arc<anyhow::Error>),
     Error(Arc<anyhow::Error>),
 }
 
@@ -50,6 +65,15 @@ struct InnerStateShared {
     console_entries: Vec<ConsoleEntry>,
     exceptions: Vec<Exception>,
     screenshot: Option<Screenshot>,
+    /// Main frame navigations since the last state capture.
+    navigations: u32,
+    /// Screenshots of the elements in `element_screenshots`, by selector.
+    element_screenshots: HashMap<String, Screenshot>,
+    /// DOM changes since the last state capture, when recording them.
+    dom_changes: DomChanges,
+    /// Consecutive responsiveness probes that timed out, across states until one succeeds or the
+    /// main frame navigates.
+    probe_timeouts: u32,
 }
 
 #[derive(Debug)]
@@ -81,13 +105,20 @@ enum InnerEvent {
     },
     Resumed,
     FrameRequestedNavigation(FrameId, ClientNavigationReason, String),
-    FrameNavigated(FrameId, NavigationType),
+    FrameNavigated(FrameId, NavigationType, String),
     TargetDestroyed(TargetId),
     NodeTreeModified(NodeModification),
     ConsoleEntry(ConsoleEntry),
     ActionAccepted(BrowserAction, Timeout),
     ActionApplied(Generation),
+    ActionFailed(Generation, BrowserAction, Arc<anyhow::Error>),
     ExceptionThrown(Exception),
+    /// Navigating to the starting URL failed, with the browser's error text.
+    NavigationFailed(String, String),
+    /// A responsiveness probe completed, with whether the page responded in time.
+    Probed(Generation, bool),
+    /// This many events were dropped as the channel was full, e.g. on a page changing a lot.
+    Lagged(u64),
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -116,6 +147,9 @@ impl std::fmt::Display for Generation {
 
 type Timeout = Duration;
 
+/// How many responsiveness probes in a row must time out for the page to count as unresponsive.
+const UNRESPONSIVE_PROBES: u32 = 3;
+
 #[derive(Clone, Debug)]
 #[allow(clippy::large_enum_variant)]
 pub enum NodeModification {
@@ -145,8 +179,27 @@ struct BrowserContext {
     shutdown_receiver: oneshot::Receiver<()>,
     page: Arc<Page>,
     frame_id: FrameId,
-    #[allow(unused, reason = "this is going into the scripts soon")]
     origin: Url,
+    url_scope: UrlScope,
+    slow_motion: Option<Duration>,
+    redirect_loop_threshold: Option<u32>,
+    responsiveness_probe: Option<Duration>,
+    element_screenshots: Vec<String>,
+    record_dom_changes: bool,
+    readiness: Option<Readiness>,
+    capture_options: CaptureOptions,
+    instrumentation_failures: Arc<instrumentation::InstrumentationFailures>,
+}
+
+impl BrowserContext {
+    /// Whether the main frame may navigate to the URL, i.e. it's within the origin's domain and
+    /// in scope, or an allowed external URL.
+    fn permits_navigation(&self, url: &str) -> bool {
+        match Url::parse(url) {
+            Ok(url) => self.url_scope.permits(&url, &self.origin),
+            Err(_) => self.url_scope.contains(url),
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -154,26 +207,163 @@ pub struct LaunchOptions {
     pub headless: bool,
     pub user_data_directory: PathBuf,
     pub no_sandbox: bool,
+    /// The Chrome or Chromium executable to launch, e.g. a pinned version, instead of the one
+    /// found on the system.
+    pub executable: Option<PathBuf>,
+    /// Arguments to pass to the browser in addition to ours, e.g. `--lang=de`.
+    pub extra_args: Vec<String>,
+    /// How many times to try launching the browser before giving up, at least once, as
+    /// launches fail transiently in CI (e.g. racing for the debugging port).
+    pub launch_attempts: u32,
+    /// How long to wait after the first failed attempt, doubling after each one.
+    pub launch_backoff: Duration,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Emulation {
     pub width: u16,
     pub height: u16,
     pub device_scale_factor: f64,
 }
 
-#[derive(Clone)]
+impl Default for Emulation {
+    fn default() -> Self {
+        Emulation {
+            width: 1024,
+            height: 768,
+            device_scale_factor: 2.0,
+        }
+    }
+}
+
+/// Deserialized from configuration files, where omitted fields keep their defaults (those of
+/// the command line) and durations are given as `{ "ms": N }`. Fields that only make sense
+/// programmatically, or per origin, can't be deserialized.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct BrowserOptions {
     pub emulation: Emulation,
+    #[serde(skip)]
     pub create_target: bool,
+    /// Whether to navigate to the origin when initiating, or to start exploring from whatever page
+    /// is open. In the latter case, the origin is only used to restrict the domain.
+    #[serde(skip)]
+    pub navigate_to_origin: bool,
+    /// Where to navigate when initiating, if not to the origin. The origin still restricts the
+    /// domain, which is useful when the origin's landing page redirects elsewhere.
+    #[serde(skip)]
+    pub start_url: Option<Url>,
+    #[serde(skip)]
     pub instrumentation: crate::instrumentation::InstrumentationConfig,
+    #[serde(skip)]
+    pub request_rules: Vec<instrumentation::RequestRule>,
+    /// Fails the test when a script or document can't be instrumented, instead of passing it
+    /// through without coverage.
+    pub strict_instrumentation: bool,
+    /// Disables the pages' Content Security Policies, which may otherwise block instrumented
+    /// scripts (e.g. inline scripts allowed by their hash).
+    pub bypass_csp: bool,
+    /// Accepts untrusted certificates, e.g. self-signed ones of test environments, instead of
+    /// failing to navigate to pages served with them.
+    pub ignore_certificate_errors: bool,
+    /// Scripts larger than this many bytes are passed through without instrumentation, to bound
+    /// memory use on sites with huge bundles.
+    pub max_script_size: Option<usize>,
+    /// Waits this long before applying each action, highlighting its target, so that a headed
+    /// run can be followed by eye.
+    #[serde(deserialize_with = "deserialize_optional_millis")]
+    pub slow_motion: Option<Duration>,
+    /// Fails with a redirect loop error when the main frame navigates more than this many times
+    /// without a state being captured, instead of hanging while navigating.
+    pub redirect_loop_threshold: Option<u32>,
+    /// Gives up on CDP interactions when capturing states after this long, so that a wedged
+    /// browser fails the test (or skips the screenshot, coverage, and hashes) instead of hanging.
+    #[serde(deserialize_with = "deserialize_optional_millis")]
+    pub cdp_timeout: Option<Duration>,
+    /// Evaluates a trivial expression in the page at this interval while acting, with the
+    /// interval as its timeout, reporting the page as unresponsive when several in a row time out.
+    #[serde(deserialize_with = "deserialize_optional_millis")]
+    pub responsiveness_probe: Option<Duration>,
+    /// Selectors of elements to take screenshots of in every state, in addition to the whole
+    /// page. The runner adds the selectors that the specification's properties focus on.
+    #[serde(skip)]
+    pub element_screenshots: Vec<String>,
+    /// How hit counts of edges are bucketed, e.g. to ignore them for stabler state hashes.
+    pub coverage_mode: CoverageMode,
+    /// Records how the DOM changed between states, in [`BrowserState::dom_changes`].
+    pub record_dom_changes: bool,
+    /// Computes [`BrowserState::dom_hash`] for each state, which walks the whole DOM. The runner
+    /// sets it when states are identified by their DOM hash.
+    #[serde(skip)]
+    pub dom_hash: bool,
+    /// How many internal events (DOM changes, console entries, and so on) are buffered for the
+    /// state machine. Pages changing faster than they're handled drop events past this, which
+    /// is logged, and may make the test miss state changes.
+    pub event_buffer_size: usize,
+    /// Waits for the page to be ready before capturing each state, on top of the pause after
+    /// actions, e.g. for lazily rendered parts of the page.
+    pub readiness: Option<Readiness>,
+}
+
+impl Default for BrowserOptions {
+    fn default() -> Self {
+        BrowserOptions {
+            emulation: Emulation::default(),
+            create_target: true,
+            navigate_to_origin: true,
+            start_url: None,
+            instrumentation:
+                crate::instrumentation::InstrumentationConfig::default(),
+            request_rules: vec![],
+            strict_instrumentation: false,
+            bypass_csp: false,
+            ignore_certificate_errors: false,
+            max_script_size: Some(20_000_000),
+            slow_motion: None,
+            redirect_loop_threshold: Some(20),
+            cdp_timeout: Some(Duration::from_secs(30)),
+            responsiveness_probe: Some(Duration::from_millis(250)),
+            element_screenshots: vec![],
+            coverage_mode: CoverageMode::default(),
+            record_dom_changes: false,
+            dom_hash: false,
+            event_buffer_size: 1024,
+            readiness: None,
+        }
+    }
+}
+
+/// An app-specific signal that the page is ready for its state to be captured, e.g. that no
+/// element is still loading.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Readiness {
+    /// A JavaScript expression, evaluated in the page, that's truthy once the page is ready.
+    pub expression: String,
+    /// How long to wait for the page to be ready, after which the state is captured anyway,
+    /// with a warning.
+    #[serde(deserialize_with = "deserialize_millis")]
+    pub timeout: Duration,
 }
 
 #[derive(Clone)]
 pub enum DebuggerOptions {
-    External { remote_debugger: Url },
-    Managed { launch_options: LaunchOptions },
+    External {
+        remote_debugger: Url,
+        /// Headers to send when connecting to the remote debugger, e.g. for authenticating
+        /// with a hosted browser. Requires a `ws://` or `wss://` URL.
+        headers: HashMap<String, String>,
+        /// How many times to try connecting when starting before giving up, at least once. A
+        /// connection lost later isn't re-established, as the page's listeners can't be
+        /// restored, and fails the test instead.
+        initial_connect_attempts: u32,
+        /// How long to wait after the first failed attempt, doubling after each one.
+        initial_connect_backoff: Duration,
+    },
+    Managed {
+        launch_options: LaunchOptions,
+    },
 }
 
 pub struct Browser {
@@ -185,39 +375,132 @@ pub struct Browser {
     browser: chromiumoxide::Browser,
     page: Arc<Page>,
     origin: Url,
-    go_to_origin_on_init: bool,
+    url_scope: UrlScope,
+    navigate_to_origin: bool,
+    start_url: Option<Url>,
+    /// The latest state, as long as the browser is paused in it waiting for an action.
+    current_state: Option<BrowserState>,
+    last_action: Option<BrowserAction>,
+    instrumented_files: Arc<instrumentation::InstrumentedFiles>,
+    instrumentation_failures: Arc<instrumentation::InstrumentationFailures>,
 }
 
 impl Browser {
     pub async fn new(
         origin: Url,
+        url_scope: UrlScope,
         browser_options: BrowserOptions,
         debugger_options: DebuggerOptions,
-    ) -> Result<Self> {
+    ) -> Result<Self, BombadilError> {
         let (mut browser, mut handler) = match debugger_options {
             DebuggerOptions::External {
                 ref remote_debugger,
+                ref headers,
+                initial_connect_attempts,
+                initial_connect_backoff,
             } => {
-                chromiumoxide::Browser::connect(remote_debugger.as_str())
-                    .await?
+                let remote_debugger = if headers.is_empty() {
+                    remote_debugger.clone()
+                } else {
+                    relay::relay_with_headers(remote_debugger, headers)
+                        .await
+                        .map_err(BombadilError::Launch)?
+                };
+                let mut attempt = 1;
+                let mut backoff = initial_connect_backoff;
+                loop {
+                    match chromiumoxide::Browser::connect(
+                        remote_debugger.as_str(),
+                    )
+                    .await
+                    {
+                        Ok(connection) => break connection,
+                        Err(error) if attempt < initial_connect_attempts => {
+                            log::warn!(
+                                "failed connecting to remote debugger (attempt {}/{}), retrying in {:?}: {}",
+                                attempt,
+                                initial_connect_attempts,
+                                backoff,
+                                error
+                            );
+                            sleep(backoff).await;
+                            attempt += 1;
+                            backoff *= 2;
+                        }
+                        Err(error) => {
+                            return Err(BombadilError::Launch(
+                                anyhow!(error).context(format!(
+                                    "failed connecting to remote debugger {}",
+                                    remote_debugger
+                                )),
+                            ));
+                        }
+                    }
+                }
             }
             DebuggerOptions::Managed { ref launch_options } => {
-                let browser_config = launch_options_to_config(
-                    launch_options,
-                    &browser_options.emulation,
-                )?;
-                chromiumoxide::Browser::launch(browser_config).await?
+                let mut attempt = 1;
+                let mut backoff = launch_options.launch_backoff;
+                loop {
+                    let (browser_config, command) = launch_options_to_config(
+                        launch_options,
+                        &browser_options,
+                    )
+                    .map_err(BombadilError::Launch)?;
+                    match chromiumoxide::Browser::launch(browser_config).await {
+                        Ok(launched) => break launched,
+                        Err(error)
+                            if attempt < launch_options.launch_attempts =>
+                        {
+                            log::warn!(
+                                "failed launching browser (attempt {}/{}), retrying in {:?}: {}",
+                                attempt,
+                                launch_options.launch_attempts,
+                                backoff,
+                                error
+                            );
+                            sleep(backoff).await;
+                            attempt += 1;
+                            backoff *= 2;
+                        }
+                        Err(error) => {
+                            return Err(BombadilError::Launch(
+                                anyhow!(error).context(format!(
+                                    "failed launching browser after {} attempts: {}",
+                                    attempt, command
+                                )),
+                            ));
+                        }
+                    }
+                }
             }
         };
 
+        let (sender, receiver) =
+            channel::<BrowserEvent>(BROWSER_EVENTS_CAPACITY);
+
+        let handler_sender = sender.clone();
         let _handle = tokio::spawn(async move {
-            loop {
-                let _ = handler.next().await;
-            }
+            let error = loop {
+                match handler.next().await {
+                    None => break anyhow!("connection closed"),
+                    // The handler reports WebSocket failures without ending its stream.
+                    Some(Err(CdpError::Ws(error))) => break anyhow!(error),
+                    Some(Err(error)) => {
+                        log::warn!("browser debugger handler error: {}", error)
+                    }
+                    Some(Ok(())) => {}
+                }
+            };
+            // The connection is gone, e.g. after a network failure with a remote debugger
+            // or the browser crashing. The page and its listeners can't be restored, so rather
+            // than stalling the test, it fails. This is also reached when terminating, when no
+            // one listens anymore.
+            let _ = handler_sender.send(BrowserEvent::Error(Arc::new(
+                error.context("lost connection to the browser's debugger"),
+            )));
         });
 
-        let (sender, receiver) = channel::<BrowserEvent>(1);
-
         let (actions_sender, _) = channel::<(BrowserAction, Timeout)>(1);
 
         let page = if browser_options.create_target {
@@ -232,6 +515,9 @@ impl Browser {
         page.enable_css().await?;
         page.enable_runtime().await?;
         page.enable_debugger().await?;
+        if browser_options.slow_motion.is_some() {
+            page.execute(overlay::EnableParams::default()).await?;
+        }
 
         page.execute(
             emulation::SetDeviceMetricsOverrideParams::builder()
@@ -250,8 +536,21 @@ impl Browser {
         )
         .await?;
 
+        if browser_options.bypass_csp {
+            page.execute(page::SetBypassCspParams::new(true))
+                .await
+                .context("failed bypassing content security policies")?;
+        }
+
+        // Set either way, as chromiumoxide ignores certificate errors by default.
+        page.execute(security::SetIgnoreCertificateErrorsParams::new(
+            browser_options.ignore_certificate_errors,
+        ))
+        .await
+        .context("failed configuring certificate error handling")?;
+
         let (inner_events_sender, inner_events_receiver) =
-            channel::<InnerEvent>(1024);
+            channel::<InnerEvent>(browser_options.event_buffer_size);
 
         let (shutdown_sender, shutdown_receiver) = oneshot::channel::<()>();
         let (done_sender, done_receiver) = oneshot::channel::<()>();
@@ -261,6 +560,10 @@ impl Browser {
             .await?
             .ok_or(anyhow!("no main frame available"))?;
 
+        let instrumentation_failures =
+            Arc::new(instrumentation::InstrumentationFailures::default());
+        let instrumented_files =
+            Arc::new(instrumentation::InstrumentedFiles::default());
         let context = BrowserContext {
             sender,
             actions_sender: actions_sender.clone(),
@@ -269,11 +572,31 @@ impl Browser {
             page: page.clone(),
             frame_id,
             origin: origin.clone(),
+            url_scope: url_scope.clone(),
+            slow_motion: browser_options.slow_motion,
+            redirect_loop_threshold: browser_options.redirect_loop_threshold,
+            responsiveness_probe: browser_options.responsiveness_probe,
+            element_screenshots: browser_options.element_screenshots.clone(),
+            record_dom_changes: browser_options.record_dom_changes,
+            readiness: browser_options.readiness.clone(),
+            capture_options: CaptureOptions {
+                cdp_timeout: browser_options.cdp_timeout,
+                coverage_mode: browser_options.coverage_mode,
+                dom_hash: browser_options.dom_hash,
+            },
+            instrumentation_failures: instrumentation_failures.clone(),
         };
 
         instrumentation::instrument_js_coverage(
             page.clone(),
             browser_options.instrumentation.clone(),
+            browser_options.request_rules.clone(),
+            browser_options.max_script_size,
+            instrumentation_failures.clone(),
+            instrumented_files.clone(),
+            browser_options
+                .strict_instrumentation
+                .then(|| context.sender.clone()),
         )
         .await?;
 
@@ -285,7 +608,11 @@ impl Browser {
         let events_all = stream::select_all(vec![
             inner_events(&context).await?,
             Box::pin(browser_events),
-            receiver_to_stream(inner_events_receiver),
+            receiver_to_stream(
+                inner_events_receiver,
+                "internal events",
+                |skipped| Some(InnerEvent::Lagged(skipped)),
+            ),
         ]);
         run_state_machine(context, events_all, done_sender);
 
@@ -298,26 +625,51 @@ impl Browser {
             done_receiver,
             page,
             origin,
-            go_to_origin_on_init: browser_options.create_target,
+            url_scope,
+            navigate_to_origin: browser_options.navigate_to_origin,
+            start_url: browser_options.start_url,
+            current_state: None,
+            last_action: None,
+            instrumented_files,
+            instrumentation_failures,
         })
     }
 
+    /// The sources instrumented for coverage so far, updated as the test runs.
+    pub fn instrumented_files(
+        &self,
+    ) -> Arc<instrumentation::InstrumentedFiles> {
+        self.instrumented_files.clone()
+    }
+
+    /// The scripts and documents that failed to be instrumented so far, updated as the test runs.
+    pub fn instrumentation_failures(
+        &self,
+    ) -> Arc<instrumentation::InstrumentationFailures> {
+        self.instrumentation_failures.clone()
+    }
+
     pub async fn initiate(&mut self) -> Result<()> {
-        if self.go_to_origin_on_init {
+        if self.navigate_to_origin {
             let page = self.page.clone();
-            let origin = self.origin.to_string();
+            let url =
+                self.start_url.as_ref().unwrap_or(&self.origin).to_string();
+            let sender = self.inner_events_sender.clone();
             spawn(async move {
-                log::info!("going to origin");
-                let _ = page.goto(origin).await;
+                log::info!("going to {}", url);
+                if let Err(error) = page.goto(url.clone()).await {
+                    let _ = sender.send(InnerEvent::NavigationFailed(
+                        url,
+                        error.to_string(),
+                    ));
+                }
             });
         } else {
             let _ = self.inner_events_sender.send(InnerEvent::StateRequested(
                 StateRequestReason::Start,
                 Generation::default(),
             ));
-            log::debug!(
-                "using externally managed debugger, not doing anything on init"
-            )
+            log::debug!("not navigating to origin, exploring from current page")
         }
         Ok(())
     }
@@ -346,10 +698,23 @@ impl Browser {
     }
 
     pub async fn next_event(&mut self) -> Option<BrowserEvent> {
-        match self.receiver.recv().await {
-            Ok(event) => Some(event),
-            Err(RecvError::Closed) => None,
-            Err(error) => Some(BrowserEvent::Error(Arc::new(anyhow!(error)))),
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) => {
+                    if let BrowserEvent::StateChanged(state) = &event {
+                        self.current_state = Some(state.clone());
+                    }
+                    return Some(event);
+                }
+                Err(RecvError::Closed) => return None,
+                // The oldest events were dropped, so the next one is still current.
+                Err(RecvError::Lagged(skipped)) => {
+                    log::warn!(
+                        "dropped {} browser events as they came faster than they were handled",
+                        skipped
+                    );
+                }
+            }
         }
     }
 
@@ -358,10 +723,36 @@ impl Browser {
         action: BrowserAction,
         timeout: Timeout,
     ) -> Result<()> {
+        self.current_state = None;
+        self.last_action = Some(action.clone());
         self.actions_sender.send((action, timeout))?;
         Ok(())
     }
 
+    /// Lists the actions that the specification offers in the current state, which is useful
+    /// for debugging why some element isn't acted upon. Only available between receiving a
+    /// state and applying the next action.
+    pub async fn available_actions(
+        &self,
+        exclude_selectors: &[String],
+        verifier: &VerifierWorker,
+    ) -> Result<Vec<ActionCandidate>> {
+        let state = self
+            .current_state
+            .as_ref()
+            .ok_or(anyhow!("browser is not paused in a state"))?;
+        let actions = crate::runner::available_actions(
+            &self.origin,
+            &self.url_scope,
+            exclude_selectors,
+            state,
+            &self.last_action,
+            verifier,
+        )
+        .await?;
+        Ok(crate::runner::action_candidates(&actions))
+    }
+
     pub async fn ensure_script_evaluated(&self, script: &str) -> Result<()> {
         let _ = self.page.evaluate_on_new_document(script).await?;
         let _ = self.page.evaluate(script).await?;
@@ -475,6 +866,7 @@ async fn inner_events(
                 InnerEvent::FrameNavigated(
                     nav.frame.id.clone(),
                     nav.r#type.clone(),
+                    nav.frame.url.clone(),
                 )
             }),
     ) as InnerEventStream;
@@ -548,37 +940,57 @@ async fn inner_events(
             }),
     ) as InnerEventStream;
 
+    let page = context.page.clone();
     let events_console = Box::pin(
         context
             .page
             .event_listener::<runtime::EventConsoleApiCalled>()
             .await?
-            .filter_map(async |call| {
-                let level = match call.r#type {
-                    runtime::ConsoleApiCalledType::Error => {
-                        state::ConsoleEntryLevel::Error
-                    }
-                    runtime::ConsoleApiCalledType::Warning => {
-                        state::ConsoleEntryLevel::Warning
+            .filter_map(move |call| {
+                let page = page.clone();
+                async move {
+                    let level = match call.r#type {
+                        runtime::ConsoleApiCalledType::Error => {
+                            state::ConsoleEntryLevel::Error
+                        }
+                        runtime::ConsoleApiCalledType::Warning => {
+                            state::ConsoleEntryLevel::Warning
+                        }
+                        _ => return None,
+                    };
+
+                    let mut args = Vec::with_capacity(call.args.len());
+                    for arg in &call.args {
+                        args.push(
+                            remote_object_to_json(
+                                &page,
+                                arg,
+                                CONSOLE_ARGUMENT_DEPTH_MAX,
+                            )
+                            .await,
+                        );
                     }
-                    _ => return None,
-                };
 
-                Some(InnerEvent::ConsoleEntry(ConsoleEntry {
-                    timestamp: UNIX_EPOCH
-                        + Duration::from_secs_f64(
-                            *call.timestamp.inner() / 1000.0,
-                        ),
-                    level,
-                    args: call.args.iter().map(remote_object_to_json).collect(),
-                }))
+                    Some(InnerEvent::ConsoleEntry(ConsoleEntry {
+                        timestamp: UNIX_EPOCH
+                            + Duration::from_secs_f64(
+                                *call.timestamp.inner() / 1000.0,
+                            ),
+                        level,
+                        args,
+                    }))
+                }
             }),
     ) as InnerEventStream;
 
-    let events_action_accepted =
-        Box::pin(receiver_to_stream(context.actions_sender.subscribe()).map(
-            |(action, timeout)| InnerEvent::ActionAccepted(action, timeout),
-        ));
+    let events_action_accepted = Box::pin(
+        receiver_to_stream(
+            context.actions_sender.subscribe(),
+            "actions",
+            |_| None,
+        )
+        .map(|(action, timeout)| InnerEvent::ActionAccepted(action, timeout)),
+    );
 
     Ok(Box::pin(stream::select_all(vec![
         events_loaded,
@@ -654,10 +1066,11 @@ async fn process_event(
     use InnerStateKind::*;
     Ok(match (state_current, event) {
         (
-            state @ InnerState { kind: Running, .. },
+            mut state @ InnerState { kind: Running, .. },
             InnerEvent::NodeTreeModified(modification),
         ) => {
             handle_node_modification(context, &modification).await?;
+            record_dom_change(context, &mut state.shared, &modification);
             capture_browser_state(state, context).await?
         }
         (state, InnerEvent::StateRequested(reason, generation)) => {
@@ -680,8 +1093,9 @@ async fn process_event(
                 capture_browser_state(state, context).await?
             }
         }
-        (state, InnerEvent::NodeTreeModified(modification)) => {
+        (mut state, InnerEvent::NodeTreeModified(modification)) => {
             handle_node_modification(context, &modification).await?;
+            record_dom_change(context, &mut state.shared, &modification);
             state
         }
         (
@@ -730,19 +1144,26 @@ async fn process_event(
                 exceptions,
                 generation,
                 screenshot,
+                element_screenshots,
+                dom_changes,
+                probe_timeouts,
+                ..
             } = state.shared;
 
-            let screenshot = screenshot
-                .ok_or(anyhow!("no screenshot available for state capture"))?;
-
-            let browser_state = BrowserState::current(
+            let mut browser_state = BrowserState::current(
                 context.page.clone(),
                 &call_frame_id,
                 console_entries,
                 exceptions,
                 screenshot,
+                context.instrumentation_failures.count(),
+                context.capture_options,
             )
             .await?;
+            browser_state.unresponsive = probe_timeouts >= UNRESPONSIVE_PROBES;
+            browser_state.element_screenshots = element_screenshots;
+            browser_state.dom_changes =
+                context.record_dom_changes.then_some(dom_changes);
 
             context
                 .sender
@@ -767,6 +1188,10 @@ async fn process_event(
                     console_entries: vec![],
                     exceptions: vec![],
                     screenshot: None,
+                    element_screenshots: HashMap::new(),
+                    dom_changes: DomChanges::default(),
+                    navigations: 0,
+                    probe_timeouts,
                 },
             }
         }
@@ -840,30 +1265,47 @@ async fn process_event(
             // throws an uncaught exception blocking the evaluation indefinitely.
             // This gives us a chance to receive the "Debugger.paused" event and
             // resume (extracting the uncaught exception information).
+            let slow_motion = context.slow_motion;
             let action_handle = spawn(async move {
+                if let Some(delay) = slow_motion {
+                    if let Err(error) = browser_action.highlight(&page).await {
+                        log::debug!(
+                            "failed to highlight action target: {}",
+                            error
+                        );
+                    }
+                    sleep(delay).await;
+                    let _ = page
+                        .execute(overlay::HideHighlightParams::default())
+                        .await;
+                }
                 log::debug!("applying: {:?}", browser_action);
-                match browser_action.apply(&page).await {
+                let event = match browser_action.apply(&page).await {
                     Ok(_) => {
                         log::debug!("applied: {:?}", browser_action);
+                        InnerEvent::ActionApplied(shared.generation)
                     }
                     Err(err) => {
                         log::error!(
                             "failed to apply action {:?}: {:?}",
                             browser_action,
                             err
+                        );
+                        InnerEvent::ActionFailed(
+                            shared.generation,
+                            browser_action,
+                            Arc::new(err),
                         )
                     }
-                }
-                if let Err(error) =
-                    sender.send(InnerEvent::ActionApplied(shared.generation))
-                {
-                    log::error!("failed to send ActionApplied: {}", error);
+                };
+                if let Err(error) = sender.send(event) {
+                    log::error!("failed to send action outcome: {}", error);
                 }
             });
 
             let sender = context.inner_events_sender.clone();
             spawn(async move {
-                sleep(timeout).await;
+                sleep(timeout + slow_motion.unwrap_or_default()).await;
                 action_handle.abort();
                 log::debug!(
                     "timeout after {}ms, aborted action, requesting new state",
@@ -880,6 +1322,31 @@ async fn process_event(
                 }
             });
 
+            // Probes until the action's timeout, after which a new state is captured anyway.
+            if let Some(interval) = context.responsiveness_probe {
+                let page = context.page.clone();
+                let sender = context.inner_events_sender.clone();
+                let generation = shared.generation;
+                spawn(async move {
+                    let started = Instant::now();
+                    while started.elapsed() < timeout {
+                        sleep(interval).await;
+                        let responded = tokio::time::timeout(
+                            interval,
+                            page.evaluate_expression("0"),
+                        )
+                        .await
+                        .is_ok();
+                        if sender
+                            .send(InnerEvent::Probed(generation, responded))
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                });
+            }
+
             shared.console_entries.clear();
             InnerState {
                 kind: Acting,
@@ -900,6 +1367,42 @@ async fn process_event(
             log::debug!("ignoring stale ActionApplied");
             state
         }
+        (
+            state @ InnerState { kind: Acting, .. },
+            InnerEvent::ActionFailed(generation, action, error),
+        ) if state.shared.generation == generation => {
+            context
+                .sender
+                .send(BrowserEvent::ActionFailed(action, error))?;
+            // Nothing is going to change in response to the action, so
+            // there's no point in waiting for its timeout.
+            capture_browser_state(state, context).await?
+        }
+        (state, InnerEvent::ActionFailed(..)) => {
+            log::debug!("ignoring stale ActionFailed");
+            state
+        }
+        (mut state, InnerEvent::Probed(generation, responded))
+            if matches!(state.kind, Acting | Running)
+                && state.shared.generation == generation =>
+        {
+            if responded {
+                state.shared.probe_timeouts = 0;
+            } else {
+                state.shared.probe_timeouts += 1;
+                if state.shared.probe_timeouts == UNRESPONSIVE_PROBES {
+                    log::warn!(
+                        "page unresponsive, {} probes in a row timed out",
+                        UNRESPONSIVE_PROBES
+                    );
+                }
+            }
+            state
+        }
+        (state, InnerEvent::Probed(..)) => {
+            log::debug!("ignoring stale or mid-navigation probe");
+            state
+        }
         (InnerState { shared, .. }, InnerEvent::Loaded) => {
             context
                 .inner_events_sender
@@ -913,10 +1416,24 @@ async fn process_event(
             }
         }
         (
-            InnerState { shared, kind },
+            InnerState { mut shared, kind },
             InnerEvent::FrameRequestedNavigation(frame_id, reason, url),
         ) => {
-            if frame_id == context.frame_id {
+            if frame_id == context.frame_id && !context.permits_navigation(&url)
+            {
+                log::info!("cancelling navigation to {} (out of scope)", url);
+                context
+                    .page
+                    .execute(page::StopLoadingParams::default())
+                    .await?;
+                InnerState { shared, kind }
+            } else if frame_id == context.frame_id {
+                shared.navigations += 1;
+                if let Some(threshold) = context.redirect_loop_threshold
+                    && shared.navigations > threshold
+                {
+                    bail!("redirect loop detected at {}", url);
+                }
                 log::debug!(
                     "navigating to {} due to {:?} (current state is {:?}, {})",
                     url,
@@ -958,19 +1475,39 @@ async fn process_event(
                 state
             }
         }
-        (state, InnerEvent::FrameNavigated(frame_id, navigation_type)) => {
-            // Track all nodes.
-            context
-                .page
-                .execute(
-                    dom::GetDocumentParams::builder()
-                        .depth(-1)
-                        .pierce(true)
-                        .build(),
-                )
-                .await?;
-            if frame_id == context.frame_id {
-                let shared = state.shared;
+        (state, InnerEvent::Lagged(skipped)) => {
+            // Dropped events may include node modifications, so the tracked nodes are
+            // re-synced like after navigating.
+            log::debug!(
+                "re-syncing the document after dropping {} events",
+                skipped
+            );
+            track_all_nodes(context).await?;
+            state
+        }
+        (state, InnerEvent::FrameNavigated(frame_id, navigation_type, url)) => {
+            track_all_nodes(context).await?;
+            if frame_id == context.frame_id
+                && !context.permits_navigation(&url)
+                && go_back(context).await?
+            {
+                // Navigations that couldn't be cancelled when requested, like server-side
+                // redirects, are undone right away instead of waiting for the page to load.
+                log::info!("navigated out of scope to {}, going back", url);
+                let mut shared = state.shared;
+                shared.navigations += 1;
+                if let Some(threshold) = context.redirect_loop_threshold
+                    && shared.navigations > threshold
+                {
+                    bail!("redirect loop detected at {}", url);
+                }
+                InnerState {
+                    kind: Navigating,
+                    shared,
+                }
+            } else if frame_id == context.frame_id {
+                let mut shared = state.shared;
+                shared.probe_timeouts = 0;
                 let kind = match navigation_type {
                     NavigationType::Navigation => Loading,
                     // Navigating history with bfcache doesn't yield a "loaded"
@@ -990,6 +1527,17 @@ async fn process_event(
                 state
             }
         }
+        (state, InnerEvent::NavigationFailed(url, error)) => {
+            if error.contains("ERR_CERT_") {
+                bail!(
+                    "certificate error navigating to {}: {} (certificate errors can be ignored for test environments with untrusted certificates)",
+                    url,
+                    error
+                );
+            }
+            log::warn!("navigating to {} failed: {}", url, error);
+            state
+        }
         (state, InnerEvent::TargetDestroyed(target_id)) => {
             if target_id == *context.page.target_id() {
                 bail!("page target {:?} was destroyed", target_id);
@@ -1003,33 +1551,140 @@ async fn process_event(
     })
 }
 
+/// Navigates to the previous history entry, returning whether there was one.
+async fn go_back(context: &BrowserContext) -> Result<bool> {
+    let history = context
+        .page
+        .execute(page::GetNavigationHistoryParams {})
+        .await?;
+    if history.current_index == 0 {
+        return Ok(false);
+    }
+    let previous = &history.entries[(history.current_index - 1) as usize];
+    context
+        .page
+        .execute(
+            page::NavigateToHistoryEntryParams::builder()
+                .entry_id(previous.id)
+                .build()
+                .map_err(|err| anyhow!(err))?,
+        )
+        .await?;
+    Ok(true)
+}
+
+/// Takes a screenshot of the first element matching the selector, if there's one with a size.
+async fn capture_element_screenshot(
+    page: &Page,
+    selector: &str,
+    format: ScreenshotFormat,
+) -> Result<Option<Screenshot>> {
+    #[derive(serde::Deserialize)]
+    struct Rect {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    }
+    let rect: Option<Rect> = page
+        .evaluate(format!(
+            "(() => {{
+                const rect = document.querySelector({})?.getBoundingClientRect();
+                return rect && rect.width > 0 && rect.height > 0
+                    ? {{ x: rect.x + window.scrollX, y: rect.y + window.scrollY, width: rect.width, height: rect.height }}
+                    : null;
+            }})()",
+            json::to_string(selector)?
+        ))
+        .await?
+        .into_value()?;
+    let Some(rect) = rect else {
+        return Ok(None);
+    };
+    let data = page
+        .screenshot(
+            ScreenshotParams::builder()
+                .format(format)
+                .clip(page::Viewport {
+                    x: rect.x,
+                    y: rect.y,
+                    width: rect.width,
+                    height: rect.height,
+                    scale: 1.0,
+                })
+                .capture_beyond_viewport(true)
+                .build(),
+        )
+        .await?;
+    Ok(Some(Screenshot { data, format }))
+}
+
 async fn capture_browser_state(
     mut state: InnerState,
     context: &BrowserContext,
 ) -> Result<InnerState> {
     log::debug!("pausing, going into next generation...");
 
+    if let Some(readiness) = &context.readiness {
+        wait_for_readiness(&context.page, readiness).await;
+    }
+
     log::debug!("taking screenshot before pause");
     let format = ScreenshotFormat::Webp;
-    let screenshot = Screenshot {
-        data: context
-            .page
-            .screenshot(
-                ScreenshotParams::builder()
-                    .omit_background(true)
-                    .format(format)
-                    .build(),
+    let data = skip_timeout(
+        with_timeout(
+            context.capture_options.cdp_timeout,
+            "taking screenshot",
+            async {
+                context
+                    .page
+                    .screenshot(
+                        ScreenshotParams::builder()
+                            .omit_background(true)
+                            .format(format)
+                            .build(),
+                    )
+                    .await
+                    .context("take screenshot before pause")
+            },
+        )
+        .await,
+    )?;
+    state.shared.screenshot = data.map(|data| Screenshot { data, format });
+
+    for selector in &context.element_screenshots {
+        match skip_timeout(
+            with_timeout(
+                context.capture_options.cdp_timeout,
+                "taking element screenshot",
+                capture_element_screenshot(&context.page, selector, format),
             )
-            .await
-            .context("take screenshot before pause")?,
-        format,
-    };
-    state.shared.screenshot = Some(screenshot);
+            .await,
+        ) {
+            Ok(Some(Some(screenshot))) => {
+                state
+                    .shared
+                    .element_screenshots
+                    .insert(selector.clone(), screenshot);
+            }
+            Ok(_) => {}
+            Err(error) => {
+                log::warn!(
+                    "failed taking screenshot of {}: {}",
+                    selector,
+                    error
+                );
+            }
+        }
+    }
 
-    context
-        .page
-        .execute(debugger::PauseParams::default())
-        .await?;
+    with_timeout(context.capture_options.cdp_timeout, "pausing", async {
+        Ok(context
+            .page
+            .execute(debugger::PauseParams::default())
+            .await?)
+    })
+    .await?;
     let page = context.page.clone();
     spawn(async move {
         let _ = page.evaluate_expression("void 0").await;
@@ -1042,6 +1697,123 @@ async fn capture_browser_state(
     })
 }
 
+/// How often the readiness expression is evaluated while waiting for it.
+const READINESS_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Waits until the readiness expression is truthy, or its timeout passes, in which case the
+/// state is captured anyway. Errors evaluating the expression count as not ready.
+async fn wait_for_readiness(page: &Page, readiness: &Readiness) {
+    let deadline = Instant::now() + readiness.timeout;
+    let expression = format!("Boolean(({}))", readiness.expression);
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match tokio::time::timeout(
+            remaining,
+            page.evaluate(expression.as_str()),
+        )
+        .await
+        {
+            Ok(Ok(result)) => match result.into_value::<bool>() {
+                Ok(true) => return,
+                Ok(false) => {}
+                Err(error) => {
+                    log::debug!("readiness expression has no value: {}", error)
+                }
+            },
+            Ok(Err(error)) => {
+                log::debug!("failed evaluating readiness expression: {}", error)
+            }
+            Err(_) => {}
+        }
+        if Instant::now() + READINESS_POLL_INTERVAL >= deadline {
+            log::warn!(
+                "page not ready within {:?} ({}), capturing the state anyway",
+                readiness.timeout,
+                readiness.expression
+            );
+            return;
+        }
+        sleep(READINESS_POLL_INTERVAL).await;
+    }
+}
+
+fn record_dom_change(
+    context: &BrowserContext,
+    shared: &mut InnerStateShared,
+    modification: &NodeModification,
+) {
+    if !context.record_dom_changes {
+        return;
+    }
+    let change = match modification {
+        NodeModification::ChildNodeInserted { parent, child } => {
+            DomChange::Inserted {
+                parent: *parent.inner(),
+                node: *child.node_id.inner(),
+                description: describe_node(child),
+            }
+        }
+        NodeModification::ChildNodeRemoved { parent, child } => {
+            DomChange::Removed {
+                parent: *parent.inner(),
+                node: *child.inner(),
+            }
+        }
+        NodeModification::AttributeModified { node, name, value } => {
+            DomChange::AttributeModified {
+                node: *node.inner(),
+                name: name.clone(),
+                value: value.clone(),
+            }
+        }
+        NodeModification::ChildNodeCountUpdated { .. } => return,
+    };
+    shared.dom_changes.push(change);
+}
+
+/// Describes a node briefly, e.g. `<li class="item">` for elements and `"some text"` for text.
+fn describe_node(node: &dom::Node) -> String {
+    const TEXT_LENGTH_MAX: usize = 80;
+    match node.node_type {
+        // Text nodes.
+        3 => {
+            let text: String =
+                node.node_value.chars().take(TEXT_LENGTH_MAX).collect();
+            format!("{:?}", text)
+        }
+        // Elements, with attributes as a flat list of names and values.
+        1 => {
+            let mut description = format!("<{}", node.local_name);
+            for pair in node.attributes.as_deref().unwrap_or(&[]).chunks(2) {
+                match pair {
+                    [name, value] => {
+                        description.push_str(&format!(" {}={:?}", name, value))
+                    }
+                    [name] => description.push_str(&format!(" {}", name)),
+                    _ => {}
+                }
+            }
+            description.push('>');
+            description
+        }
+        _ => node.node_name.clone(),
+    }
+}
+
+/// Requests the whole document, so that the browser reports modifications of all of its nodes.
+async fn track_all_nodes(context: &BrowserContext) -> Result<()> {
+    context
+        .page
+        .execute(
+            dom::GetDocumentParams::builder()
+                .depth(-1)
+                .pierce(true)
+                .build(),
+        )
+        .await?;
+    Ok(())
+}
+
 async fn handle_node_modification(
     context: &BrowserContext,
     modification: &NodeModification,
@@ -1065,13 +1837,60 @@ async fn handle_node_modification(
     Ok(())
 }
 
+/// Streams the messages of a broadcast channel, logging those dropped when the stream lags
+/// behind the channel's capacity, and then streaming what `on_lagged` returns for them.
 fn receiver_to_stream<T: Clone + Send + 'static>(
     receiver: Receiver<T>,
+    name: &'static str,
+    on_lagged: fn(u64) -> Option<T>,
 ) -> Pin<Box<dyn stream::Stream<Item = T> + Send>> {
-    Box::pin(BroadcastStream::new(receiver).filter_map(async |r| r.ok()))
+    Box::pin(BroadcastStream::new(receiver).filter_map(move |result| {
+        std::future::ready(match result {
+            Ok(message) => Some(message),
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                log::warn!(
+                    "dropped {} {} as they came faster than they were handled",
+                    skipped,
+                    name
+                );
+                on_lagged(skipped)
+            }
+        })
+    }))
 }
 
-fn remote_object_to_json(object: &runtime::RemoteObject) -> json::Value {
+/// How many browser events are buffered for the runner, which may be a few behind when an
+/// action fails and a state is captured right after.
+const BROWSER_EVENTS_CAPACITY: usize = 16;
+
+/// How deep objects and arrays logged to the console are materialized as JSON, bounding the
+/// size of console entries (and avoiding cycles). Deeper values are described as strings.
+const CONSOLE_ARGUMENT_DEPTH_MAX: usize = 4;
+
+async fn remote_object_to_json(
+    page: &Page,
+    object: &runtime::RemoteObject,
+    depth: usize,
+) -> json::Value {
+    let is_array = object.subtype == Some(runtime::RemoteObjectSubtype::Array);
+    // Other subtypes, like errors and DOM nodes, are better off described.
+    if object.value.is_none()
+        && object.r#type == runtime::RemoteObjectType::Object
+        && (object.subtype.is_none() || is_array)
+        && depth > 0
+        && let Some(object_id) = &object.object_id
+    {
+        match Box::pin(materialize_remote_object(
+            page, object_id, is_array, depth,
+        ))
+        .await
+        {
+            Ok(value) => return value,
+            Err(error) => {
+                log::debug!("failed to materialize remote object: {}", error)
+            }
+        }
+    }
     match (&object.r#type, &object.value, &object.description) {
         (_, Some(value), _) => value.clone(),
         (_, None, Some(description)) => {
@@ -1083,48 +1902,135 @@ fn remote_object_to_json(object: &runtime::RemoteObject) -> json::Value {
     }
 }
 
+async fn materialize_remote_object(
+    page: &Page,
+    object_id: &runtime::RemoteObjectId,
+    is_array: bool,
+    depth: usize,
+) -> Result<json::Value> {
+    let properties = page
+        .execute(
+            runtime::GetPropertiesParams::builder()
+                .object_id(object_id.clone())
+                .own_properties(true)
+                .build()
+                .map_err(|err| anyhow!(err))?,
+        )
+        .await?;
+    let values = properties
+        .result
+        .result
+        .iter()
+        .filter(|property| property.symbol.is_none())
+        .filter_map(|property| {
+            property.value.as_ref().map(|value| (&property.name, value))
+        });
+    if is_array {
+        let mut elements = vec![];
+        for (name, value) in values {
+            if name.parse::<usize>().is_ok() {
+                elements
+                    .push(remote_object_to_json(page, value, depth - 1).await);
+            }
+        }
+        Ok(json::Value::Array(elements))
+    } else {
+        let mut fields = json::Map::new();
+        for (name, value) in values {
+            if name != "__proto__" {
+                fields.insert(
+                    name.clone(),
+                    remote_object_to_json(page, value, depth - 1).await,
+                );
+            }
+        }
+        Ok(json::Value::Object(fields))
+    }
+}
+
+/// Builds the browser configuration, along with a description of the command it launches (the
+/// resolved executable and our arguments, on top of chromiumoxide's defaults) for errors.
 fn launch_options_to_config(
     launch_options: &LaunchOptions,
-    emulation: &Emulation,
-) -> Result<BrowserConfig> {
+    browser_options: &BrowserOptions,
+) -> Result<(BrowserConfig, String)> {
+    let emulation = &browser_options.emulation;
     let crash_dumps_dir = TempDir::new()?;
+    let executable = match &launch_options.executable {
+        Some(executable) => {
+            if !executable.is_file() {
+                bail!(
+                    "browser executable {} does not exist",
+                    executable.display()
+                );
+            }
+            executable.clone()
+        }
+        None => {
+            chromiumoxide::detection::default_executable(Default::default())
+                .map_err(|s| anyhow!(s))?
+        }
+    };
+    let mut args = vec![];
+    if launch_options.no_sandbox {
+        args.extend([
+            "--disable-setuid-sandbox".to_string(),
+            "--disable-dev-shm-usage".to_string(),
+        ]);
+    }
+    args.extend([
+        format!(
+            "--crash-dumps-dir={}",
+            crash_dumps_dir
+                .path()
+                .to_path_buf()
+                .to_str()
+                .expect("invalid tmp dir path")
+        ),
+        "--no-crashpad".to_string(),
+        "--disable-background-networking".to_string(),
+        "--disable-component-update".to_string(),
+        "--disable-domain-reliability".to_string(),
+        "--no-pings".to_string(),
+        "--disable-crash-reporter".to_string(),
+    ]);
+    if browser_options.ignore_certificate_errors {
+        args.push("--ignore-certificate-errors".to_string());
+    }
+    args.extend(launch_options.extra_args.iter().cloned());
     let apply_sandbox =
         |builder: BrowserConfigBuilder| -> BrowserConfigBuilder {
             if launch_options.no_sandbox {
-                builder.no_sandbox().args([
-                    "--disable-setuid-sandbox",
-                    "--disable-dev-shm-usage",
-                ])
+                builder.no_sandbox()
             } else {
                 builder
             }
         };
-    apply_sandbox(BrowserConfig::builder())
-        .headless_mode(if launch_options.headless {
-            HeadlessMode::New
-        } else {
-            HeadlessMode::False
-        })
-        .window_size(emulation.width as u32, emulation.height as u32)
-        .user_data_dir(launch_options.user_data_directory.clone())
-        .args([
-            &format!(
-                "--crash-dumps-dir={}",
-                crash_dumps_dir
-                    .path()
-                    .to_path_buf()
-                    .to_str()
-                    .expect("invalid tmp dir path")
-            ),
-            "--no-crashpad",
-            "--disable-background-networking",
-            "--disable-component-update",
-            "--disable-domain-reliability",
-            "--no-pings",
-            "--disable-crash-reporter",
-        ])
-        .build()
-        .map_err(|s| anyhow!(s))
+    let apply_certificate_errors =
+        |builder: BrowserConfigBuilder| -> BrowserConfigBuilder {
+            if browser_options.ignore_certificate_errors {
+                builder
+            } else {
+                builder.respect_https_errors()
+            }
+        };
+    let config =
+        apply_certificate_errors(apply_sandbox(BrowserConfig::builder()))
+            .chrome_executable(&executable)
+            .headless_mode(if launch_options.headless {
+                HeadlessMode::New
+            } else {
+                HeadlessMode::False
+            })
+            .window_size(emulation.width as u32, emulation.height as u32)
+            .user_data_dir(launch_options.user_data_directory.clone())
+            .args(&args)
+            .build()
+            .map_err(|s| anyhow!(s))?;
+    Ok((
+        config,
+        format!("{} {}", executable.display(), args.join(" ")),
+    ))
 }
 
 async fn find_page(browser: &mut chromiumoxide::Browser) -> Result<Page> {
@@ -2,14 +2,19 @@ use std::time::Duration;
 
 use anyhow::{Result, anyhow, bail};
 use chromiumoxide::Page;
-use chromiumoxide::cdp::browser_protocol::{input, page};
-use serde::Serialize;
+use chromiumoxide::cdp::browser_protocol::{dom, input, overlay, page};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
 use tokio::time::sleep;
 
 use crate::browser::keys::key_name;
 use crate::geometry::Point;
 
-#[derive(Clone, Debug, Serialize)]
+/// Serialized externally tagged, e.g. `"Back"` or `{ "TypeText": { "text": "hi",
+/// "delay_millis": 50 } }`, with durations in whole milliseconds. Traces and action files
+/// depend on this representation, which a snapshot test guards.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum BrowserAction {
     Back,
     Forward,
@@ -17,10 +22,16 @@ pub enum BrowserAction {
         name: String,
         content: Option<String>,
         point: Point,
+        /// A CSS selector for the clicked element. When it resolves at the time of clicking,
+        /// it's preferred over `point`, which can go stale when the layout changes.
+        selector: Option<String>,
     },
     TypeText {
         text: String,
         delay_millis: u64,
+        /// Randomizes the delay before each character, when set.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        jitter: Option<TypingJitter>,
     },
     PressKey {
         code: u8,
@@ -34,9 +45,94 @@ pub enum BrowserAction {
         distance: f64,
     },
     Reload,
+    /// Does nothing, letting the page run until the action's timeout, e.g. while waiting for it
+    /// to become ready.
+    Wait,
+    /// Applies the actions in order as a single step, without capturing states in between, e.g.
+    /// filling in a form and submitting it.
+    Sequence(Vec<BrowserAction>),
+}
+
+/// Randomized delays between typed characters, drawn uniformly between the action's
+/// `delay_millis` and `max_delay_millis` by a generator seeded with `seed`. The delays only
+/// depend on the action, so a replayed action types with the same timing.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TypingJitter {
+    pub max_delay_millis: u64,
+    pub seed: u64,
+}
+
+/// The delay before each of `count` characters typed with the given delay and jitter.
+pub fn typing_delays(
+    delay_millis: u64,
+    jitter: Option<&TypingJitter>,
+    count: usize,
+) -> Vec<Duration> {
+    match jitter {
+        Some(jitter) => {
+            let mut rng = ChaCha8Rng::seed_from_u64(jitter.seed);
+            (0..count)
+                .map(|_| {
+                    Duration::from_millis(rng.random_range(
+                        delay_millis
+                            ..=jitter.max_delay_millis.max(delay_millis),
+                    ))
+                })
+                .collect()
+        }
+        None => vec![Duration::from_millis(delay_millis); count],
+    }
 }
 
 impl BrowserAction {
+    /// The point in the viewport that the action targets, if any.
+    pub fn target_point(&self) -> Option<Point> {
+        match self {
+            BrowserAction::Click { point, .. } => Some(*point),
+            BrowserAction::ScrollUp { origin, .. }
+            | BrowserAction::ScrollDown { origin, .. } => Some(*origin),
+            BrowserAction::Sequence(actions) => {
+                actions.iter().find_map(BrowserAction::target_point)
+            }
+            _ => None,
+        }
+    }
+
+    /// Highlights the element at the action's target point using the DevTools overlay, which
+    /// requires the overlay domain to be enabled. The highlight stays until hidden with
+    /// `Overlay.hideHighlight`.
+    pub async fn highlight(&self, page: &Page) -> Result<()> {
+        let Some(point) = self.target_point() else {
+            return Ok(());
+        };
+        let node = page
+            .execute(dom::GetNodeForLocationParams::new(
+                point.x as i64,
+                point.y as i64,
+            ))
+            .await?;
+        let color = |alpha| dom::Rgba {
+            r: 255,
+            g: 64,
+            b: 64,
+            a: Some(alpha),
+        };
+        page.execute(
+            overlay::HighlightNodeParams::builder()
+                .highlight_config(
+                    overlay::HighlightConfig::builder()
+                        .content_color(color(0.3))
+                        .border_color(color(1.0))
+                        .build(),
+                )
+                .backend_node_id(node.result.backend_node_id)
+                .build()
+                .map_err(|err| anyhow!(err))?,
+        )
+        .await?;
+        Ok(())
+    }
+
     pub async fn apply(&self, page: &Page) -> Result<()> {
         match self {
             BrowserAction::Back => {
@@ -76,6 +172,12 @@ impl BrowserAction {
             BrowserAction::Reload => {
                 page.reload().await?;
             }
+            BrowserAction::Wait => {}
+            BrowserAction::Sequence(actions) => {
+                for action in actions {
+                    Box::pin(action.apply(page)).await?;
+                }
+            }
             BrowserAction::ScrollUp { origin, distance } => {
                 page.execute(
                     input::SynthesizeScrollGestureParams::builder()
@@ -100,12 +202,47 @@ impl BrowserAction {
                 )
                 .await?;
             }
-            BrowserAction::Click { point, .. } => {
-                page.click((*point).into()).await?;
+            BrowserAction::Click {
+                point, selector, ..
+            } => {
+                let point = match selector {
+                    Some(selector) => {
+                        match resolve_selector(page, selector).await {
+                            Ok(Some(resolved)) => resolved,
+                            Ok(None) => {
+                                log::debug!(
+                                    "selector {:?} didn't resolve, clicking at {:?}",
+                                    selector,
+                                    point
+                                );
+                                *point
+                            }
+                            Err(error) => {
+                                log::debug!(
+                                    "failed to resolve selector {:?}, clicking at {:?}: {}",
+                                    selector,
+                                    point,
+                                    error
+                                );
+                                *point
+                            }
+                        }
+                    }
+                    None => *point,
+                };
+                page.click(point.into()).await?;
             }
-            BrowserAction::TypeText { text, delay_millis } => {
-                let delay = Duration::from_millis(*delay_millis);
-                for char in text.chars() {
+            BrowserAction::TypeText {
+                text,
+                delay_millis,
+                jitter,
+            } => {
+                let delays = typing_delays(
+                    *delay_millis,
+                    jitter.as_ref(),
+                    text.chars().count(),
+                );
+                for (char, delay) in text.chars().zip(delays) {
                     sleep(delay).await;
                     page.execute(input::InsertTextParams::new(char)).await?;
                 }
@@ -140,3 +277,129 @@ impl BrowserAction {
         Ok(())
     }
 }
+
+/// Finds the center of the content box of the first element matching the selector in the main
+/// frame's document.
+async fn resolve_selector(
+    page: &Page,
+    selector: &str,
+) -> Result<Option<Point>> {
+    let document = page.execute(dom::GetDocumentParams::default()).await?;
+    let node = page
+        .execute(dom::QuerySelectorParams::new(
+            document.result.root.node_id,
+            selector,
+        ))
+        .await?;
+    // A zero node id means that nothing matched.
+    if *node.result.node_id.inner() == 0 {
+        return Ok(None);
+    }
+    let box_model = page
+        .execute(
+            dom::GetBoxModelParams::builder()
+                .node_id(node.result.node_id)
+                .build(),
+        )
+        .await?;
+    Ok(Point::center_of_quad(
+        box_model.result.model.content.inner(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_snapshot;
+    use serde_json as json;
+
+    fn all_actions() -> Vec<BrowserAction> {
+        let point = Point { x: 12.5, y: 40.0 };
+        vec![
+            BrowserAction::Back,
+            BrowserAction::Forward,
+            BrowserAction::Click {
+                name: "BUTTON".to_string(),
+                content: Some("Submit".to_string()),
+                point,
+                selector: Some("#submit".to_string()),
+            },
+            BrowserAction::Click {
+                name: "A".to_string(),
+                content: None,
+                point,
+                selector: None,
+            },
+            BrowserAction::TypeText {
+                text: "hello".to_string(),
+                delay_millis: 50,
+                jitter: None,
+            },
+            BrowserAction::TypeText {
+                text: "hello".to_string(),
+                delay_millis: 20,
+                jitter: Some(TypingJitter {
+                    max_delay_millis: 300,
+                    seed: 7,
+                }),
+            },
+            BrowserAction::PressKey { code: 13 },
+            BrowserAction::ScrollUp {
+                origin: point,
+                distance: 100.0,
+            },
+            BrowserAction::ScrollDown {
+                origin: point,
+                distance: 250.5,
+            },
+            BrowserAction::Reload,
+            BrowserAction::Wait,
+            BrowserAction::Sequence(vec![
+                BrowserAction::TypeText {
+                    text: "hello".to_string(),
+                    delay_millis: 50,
+                    jitter: None,
+                },
+                BrowserAction::PressKey { code: 13 },
+            ]),
+        ]
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for action in all_actions() {
+            let serialized = json::to_string(&action).unwrap();
+            let deserialized: BrowserAction =
+                json::from_str(&serialized).unwrap();
+            assert_eq!(deserialized, action, "{}", serialized);
+        }
+    }
+
+    #[test]
+    fn test_representation() {
+        let lines: Vec<String> = all_actions()
+            .iter()
+            .map(|action| json::to_string(action).unwrap())
+            .collect();
+        assert_snapshot!(lines.join("\n"));
+    }
+
+    #[test]
+    fn test_typing_delays() {
+        assert_eq!(
+            typing_delays(50, None, 3),
+            vec![Duration::from_millis(50); 3]
+        );
+        let jitter = TypingJitter {
+            max_delay_millis: 300,
+            seed: 7,
+        };
+        let delays = typing_delays(20, Some(&jitter), 100);
+        assert!(delays.iter().all(|delay| {
+            (Duration::from_millis(20)..=Duration::from_millis(300))
+                .contains(delay)
+        }));
+        assert!(delays.iter().any(|delay| *delay != delays[0]));
+        assert_eq!(typing_delays(20, Some(&jitter), 100), delays);
+    }
+}
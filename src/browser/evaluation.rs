@@ -8,6 +8,56 @@ use chromiumoxide::{
 };
 use serde::de::DeserializeOwned;
 use serde_json as json;
+use std::time::Duration;
+
+/// A CDP interaction that didn't complete in time, e.g. because the browser is wedged.
+#[derive(Debug)]
+pub struct CdpTimeout {
+    pub what: String,
+    pub timeout: Duration,
+}
+
+impl std::fmt::Display for CdpTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "timed out after {:?} {}", self.timeout, self.what)
+    }
+}
+
+impl std::error::Error for CdpTimeout {}
+
+/// Fails with a `CdpTimeout` when the CDP interaction doesn't complete within the timeout, if
+/// there is one.
+pub async fn with_timeout<Output>(
+    timeout: Option<Duration>,
+    what: &str,
+    interaction: impl Future<Output = Result<Output>>,
+) -> Result<Output> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, interaction)
+            .await
+            .map_err(|_| {
+                anyhow!(CdpTimeout {
+                    what: what.to_string(),
+                    timeout,
+                })
+            })?,
+        None => interaction.await,
+    }
+}
+
+/// Turns a `CdpTimeout` into `None`, for interactions that can be skipped.
+pub fn skip_timeout<Output>(result: Result<Output>) -> Result<Option<Output>> {
+    match result {
+        Ok(output) => Ok(Some(output)),
+        Err(error) => match error.downcast_ref::<CdpTimeout>() {
+            Some(timeout) => {
+                log::warn!("skipping: {}", timeout);
+                Ok(None)
+            }
+            None => Err(error),
+        },
+    }
+}
 
 pub async fn evaluate_expression_in_debugger<Output: DeserializeOwned>(
     page: &Page,
@@ -81,3 +131,26 @@ pub async fn evaluate_function_call_in_debugger<Output: DeserializeOwned>(
 
     evaluate_expression_in_debugger(page, call_frame_id, expression).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_skip_timeout() {
+        let result =
+            with_timeout(Some(Duration::from_millis(10)), "sleeping", async {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                Ok(())
+            })
+            .await;
+        assert!(matches!(skip_timeout(result), Ok(None)));
+
+        let result: Result<()> =
+            with_timeout(Some(Duration::from_secs(10)), "failing", async {
+                bail!("failed")
+            })
+            .await;
+        assert!(skip_timeout(result).is_err());
+    }
+}
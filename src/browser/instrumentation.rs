@@ -7,21 +7,221 @@ use chromiumoxide::cdp::browser_protocol::network;
 use futures::StreamExt;
 use log;
 use oxc::span::SourceType;
+use serde::Serialize;
 use serde_json as json;
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use tokio::spawn;
+use tokio::sync::broadcast::Sender;
 
+use crate::browser::BrowserEvent;
 use crate::instrumentation;
 use crate::instrumentation::InstrumentationConfig;
-use crate::instrumentation::source_id::SourceId;
+use crate::instrumentation::js::{
+    Banner, InstrumentationError, InstrumentationResult,
+};
+use crate::instrumentation::source_id::{SourceId, SourceIdStrategy};
+use crate::url::UrlPattern;
 
+/// Rules applied to outgoing requests before they reach the network, e.g. to
+/// isolate the frontend from analytics or backend services.
+#[derive(Clone, Debug)]
+pub enum RequestRule {
+    /// Fail matching requests as if they were blocked by the client.
+    BlockUrls(Vec<UrlPattern>),
+    /// Fulfill matching requests with a canned JSON response.
+    StubUrls(Vec<(UrlPattern, StubResponse)>),
+}
+
+#[derive(Clone, Debug)]
+pub struct StubResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+enum RequestRuleMatch<'a> {
+    Block,
+    Stub(&'a StubResponse),
+}
+
+fn match_request_rules<'a>(
+    rules: &'a [RequestRule],
+    url: &str,
+) -> Option<RequestRuleMatch<'a>> {
+    rules.iter().find_map(|rule| match rule {
+        RequestRule::BlockUrls(patterns) => patterns
+            .iter()
+            .any(|pattern| pattern.matches(url))
+            .then_some(RequestRuleMatch::Block),
+        RequestRule::StubUrls(stubs) => stubs
+            .iter()
+            .find(|(pattern, _)| pattern.matches(url))
+            .map(|(_, response)| RequestRuleMatch::Stub(response)),
+    })
+}
+
+/// Counts the scripts and documents that couldn't be instrumented and were passed through as-is,
+/// so their coverage is missing, by reason.
+#[derive(Debug, Default)]
+pub struct InstrumentationFailures {
+    parse: AtomicUsize,
+    semantic: AtomicUsize,
+    other: AtomicUsize,
+}
+
+impl InstrumentationFailures {
+    pub fn count(&self) -> usize {
+        self.parse.load(Ordering::Relaxed)
+            + self.semantic.load(Ordering::Relaxed)
+            + self.other.load(Ordering::Relaxed)
+    }
+
+    fn record(&self, error: &anyhow::Error) {
+        let counter = match error.downcast_ref::<InstrumentationError>() {
+            Some(InstrumentationError::ParseErrors(_)) => &self.parse,
+            Some(InstrumentationError::SemanticErrors(_)) => &self.semantic,
+            None => &self.other,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// How much of the app's code was instrumented over a run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CoverageStats {
+    /// Unique scripts and documents instrumented.
+    pub files: usize,
+    /// Unique scripts and documents that opted out of instrumentation with the
+    /// `bombadil-disable-instrumentation` comment, which aren't counted in `files` or the byte
+    /// totals.
+    pub skipped: usize,
+    /// Their total size in bytes as served.
+    pub original_bytes: usize,
+    /// Their total size in bytes with instrumentation added.
+    pub instrumented_bytes: usize,
+    /// Scripts that failed to parse, and weren't instrumented.
+    pub parse_failures: usize,
+    /// Scripts with semantic errors (e.g. redeclared variables), which weren't instrumented.
+    pub semantic_failures: usize,
+    /// Scripts and documents that weren't instrumented for other reasons, e.g. bodies that
+    /// couldn't be fetched.
+    pub other_failures: usize,
+}
+
+impl CoverageStats {
+    pub fn collect(
+        files: &InstrumentedFiles,
+        failures: &InstrumentationFailures,
+    ) -> Self {
+        let skipped = files.skipped();
+        let files = files.list();
+        CoverageStats {
+            files: files.len(),
+            skipped,
+            original_bytes: files.iter().map(|file| file.size).sum(),
+            instrumented_bytes: files
+                .iter()
+                .map(|file| file.instrumented_size)
+                .sum(),
+            parse_failures: failures.parse.load(Ordering::Relaxed),
+            semantic_failures: failures.semantic.load(Ordering::Relaxed),
+            other_failures: failures.other.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A script or document instrumented for coverage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstrumentedFile {
+    pub source_id: u64,
+    pub url: String,
+    /// Size in bytes as served.
+    pub size: usize,
+    /// Size in bytes with instrumentation added.
+    pub instrumented_size: usize,
+}
+
+/// The unique scripts and documents instrumented so far, in the order they were first seen, to
+/// show which sources are covered, e.g. that a lazily loaded chunk is. Sources that opted out of
+/// instrumentation are only counted.
+#[derive(Debug, Default)]
+pub struct InstrumentedFiles(
+    Mutex<(HashSet<u64>, Vec<InstrumentedFile>, HashSet<u64>)>,
+);
+
+impl InstrumentedFiles {
+    /// Records an instrumented file, returning whether its source is new.
+    pub fn record(&self, file: InstrumentedFile) -> bool {
+        let mut files =
+            self.0.lock().expect("instrumented files lock poisoned");
+        let (seen, list, _) = &mut *files;
+        if !seen.insert(file.source_id) {
+            return false;
+        }
+        list.push(file);
+        true
+    }
+
+    /// Records a source that opted out of instrumentation, returning whether it's new.
+    pub fn record_skipped(&self, source_id: u64) -> bool {
+        self.0
+            .lock()
+            .expect("instrumented files lock poisoned")
+            .2
+            .insert(source_id)
+    }
+
+    pub fn list(&self) -> Vec<InstrumentedFile> {
+        self.0
+            .lock()
+            .expect("instrumented files lock poisoned")
+            .1
+            .clone()
+    }
+
+    /// How many unique sources opted out of instrumentation.
+    pub fn skipped(&self) -> usize {
+        self.0
+            .lock()
+            .expect("instrumented files lock poisoned")
+            .2
+            .len()
+    }
+}
+
+/// Intercepts scripts and documents to instrument them for coverage. Failures are counted in
+/// `failures`, and if `strict_errors` is set, also sent to it as errors failing the test. Scripts
+/// larger than `max_script_size` bytes are passed through as-is, to bound memory use. Newly
+/// instrumented sources, and those opting out, are recorded in `files`.
 pub async fn instrument_js_coverage(
     page: Arc<Page>,
     config: InstrumentationConfig,
+    request_rules: Vec<RequestRule>,
+    max_script_size: Option<usize>,
+    failures: Arc<InstrumentationFailures>,
+    files: Arc<InstrumentedFiles>,
+    strict_errors: Option<Sender<BrowserEvent>>,
 ) -> Result<()> {
+    let request_stage_patterns: Vec<fetch::RequestPattern> = request_rules
+        .iter()
+        .flat_map(|rule| match rule {
+            RequestRule::BlockUrls(patterns) => patterns.clone(),
+            RequestRule::StubUrls(stubs) => {
+                stubs.iter().map(|(pattern, _)| pattern.clone()).collect()
+            }
+        })
+        .map(|pattern| {
+            fetch::RequestPattern::builder()
+                .url_pattern(pattern.as_str())
+                .request_stage(fetch::RequestStage::Request)
+                .build()
+        })
+        .collect();
+
     page.execute(
         fetch::EnableParams::builder()
+            .patterns(request_stage_patterns)
             .pattern(
                 fetch::RequestPattern::builder()
                     .request_stage(fetch::RequestStage::Response)
@@ -44,25 +244,34 @@ pub async fn instrument_js_coverage(
     let _handle = spawn(async move {
         let intercept =
             async |event: &fetch::EventRequestPaused| -> Result<()> {
+                // Request-stage interceptions have no response yet, and only
+                // happen for URLs matching the request rules.
+                if event.response_status_code.is_none()
+                    && event.response_error_reason.is_none()
+                {
+                    return apply_request_rules(&page, &request_rules, event)
+                        .await;
+                }
+
                 // Any non-200 upstream response is forwarded as-is.
                 if let Some(status) = event.response_status_code
                     && status != 200
                 {
-                    return page
-                        .execute(
-                            fetch::ContinueRequestParams::builder()
-                                .request_id(event.request_id.clone())
-                                .build()
-                                .map_err(|error| {
-                                    anyhow!(
-                                    "failed building ContinueRequestParams: {}",
-                                    error
-                                )
-                                })?,
-                        )
-                        .await
-                        .map(|_| ())
-                        .context("failed continuing request");
+                    return continue_request(&page, event).await;
+                }
+
+                let too_large = |size: usize| {
+                    event.resource_type == network::ResourceType::Script
+                        && max_script_size.is_some_and(|limit| size > limit)
+                };
+                // Checked before fetching the body too, so that it's never buffered when the
+                // size is known upfront.
+                if let Some(size) =
+                    event.response_headers.as_deref().and_then(content_length)
+                    && too_large(size)
+                {
+                    log_too_large(&event.request.url, size);
+                    return continue_request(&page, event).await;
                 }
 
                 let headers: HashMap<String, String> =
@@ -89,8 +298,19 @@ pub async fn instrument_js_coverage(
                 } else {
                     body_response.body.clone()
                 };
+                if too_large(body.len()) {
+                    log_too_large(&event.request.url, body.len());
+                    return continue_request(&page, event).await;
+                }
 
-                let source_id = source_id(headers, &body);
+                let source_type =
+                    script_source_type(&event.request.url, &headers);
+                let source_id = source_id(
+                    config.source_ids,
+                    &event.request.url,
+                    headers,
+                    &body,
+                );
 
                 let is_html_document = event.resource_type
                     == network::ResourceType::Document
@@ -107,7 +327,12 @@ pub async fn instrument_js_coverage(
                             !body.trim_start().starts_with("<?xml")
                         });
 
-                let body_instrumented = if event.resource_type
+                let instruments = (event.resource_type
+                    == network::ResourceType::Script
+                    && config.instrument_files)
+                    || (is_html_document && config.instrument_inline);
+                // `None` when passed through as-is.
+                let instrumented = if event.resource_type
                     == network::ResourceType::Script
                 {
                     let instrumented = if !config.instrument_files {
@@ -115,15 +340,21 @@ pub async fn instrument_js_coverage(
                             "skipping script file (disabled): {}",
                             event.request.url
                         );
-                        body.clone()
+                        None
                     } else {
-                        instrumentation::js::instrument_source_code(
+                        instrument_script(
                             source_id,
                             &body,
-                            // As we can't know if the script is an ES module or a regular script,
-                            // we use this source type to let the parser decide.
-                            SourceType::unambiguous(),
-                        )?
+                            source_type,
+                            &config.banner,
+                        )
+                        .inspect_err(|error| {
+                            log::warn!(
+                                "failed to parse {} near: {:?}",
+                                event.request.url,
+                                error.snippet(&body)
+                            );
+                        })?
                     };
 
                     // Write to /tmp/ for debugging
@@ -133,8 +364,11 @@ pub async fn instrument_js_coverage(
                         let safe_filename =
                             filename.replace(['?', '#', '&', '='], "_");
                         let path = format!("/tmp/{}", safe_filename);
-                        if let Err(e) =
-                            tokio::fs::write(&path, &instrumented).await
+                        if let Err(e) = tokio::fs::write(
+                            &path,
+                            instrumented.as_ref().unwrap_or(&body),
+                        )
+                        .await
                         {
                             log::debug!(
                                 "failed to write debug file to {}: {}",
@@ -153,17 +387,19 @@ pub async fn instrument_js_coverage(
                 } else if is_html_document {
                     if config.instrument_inline {
                         instrumentation::html::instrument_inline_scripts(
-                            source_id, &body,
+                            source_id,
+                            &body,
+                            &config.banner,
                         )?
                     } else {
                         log::debug!("skipping inline scripts (disabled)");
-                        body.clone()
+                        None
                     }
                 } else if event.resource_type == network::ResourceType::Document
                 {
                     // Non-HTML documents (XML, PDF, etc.) are passed
                     // through without instrumentation.
-                    body.clone()
+                    None
                 } else {
                     bail!(
                         "should only intercept script and document resources, but got {:?}",
@@ -171,6 +407,17 @@ pub async fn instrument_js_coverage(
                     );
                 };
 
+                if instruments {
+                    record_file(
+                        &files,
+                        source_id,
+                        &event.request.url,
+                        &body,
+                        instrumented.as_deref(),
+                    );
+                }
+                let body_instrumented = instrumented.unwrap_or(body);
+
                 page.execute(
                     fetch::FulfillRequestParams::builder()
                         .request_id(event.request_id.clone())
@@ -208,7 +455,20 @@ pub async fn instrument_js_coverage(
                     continue;
                 }
 
-                log::warn!("failed to instrument requested script: {error}");
+                failures.record(&error);
+                log::warn!(
+                    "failed to instrument {}: {:#}",
+                    event.request.url,
+                    error
+                );
+                if let Some(strict_errors) = &strict_errors {
+                    let _ = strict_errors.send(BrowserEvent::Error(Arc::new(
+                        error.context(format!(
+                            "failed to instrument {} (in strict instrumentation mode)",
+                            event.request.url
+                        )),
+                    )));
+                }
                 if let Err(error) = async {
                     let params = fetch::ContinueRequestParams::builder()
                         .request_id(event.request_id.clone())
@@ -232,11 +492,462 @@ pub async fn instrument_js_coverage(
     Ok(())
 }
 
-/// Calculate source ID from etag or body.
-fn source_id(headers: HashMap<String, String>, body: &str) -> SourceId {
-    if let Some(etag) = headers.get("etag") {
-        SourceId::hash(etag)
+async fn apply_request_rules(
+    page: &Page,
+    rules: &[RequestRule],
+    event: &fetch::EventRequestPaused,
+) -> Result<()> {
+    match match_request_rules(rules, &event.request.url) {
+        Some(RequestRuleMatch::Block) => {
+            log::debug!("blocking request: {}", event.request.url);
+            page.execute(
+                fetch::FailRequestParams::builder()
+                    .request_id(event.request_id.clone())
+                    .error_reason(network::ErrorReason::BlockedByClient)
+                    .build()
+                    .map_err(|error| {
+                        anyhow!("failed building FailRequestParams: {}", error)
+                    })?,
+            )
+            .await
+            .context("failed blocking request")?;
+        }
+        Some(RequestRuleMatch::Stub(response)) => {
+            log::debug!("stubbing request: {}", event.request.url);
+            page.execute(
+                fetch::FulfillRequestParams::builder()
+                    .request_id(event.request_id.clone())
+                    .body(BASE64_STANDARD.encode(&response.body))
+                    .response_code(response.status as i64)
+                    .response_header(fetch::HeaderEntry {
+                        name: "content-type".to_string(),
+                        value: "application/json".to_string(),
+                    })
+                    .build()
+                    .map_err(|error| {
+                        anyhow!(
+                            "failed building FulfillRequestParams: {}",
+                            error
+                        )
+                    })?,
+            )
+            .await
+            .context("failed stubbing request")?;
+        }
+        None => {
+            page.execute(
+                fetch::ContinueRequestParams::builder()
+                    .request_id(event.request_id.clone())
+                    .build()
+                    .map_err(|error| {
+                        anyhow!(
+                            "failed building ContinueRequestParams: {}",
+                            error
+                        )
+                    })?,
+            )
+            .await
+            .context("failed continuing request")?;
+        }
+    }
+    Ok(())
+}
+
+async fn continue_request(
+    page: &Page,
+    event: &fetch::EventRequestPaused,
+) -> Result<()> {
+    page.execute(
+        fetch::ContinueRequestParams::builder()
+            .request_id(event.request_id.clone())
+            .build()
+            .map_err(|error| {
+                anyhow!("failed building ContinueRequestParams: {}", error)
+            })?,
+    )
+    .await
+    .map(|_| ())
+    .context("failed continuing request")
+}
+
+fn content_length(headers: &[fetch::HeaderEntry]) -> Option<usize> {
+    headers
+        .iter()
+        .find(|header| header.name.eq_ignore_ascii_case("content-length"))
+        .and_then(|header| header.value.trim().parse().ok())
+}
+
+fn log_too_large(url: &str, size: usize) {
+    log::warn!(
+        "not instrumenting {} ({} bytes), as it's over the script size limit",
+        url,
+        size
+    );
+}
+
+/// Picks how to parse a script, as only some ES modules can be told apart from regular scripts by
+/// their syntax. Otherwise, the parser decides.
+fn script_source_type(
+    url: &str,
+    request_headers: &HashMap<String, String>,
+) -> SourceType {
+    let header = |name: &str| {
+        request_headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    };
+    let is_mjs = |url: &str| {
+        url::Url::parse(url).is_ok_and(|url| url.path().ends_with(".mjs"))
+    };
+    // Browsers fetch module scripts in CORS mode, but also regular scripts with a `crossorigin`
+    // attribute, so the mode only counts for scripts imported by a module.
+    let is_module_import = header("sec-fetch-dest") == Some("script")
+        && header("sec-fetch-mode") == Some("cors")
+        && header("referer").is_some_and(is_mjs);
+    if is_mjs(url) || is_module_import {
+        SourceType::mjs()
     } else {
-        SourceId::hash(body)
+        SourceType::unambiguous()
+    }
+}
+
+/// Records a source that was meant to be instrumented, as skipped if it opted out, which leaves
+/// it without instrumented code.
+fn record_file(
+    files: &InstrumentedFiles,
+    source_id: SourceId,
+    url: &str,
+    body: &str,
+    instrumented: Option<&str>,
+) {
+    match instrumented {
+        Some(instrumented) => {
+            let file = InstrumentedFile {
+                source_id: source_id.0,
+                url: url.to_string(),
+                size: body.len(),
+                instrumented_size: instrumented.len(),
+            };
+            if files.record(file.clone()) {
+                log::info!(
+                    "instrumented {} ({} bytes, {} instrumented)",
+                    file.url,
+                    file.size,
+                    file.instrumented_size
+                );
+            }
+        }
+        None => {
+            if files.record_skipped(source_id.0) {
+                log::info!("not instrumenting {} (opted out)", url);
+            }
+        }
+    }
+}
+
+/// Instruments a script file, retrying as the other kind of script (module or not) when it
+/// fails as the inferred one. Inference goes wrong on real-world files, e.g. without hints,
+/// modules using only module syntax that's also valid in scripts (like top-level `await`) fail
+/// to parse as scripts, and scripts using legacy syntax (like octal literals) fail as modules.
+fn instrument_script(
+    source_id: SourceId,
+    body: &str,
+    source_type: SourceType,
+    banner: &Banner,
+) -> InstrumentationResult<Option<String>> {
+    let instrument = |source_type| {
+        instrumentation::js::instrument_source_code_with_banner(
+            source_id,
+            body,
+            source_type,
+            banner,
+        )
+    };
+    instrument(source_type).or_else(|error| {
+        let other = if source_type.is_module() {
+            SourceType::cjs()
+        } else {
+            SourceType::mjs()
+        };
+        instrument(other).map_err(|_| error)
+    })
+}
+
+/// Calculate source ID from etag or body.
+fn source_id(
+    strategy: SourceIdStrategy,
+    url: &str,
+    headers: HashMap<String, String>,
+    body: &str,
+) -> SourceId {
+    match strategy {
+        SourceIdStrategy::FromEtag => match headers.get("etag") {
+            Some(etag) => SourceId::from_etag(etag),
+            None => SourceId::from_body(body),
+        },
+        SourceIdStrategy::FromBody => SourceId::from_body(body),
+        SourceIdStrategy::FromUrlPath => match url::Url::parse(url) {
+            Ok(url) => SourceId::from_url_path(&url),
+            Err(_) => SourceId::from_body(body),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(string: &str) -> UrlPattern {
+        string.parse().unwrap()
+    }
+
+    #[test]
+    fn test_instrumented_files_deduplicated() {
+        let files = InstrumentedFiles::default();
+        let file = |source_id: u64, url: &str| InstrumentedFile {
+            source_id,
+            url: url.to_string(),
+            size: 100,
+            instrumented_size: 250,
+        };
+        assert!(files.record(file(1, "https://example.com/main.js")));
+        assert!(files.record(file(2, "https://example.com/chunk.js")));
+        assert!(!files.record(file(1, "https://example.com/main.js?v=2")));
+        assert_eq!(
+            files.list(),
+            vec![
+                file(1, "https://example.com/main.js"),
+                file(2, "https://example.com/chunk.js")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_coverage_stats() {
+        let files = InstrumentedFiles::default();
+        let file = |source_id: u64, size: usize| InstrumentedFile {
+            source_id,
+            url: format!("https://example.com/{}.js", source_id),
+            size,
+            instrumented_size: size * 2,
+        };
+        files.record(file(1, 100));
+        files.record(file(2, 50));
+        files.record(file(1, 100));
+        assert!(files.record_skipped(3));
+        assert!(!files.record_skipped(3));
+        let failures = InstrumentationFailures::default();
+        failures.record(&InstrumentationError::ParseErrors(vec![]).into());
+        failures.record(
+            &anyhow::Error::from(InstrumentationError::SemanticErrors(vec![]))
+                .context("instrumenting"),
+        );
+        failures.record(&anyhow!("failed getting response body"));
+        assert_eq!(failures.count(), 3);
+        assert_eq!(
+            CoverageStats::collect(&files, &failures),
+            CoverageStats {
+                files: 2,
+                skipped: 1,
+                original_bytes: 150,
+                instrumented_bytes: 300,
+                parse_failures: 1,
+                semantic_failures: 1,
+                other_failures: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_coverage_stats_skips_disable_pragma() {
+        let files = InstrumentedFiles::default();
+        let banner = Banner::default();
+        for (source_id, body) in [
+            (1, "let x = a ? b : c;"),
+            (2, "// bombadil-disable-instrumentation\nlet x = a ? b : c;"),
+        ] {
+            let instrumented = instrument_script(
+                SourceId(source_id),
+                body,
+                SourceType::cjs(),
+                &banner,
+            )
+            .unwrap();
+            record_file(
+                &files,
+                SourceId(source_id),
+                &format!("https://example.com/{}.js", source_id),
+                body,
+                instrumented.as_deref(),
+            );
+        }
+        let stats =
+            CoverageStats::collect(&files, &InstrumentationFailures::default());
+        assert_eq!(stats.files, 1);
+        assert_eq!(stats.skipped, 1);
+        assert_eq!(stats.original_bytes, "let x = a ? b : c;".len());
+        assert!(stats.instrumented_bytes > stats.original_bytes);
+    }
+
+    #[test]
+    fn test_instrument_script_retries_other_source_type() {
+        let banner = Banner::default();
+        // Legacy octal literals are errors in modules, which are always strict.
+        assert!(
+            instrument_script(
+                SourceId(0),
+                "var x = 010;",
+                SourceType::mjs(),
+                &banner
+            )
+            .is_ok()
+        );
+        assert!(
+            instrument_script(
+                SourceId(0),
+                "await Promise.resolve(1);",
+                SourceType::cjs(),
+                &banner
+            )
+            .is_ok()
+        );
+        assert!(
+            instrument_script(
+                SourceId(0),
+                "let x = a ? ;",
+                SourceType::cjs(),
+                &banner
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_script_source_type_hints() {
+        let headers = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect()
+        };
+        assert!(
+            script_source_type(
+                "https://example.com/app.mjs?v=1",
+                &headers(&[])
+            )
+            .is_module()
+        );
+        assert!(
+            script_source_type(
+                "https://example.com/app.js",
+                &headers(&[
+                    ("Sec-Fetch-Dest", "script"),
+                    ("Sec-Fetch-Mode", "cors"),
+                    ("Referer", "https://example.com/main.mjs"),
+                ])
+            )
+            .is_module()
+        );
+        // Classic scripts with a `crossorigin` attribute are fetched in CORS mode too.
+        assert!(
+            !script_source_type(
+                "https://example.com/app.js",
+                &headers(&[
+                    ("Sec-Fetch-Dest", "script"),
+                    ("Sec-Fetch-Mode", "cors"),
+                    ("Referer", "https://example.com/index.html"),
+                ])
+            )
+            .is_module()
+        );
+        assert!(
+            !script_source_type(
+                "https://example.com/app.js",
+                &headers(&[
+                    ("Sec-Fetch-Dest", "script"),
+                    ("Sec-Fetch-Mode", "no-cors")
+                ])
+            )
+            .is_module()
+        );
+    }
+
+    #[test]
+    fn test_source_id_strategies() {
+        let etag = HashMap::from([("etag".to_string(), "abc".to_string())]);
+        let id = |strategy, url, headers: &HashMap<String, String>, body| {
+            source_id(strategy, url, headers.clone(), body).0
+        };
+
+        let from_etag =
+            |url, body| id(SourceIdStrategy::FromEtag, url, &etag, body);
+        assert_eq!(
+            from_etag("https://a.com/app.js", "1"),
+            from_etag("https://b.com/other.js", "2")
+        );
+        assert_ne!(
+            id(SourceIdStrategy::FromEtag, "", &HashMap::new(), "1"),
+            id(SourceIdStrategy::FromEtag, "", &HashMap::new(), "2")
+        );
+
+        let from_body = |body| {
+            id(SourceIdStrategy::FromBody, "https://a.com/", &etag, body)
+        };
+        assert_ne!(from_body("1"), from_body("2"));
+
+        let from_url_path =
+            |url, body| id(SourceIdStrategy::FromUrlPath, url, &etag, body);
+        assert_eq!(
+            from_url_path("https://a.com/app.js?v=1", "1"),
+            from_url_path("https://cdn.a.com/app.js?v=2", "2")
+        );
+        assert_ne!(
+            from_url_path("https://a.com/app.js", "1"),
+            from_url_path("https://a.com/vendor.js", "1")
+        );
+    }
+
+    #[test]
+    fn test_content_length() {
+        let header = |name: &str, value: &str| fetch::HeaderEntry {
+            name: name.to_string(),
+            value: value.to_string(),
+        };
+        assert_eq!(
+            content_length(&[
+                header("Content-Type", "text/javascript"),
+                header("Content-Length", "1024"),
+            ]),
+            Some(1024)
+        );
+        assert_eq!(content_length(&[header("content-length", "?")]), None);
+        assert_eq!(content_length(&[]), None);
+    }
+
+    #[test]
+    fn test_match_request_rules_first_rule_wins() {
+        let rules = vec![
+            RequestRule::BlockUrls(vec![pattern("*://tracker.com/*")]),
+            RequestRule::StubUrls(vec![(
+                pattern("*/api/*"),
+                StubResponse {
+                    status: 200,
+                    body: "{}".to_string(),
+                },
+            )]),
+        ];
+        assert!(matches!(
+            match_request_rules(&rules, "https://tracker.com/api/x"),
+            Some(RequestRuleMatch::Block)
+        ));
+        assert!(matches!(
+            match_request_rules(&rules, "https://example.com/api/x"),
+            Some(RequestRuleMatch::Stub(StubResponse { status: 200, .. }))
+        ));
+        assert!(
+            match_request_rules(&rules, "https://example.com/index.js")
+                .is_none()
+        );
     }
 }
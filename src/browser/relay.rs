@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result, bail};
+use async_tungstenite::tungstenite::{
+    client::IntoClientRequest,
+    handshake::client::Request,
+    http::{HeaderName, HeaderValue},
+    protocol::WebSocketConfig,
+};
+use futures::StreamExt;
+use tokio::net::{TcpListener, TcpStream};
+use url::Url;
+
+/// Relays a local WebSocket endpoint to a remote debugger, adding headers to the handshake with
+/// the remote. This is needed for hosted browsers requiring authentication headers, as
+/// chromiumoxide can't set headers when connecting. Returns the local endpoint to connect to.
+pub async fn relay_with_headers(
+    remote_debugger: &Url,
+    headers: &HashMap<String, String>,
+) -> Result<Url> {
+    if !matches!(remote_debugger.scheme(), "ws" | "wss") {
+        bail!(
+            "remote debugger URL must be a ws:// or wss:// URL when sending headers, got {}",
+            remote_debugger
+        );
+    }
+    // Check the request upfront, rather than in the relay when chromiumoxide connects.
+    handshake_request(remote_debugger, headers)?;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let local = Url::parse(&format!("ws://{}", listener.local_addr()?))?;
+
+    let remote_debugger = remote_debugger.clone();
+    let headers = headers.clone();
+    tokio::spawn(async move {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(error) => {
+                    log::error!("remote debugger relay failed: {}", error);
+                    return;
+                }
+            };
+            let remote_debugger = remote_debugger.clone();
+            let headers = headers.clone();
+            tokio::spawn(async move {
+                if let Err(error) =
+                    relay(stream, &remote_debugger, &headers).await
+                {
+                    log::error!("remote debugger relay failed: {:?}", error);
+                }
+            });
+        }
+    });
+
+    Ok(local)
+}
+
+fn handshake_request(
+    remote_debugger: &Url,
+    headers: &HashMap<String, String>,
+) -> Result<Request> {
+    let mut request = remote_debugger.as_str().into_client_request()?;
+    for (name, value) in headers {
+        request.headers_mut().insert(
+            HeaderName::from_bytes(name.as_bytes())
+                .with_context(|| format!("invalid header name {:?}", name))?,
+            HeaderValue::from_str(value).with_context(|| {
+                format!("invalid value for header {:?}", name)
+            })?,
+        );
+    }
+    Ok(request)
+}
+
+async fn relay(
+    stream: TcpStream,
+    remote_debugger: &Url,
+    headers: &HashMap<String, String>,
+) -> Result<()> {
+    // The DevTools protocol has messages (e.g. screenshots) above the default size limits.
+    let config = WebSocketConfig::default()
+        .max_message_size(None)
+        .max_frame_size(None);
+    let local = async_tungstenite::tokio::accept_async_with_config(
+        stream,
+        Some(config),
+    )
+    .await?;
+    let (remote, _) = async_tungstenite::tokio::connect_async_with_config(
+        handshake_request(remote_debugger, headers)?,
+        Some(config),
+    )
+    .await
+    .with_context(|| {
+        format!("failed connecting to remote debugger {}", remote_debugger)
+    })?;
+
+    let (local_sink, local_stream) = local.split();
+    let (remote_sink, remote_stream) = remote.split();
+    tokio::select! {
+        result = local_stream.forward(remote_sink) => result?,
+        result = remote_stream.forward(local_sink) => result?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use async_tungstenite::tungstenite::{
+        Message,
+        handshake::server::{Request, Response},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_relay_sends_headers() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote_debugger = Url::parse(&format!(
+            "ws://{}/devtools",
+            listener.local_addr().unwrap()
+        ))
+        .unwrap();
+
+        // An echo server that only accepts handshakes with the expected header.
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            // The callback's error type is fixed by tungstenite.
+            #[allow(clippy::result_large_err)]
+            let callback = |request: &Request, response: Response| {
+                assert_eq!(
+                    request.headers().get("authorization").unwrap(),
+                    "Bearer secret"
+                );
+                Ok(response)
+            };
+            let mut remote =
+                async_tungstenite::tokio::accept_hdr_async(stream, callback)
+                    .await
+                    .unwrap();
+            while let Some(Ok(message)) = remote.next().await {
+                remote.send(message).await.unwrap();
+            }
+        });
+
+        let local = relay_with_headers(
+            &remote_debugger,
+            &HashMap::from([(
+                "Authorization".to_string(),
+                "Bearer secret".to_string(),
+            )]),
+        )
+        .await
+        .unwrap();
+        let (mut client, _) =
+            async_tungstenite::tokio::connect_async(local.as_str())
+                .await
+                .unwrap();
+        client.send(Message::text("hello")).await.unwrap();
+        assert_eq!(
+            client.next().await.unwrap().unwrap(),
+            Message::text("hello")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_relay_requires_websocket_url() {
+        let error = relay_with_headers(
+            &Url::parse("http://localhost:9222").unwrap(),
+            &HashMap::from([("Authorization".to_string(), "x".to_string())]),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.to_string().contains("ws://"));
+    }
+}
@@ -11,11 +11,16 @@ use chromiumoxide::{
 };
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use serde_json as json;
-use std::{sync::Arc, time::SystemTime};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
 use url::Url;
 
 use crate::browser::evaluation::{
     evaluate_expression_in_debugger, evaluate_function_call_in_debugger,
+    skip_timeout, with_timeout,
 };
 
 #[derive(Clone, Debug)]
@@ -31,8 +36,57 @@ pub struct BrowserState {
     pub navigation_history: NavigationHistory,
     pub exceptions: Vec<Exception>,
     pub transition_hash: Option<u64>,
+    /// A hash of the DOM's structure (element tags and attribute names, but not text or
+    /// attribute values), identifying states independently of which code ran to reach them. Only
+    /// computed with [`BrowserOptions::dom_hash`](crate::browser::BrowserOptions::dom_hash).
+    pub dom_hash: Option<u64>,
     pub coverage: Coverage,
-    pub screenshot: Screenshot,
+    /// Missing when taking the screenshot timed out.
+    pub screenshot: Option<Screenshot>,
+    pub scroll: Scroll,
+    pub viewport: Viewport,
+    /// How the DOM changed since the previous state, when recording it.
+    pub dom_changes: Option<DomChanges>,
+    /// Whether the page stopped responding to probes while acting, e.g. when stuck in a loop.
+    pub unresponsive: bool,
+    /// Screenshots of the elements that the specification's properties focus on, by selector.
+    /// Missing for elements that weren't found or had no size.
+    pub element_screenshots: HashMap<String, Screenshot>,
+}
+
+/// Which hash identifies states, e.g. when deciding whether a state is new.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum StateHash {
+    /// The transition hash, computed from the coverage of the last transition.
+    #[default]
+    Coverage,
+    /// The DOM hash, which is more stable when coverage is noisy.
+    Dom,
+}
+
+/// How hit counts of edges are bucketed when comparing coverage between states.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum CoverageMode {
+    /// Buckets hit counts into [1,8], similar to AFL, so that hitting an edge more often
+    /// counts as new coverage.
+    #[default]
+    HitCounts,
+    /// Only whether an edge was hit, for stabler state hashes when hit counts vary.
+    Presence,
+}
+
+/// How states are captured.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct CaptureOptions {
+    pub cdp_timeout: Option<Duration>,
+    pub coverage_mode: CoverageMode,
+    pub dom_hash: bool,
+}
+
+#[derive(Deserialize)]
+struct CoverageEvaluation {
+    edges_new: Vec<(EdgeIndex, EdgeBucket)>,
+    transition_hash: Option<String>,
 }
 
 pub type EdgeIndex = u32;
@@ -41,6 +95,75 @@ pub type EdgeBucket = u8;
 #[derive(Clone, Debug)]
 pub struct Coverage {
     pub edges_new: Vec<(EdgeIndex, EdgeBucket)>,
+    /// How many scripts and documents have failed to be instrumented so far in the test.
+    pub instrumentation_failures: usize,
+}
+
+/// How many DOM changes are kept per state, as large mutations would otherwise bloat the trace.
+const DOM_CHANGES_MAX: usize = 100;
+
+/// How the DOM changed between two states, as reported by the browser. Only the first changes
+/// are kept, and the rest are counted.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct DomChanges {
+    pub changes: Vec<DomChange>,
+    pub omitted: usize,
+}
+
+impl DomChanges {
+    pub fn push(&mut self, change: DomChange) {
+        if self.changes.len() < DOM_CHANGES_MAX {
+            self.changes.push(change);
+        } else {
+            self.omitted += 1;
+        }
+    }
+}
+
+/// A change of the DOM, with nodes identified by their DevTools node IDs.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DomChange {
+    Inserted {
+        parent: i64,
+        node: i64,
+        /// The inserted node, e.g. `<li class="item">` or `"some text"`.
+        description: String,
+    },
+    Removed {
+        parent: i64,
+        node: i64,
+    },
+    AttributeModified {
+        node: i64,
+        name: String,
+        value: String,
+    },
+}
+
+/// The document's scroll position, in CSS pixels.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Scroll {
+    pub x: f64,
+    pub y: f64,
+    /// The furthest the document scrolls horizontally, 0 when it fits in the viewport.
+    pub max_x: f64,
+    /// The furthest the document scrolls vertically, 0 when it fits in the viewport.
+    pub max_y: f64,
+}
+
+/// The size of the viewport, in CSS pixels.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Viewport {
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Deserialize)]
+struct ScrollEvaluation {
+    scroll: Scroll,
+    viewport: Viewport,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -147,37 +270,91 @@ impl BrowserState {
         call_frame_id: &CallFrameId,
         console_entries: Vec<ConsoleEntry>,
         exceptions: Vec<Exception>,
-        screenshot: Screenshot,
+        screenshot: Option<Screenshot>,
+        instrumentation_failures: usize,
+        options: CaptureOptions,
     ) -> Result<Self> {
+        let CaptureOptions {
+            cdp_timeout,
+            coverage_mode,
+            dom_hash,
+        } = options;
+        let started = Instant::now();
+        let mut cdp_calls = 0;
+
         log::trace!("BrowserState::current: evaluating url");
+        cdp_calls += 1;
         let url = Url::parse(
-            &evaluate_expression_in_debugger::<String>(
-                &page,
-                call_frame_id,
-                "window.location.href",
+            &with_timeout(
+                cdp_timeout,
+                "evaluating url",
+                evaluate_expression_in_debugger::<String>(
+                    &page,
+                    call_frame_id,
+                    "window.location.href",
+                ),
             )
             .await?,
         )?;
 
         log::trace!("BrowserState::current: evaluating title");
-        let title: String = evaluate_expression_in_debugger(
-            &page,
-            call_frame_id,
-            "document.title",
+        cdp_calls += 1;
+        let title: String = with_timeout(
+            cdp_timeout,
+            "evaluating title",
+            evaluate_expression_in_debugger(
+                &page,
+                call_frame_id,
+                "document.title",
+            ),
         )
         .await?;
 
         log::trace!("BrowserState::current: evaluating content_type");
-        let content_type: String = evaluate_expression_in_debugger(
-            &page,
-            call_frame_id,
-            "document.contentType",
+        cdp_calls += 1;
+        let content_type: String = with_timeout(
+            cdp_timeout,
+            "evaluating content type",
+            evaluate_expression_in_debugger(
+                &page,
+                call_frame_id,
+                "document.contentType",
+            ),
+        )
+        .await?;
+
+        log::trace!("BrowserState::current: evaluating scroll and viewport");
+        cdp_calls += 1;
+        let ScrollEvaluation { scroll, viewport } = with_timeout(
+            cdp_timeout,
+            "evaluating scroll and viewport",
+            evaluate_expression_in_debugger(
+                &page,
+                call_frame_id,
+                "
+            (() => {
+                const element = document.scrollingElement;
+                return {
+                    scroll: {
+                        x: window.scrollX,
+                        y: window.scrollY,
+                        maxX: element ? Math.max(0, element.scrollWidth - element.clientWidth) : 0,
+                        maxY: element ? Math.max(0, element.scrollHeight - element.clientHeight) : 0,
+                    },
+                    viewport: { width: window.innerWidth, height: window.innerHeight },
+                };
+            })()
+            ",
+            ),
         )
         .await?;
 
         log::trace!("BrowserState::current: getting navigation history");
-        let navigation_history_result = page
-            .execute(page::GetNavigationHistoryParams {})
+        cdp_calls += 1;
+        let navigation_history_result =
+            with_timeout(cdp_timeout, "getting navigation history", async {
+                Ok(page.execute(page::GetNavigationHistoryParams {}).await?)
+            })
             .await?
             .result;
 
@@ -208,25 +385,46 @@ impl BrowserState {
                 .collect(),
         };
 
-        log::trace!("BrowserState::current: evaluating coverage");
-        let edges_new: Vec<(u32, u8)> = evaluate_expression_in_debugger(
+        // Coverage and hashes are skipped when timing out, as the state is
+        // still usable without them. The edge diff and the transition hash
+        // are computed in a single evaluation, to save a round-trip per state.
+        log::trace!(
+            "BrowserState::current: evaluating coverage and transition hash"
+        );
+        cdp_calls += 1;
+        // Edges that weren't hit stay in bucket 0, in both modes.
+        let bucket = match coverage_mode {
+            CoverageMode::HitCounts => {
+                "
+                function bucket(hits) {
+                    if (hits <= 3) return hits;
+                    let msb = 0;
+                    let n = hits;
+                    while (n > 0) {
+                        n = n >> 1;
+                        msb++;
+                    }
+                    return Math.min(msb + 1, 8);
+                }
+                "
+            }
+            CoverageMode::Presence => {
+                "
+                function bucket(hits) {
+                    return hits > 0 ? 1 : 0;
+                }
+                "
+            }
+        };
+        let coverage: Option<CoverageEvaluation> = skip_timeout(with_timeout(cdp_timeout, "evaluating coverage", evaluate_expression_in_debugger(
             &page,
             call_frame_id,
             format!("
                 (() => {{
-                    if (!window.{NAMESPACE}) return [];
-
-                    // Bucket current hits into [1,8], similar to AFL.
-                    function bucket(hits) {{
-                        if (hits <= 3) return hits;
-                        let msb = 0;
-                        let n = hits;
-                        while (n > 0) {{
-                            n = n >> 1;
-                            msb++;
-                        }}
-                        return Math.min(msb + 1, 8);
-                    }}
+                    if (!window.{NAMESPACE}) return {{ edges_new: [], transition_hash: null }};
+
+                    // Bucket current hits.
+                    {bucket}
                     for (let i = 0; i < window.{NAMESPACE}.{EDGES_CURRENT}.length; i++) {{
                         window.{NAMESPACE}.{EDGES_CURRENT}[i] = bucket(window.{NAMESPACE}.{EDGES_CURRENT}[i]);
                     }}
@@ -243,23 +441,7 @@ impl BrowserState {
                     window.{NAMESPACE}.{EDGES_PREVIOUS} = window.{NAMESPACE}.{EDGES_CURRENT};
                     window.{NAMESPACE}.{EDGES_CURRENT} = new Uint8Array({EDGE_MAP_SIZE});
 
-                    return differences;
-                }})()
-                "
-            ),
-        )
-        .await?;
-
-        log::trace!("BrowserState::current: evaluating transition hash");
-        let transition_hash_bigint: Option<String> =
-            evaluate_expression_in_debugger(
-                &page,
-                call_frame_id,
-                format!(
-                    "
-                (() => {{
-                    if (!window.{NAMESPACE}) return null;
-
+                    // Compute the transition hash from the shifted edges.
                     const SIMHASH_BITS = 64;
                     function hash64(x) {{
                         let h = BigInt(x) + 0x9e3779b97f4a7c15n;
@@ -275,7 +457,6 @@ impl BrowserState {
                         if (bucket === 0) continue;
 
                         const weight = Math.max(1, Math.min(3, Math.floor(Math.log2(bucket))));
-                        // const weight = bucket > 0 ? 1 : 0; // presence only
                         let h = hash64(i);
 
                         for (let b = 0; b < SIMHASH_BITS; b++) {{
@@ -284,7 +465,9 @@ impl BrowserState {
                         }}
                     }}
 
-                    if (acc.every(b => b == 0)) return null;
+                    if (acc.every(b => b == 0)) {{
+                        return {{ edges_new: differences, transition_hash: null }};
+                    }}
 
                     let out = 0n;
                     for (let b = 0; b < SIMHASH_BITS; b++) {{
@@ -293,20 +476,83 @@ impl BrowserState {
                         }}
                     }}
 
-                    window.{NAMESPACE}.{EDGES_CURRENT}.fill(0);
-                    return out;
+                    // BigInts aren't serializable as JSON, so it's returned as a string.
+                    return {{ edges_new: differences, transition_hash: out.toString() }};
                 }})()
-            "
-                ),
-            )
-            .await?;
+                "
+            ),
+        )).await)?;
+        let (edges_new, transition_hash) = match coverage {
+            Some(CoverageEvaluation {
+                edges_new,
+                transition_hash,
+            }) => (
+                edges_new,
+                transition_hash
+                    .map(|hash| hash.parse::<u64>())
+                    .transpose()?,
+            ),
+            None => (vec![], None),
+        };
 
-        let transition_hash = match transition_hash_bigint {
-            Some(string) => Some(string.parse::<u64>()?),
-            None => None,
+        // A 53-bit hash (cyrb53), so that it fits in a JavaScript number.
+        let dom_hash: Option<u64> = if dom_hash {
+            log::trace!("BrowserState::current: evaluating dom hash");
+            cdp_calls += 1;
+            skip_timeout(
+                with_timeout(
+                    cdp_timeout,
+                    "evaluating dom hash",
+                    evaluate_expression_in_debugger(
+                        &page,
+                        call_frame_id,
+                        "
+            (() => {
+                if (!document.documentElement) return null;
+
+                let h1 = 0xdeadbeef, h2 = 0x41c6ce57;
+                function update(string) {
+                    for (let i = 0; i < string.length; i++) {
+                        const c = string.charCodeAt(i);
+                        h1 = Math.imul(h1 ^ c, 2654435761);
+                        h2 = Math.imul(h2 ^ c, 1597334677);
+                    }
+                }
+
+                function visit(element) {
+                    update('<' + element.tagName);
+                    for (const name of element.getAttributeNames().sort()) {
+                        update(' ' + name);
+                    }
+                    update('>');
+                    for (const child of element.children) {
+                        visit(child);
+                    }
+                    update('</>');
+                }
+                visit(document.documentElement);
+
+                h1 = Math.imul(h1 ^ (h1 >>> 16), 2246822507);
+                h1 ^= Math.imul(h2 ^ (h2 >>> 13), 3266489909);
+                h2 = Math.imul(h2 ^ (h2 >>> 16), 2246822507);
+                h2 ^= Math.imul(h1 ^ (h1 >>> 13), 3266489909);
+                return 4294967296 * (2097151 & h2) + (h1 >>> 0);
+            })()
+            ",
+                    ),
+                )
+                .await,
+            )?
+            .flatten()
+        } else {
+            None
         };
 
-        log::trace!("BrowserState::current: done");
+        log::debug!(
+            "BrowserState::current: done with {} CDP calls in {:?}",
+            cdp_calls,
+            started.elapsed()
+        );
         Ok(BrowserState {
             timestamp: SystemTime::now(),
             page: page.clone(),
@@ -317,12 +563,28 @@ impl BrowserState {
             console_entries,
             navigation_history,
             exceptions,
-            coverage: Coverage { edges_new },
+            coverage: Coverage {
+                edges_new,
+                instrumentation_failures,
+            },
             transition_hash,
+            dom_hash,
             screenshot,
+            scroll,
+            viewport,
+            unresponsive: false,
+            element_screenshots: HashMap::new(),
+            dom_changes: None,
         })
     }
 
+    pub fn hash(&self, kind: StateHash) -> Option<u64> {
+        match kind {
+            StateHash::Coverage => self.transition_hash,
+            StateHash::Dom => self.dom_hash,
+        }
+    }
+
     pub async fn evaluate_function_call<Output: DeserializeOwned>(
         &self,
         function_expression: impl Into<String>,
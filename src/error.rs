@@ -0,0 +1,138 @@
+//! Errors at the library's public API, so that embedders can tell kinds of failures apart, e.g.
+//! to retry only when launching the browser failed. Internally, errors are `anyhow::Error`s,
+//! which each kind wraps with its context and source chain intact.
+
+use std::fmt;
+
+use chromiumoxide::error::CdpError;
+
+use crate::instrumentation::js::InstrumentationError;
+use crate::specification::result::SpecificationError;
+use crate::specification::worker::WorkerError;
+
+#[derive(Debug)]
+pub enum BombadilError {
+    /// Launching the browser or connecting to its debugger failed, which is often transient.
+    Launch(anyhow::Error),
+    /// A DevTools protocol command failed.
+    Cdp(anyhow::Error),
+    /// The specification failed to bundle, load, or evaluate.
+    Specification(anyhow::Error),
+    /// JavaScript from the page under test couldn't be instrumented.
+    Instrumentation(anyhow::Error),
+    Other(anyhow::Error),
+}
+
+impl BombadilError {
+    /// The underlying error, e.g. for downcasting.
+    pub fn inner(&self) -> &anyhow::Error {
+        match self {
+            BombadilError::Launch(error)
+            | BombadilError::Cdp(error)
+            | BombadilError::Specification(error)
+            | BombadilError::Instrumentation(error)
+            | BombadilError::Other(error) => error,
+        }
+    }
+
+    pub fn into_inner(self) -> anyhow::Error {
+        match self {
+            BombadilError::Launch(error)
+            | BombadilError::Cdp(error)
+            | BombadilError::Specification(error)
+            | BombadilError::Instrumentation(error)
+            | BombadilError::Other(error) => error,
+        }
+    }
+}
+
+impl fmt::Display for BombadilError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Transparent, as the source chain continues with the inner error's sources.
+        fmt::Display::fmt(&**self.inner(), f)
+    }
+}
+
+impl std::error::Error for BombadilError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.inner().source()
+    }
+}
+
+/// Classifies by the error's root, or any error in its chain of contexts.
+impl From<anyhow::Error> for BombadilError {
+    fn from(error: anyhow::Error) -> Self {
+        if error.downcast_ref::<CdpError>().is_some() {
+            BombadilError::Cdp(error)
+        } else if error.downcast_ref::<SpecificationError>().is_some()
+            || error.downcast_ref::<WorkerError>().is_some()
+        {
+            BombadilError::Specification(error)
+        } else if error.downcast_ref::<InstrumentationError>().is_some() {
+            BombadilError::Instrumentation(error)
+        } else {
+            BombadilError::Other(error)
+        }
+    }
+}
+
+impl From<CdpError> for BombadilError {
+    fn from(error: CdpError) -> Self {
+        BombadilError::Cdp(error.into())
+    }
+}
+
+impl From<SpecificationError> for BombadilError {
+    fn from(error: SpecificationError) -> Self {
+        BombadilError::Specification(error.into())
+    }
+}
+
+impl From<WorkerError> for BombadilError {
+    fn from(error: WorkerError) -> Self {
+        BombadilError::Specification(error.into())
+    }
+}
+
+impl From<InstrumentationError> for BombadilError {
+    fn from(error: InstrumentationError) -> Self {
+        BombadilError::Instrumentation(error.into())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, BombadilError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{Context, anyhow};
+
+    #[test]
+    fn test_classifies_through_context() {
+        let error: BombadilError = Err::<(), _>(
+            SpecificationError::OtherError("no actions exported".to_string()),
+        )
+        .context("failed loading specification")
+        .unwrap_err()
+        .into();
+        assert!(matches!(error, BombadilError::Specification(_)));
+        assert_eq!(error.to_string(), "failed loading specification");
+        assert_eq!(
+            std::error::Error::source(&error).map(|source| source.to_string()),
+            Some("no actions exported".to_string())
+        );
+
+        let error: BombadilError = anyhow!("something else").into();
+        assert!(matches!(error, BombadilError::Other(_)));
+
+        let error: BombadilError =
+            InstrumentationError::ParseErrors(vec![]).into();
+        assert!(matches!(error, BombadilError::Instrumentation(_)));
+        assert!(
+            error
+                .into_inner()
+                .downcast_ref::<InstrumentationError>()
+                .is_some()
+        );
+    }
+}
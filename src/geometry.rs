@@ -1,12 +1,118 @@
-use chromiumoxide::layout;
+use chromiumoxide::{cdp::browser_protocol::dom, layout};
 use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone, Serialize, Deserialize, Debug)]
+/// A point in the viewport, in CSS pixels from its top-left corner. This is what CDP input
+/// events take, and how actions are serialized in traces.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub struct Point {
     pub x: f64,
     pub y: f64,
 }
 
+impl Point {
+    pub fn new(x: f64, y: f64) -> Self {
+        Point { x, y }
+    }
+
+    pub fn distance(&self, other: &Point) -> f64 {
+        (self.x - other.x).hypot(self.y - other.y)
+    }
+
+    /// The center of a quad, given as four (x, y) vertices in a flat slice, like the quads of a
+    /// CDP box model.
+    pub fn center_of_quad(quad: &[f64]) -> Option<Point> {
+        if quad.len() != 8 {
+            return None;
+        }
+        let xs = quad.iter().step_by(2);
+        let ys = quad.iter().skip(1).step_by(2);
+        Some(Point {
+            x: xs.sum::<f64>() / 4.0,
+            y: ys.sum::<f64>() / 4.0,
+        })
+    }
+}
+
+/// An axis-aligned rectangle in the viewport, in CSS pixels, e.g. the bounds of an element.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    pub fn new(x: f64, y: f64, width: f64, height: f64) -> Self {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// The bounding rectangle of a quad, given as four (x, y) vertices in a flat slice, like the
+    /// quads of a CDP box model. Transformed elements have quads that aren't rectangles.
+    pub fn bounding_quad(quad: &[f64]) -> Option<Rect> {
+        if quad.len() != 8 {
+            return None;
+        }
+        let xs = || quad.iter().step_by(2).copied();
+        let ys = || quad.iter().skip(1).step_by(2).copied();
+        let left = xs().fold(f64::INFINITY, f64::min);
+        let top = ys().fold(f64::INFINITY, f64::min);
+        Some(Rect {
+            x: left,
+            y: top,
+            width: xs().fold(f64::NEG_INFINITY, f64::max) - left,
+            height: ys().fold(f64::NEG_INFINITY, f64::max) - top,
+        })
+    }
+
+    /// The vertices clockwise from the top-left, in the flat layout of CDP quads.
+    pub fn to_quad(&self) -> Vec<f64> {
+        let (right, bottom) = (self.x + self.width, self.y + self.height);
+        vec![self.x, self.y, right, self.y, right, bottom, self.x, bottom]
+    }
+
+    pub fn center(&self) -> Point {
+        Point {
+            x: self.x + self.width / 2.0,
+            y: self.y + self.height / 2.0,
+        }
+    }
+
+    /// Whether the point is within the rectangle, including its edges.
+    pub fn contains(&self, point: &Point) -> bool {
+        (self.x..=self.x + self.width).contains(&point.x)
+            && (self.y..=self.y + self.height).contains(&point.y)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width <= 0.0 || self.height <= 0.0
+    }
+}
+
+impl TryFrom<&dom::Quad> for Rect {
+    type Error = anyhow::Error;
+
+    fn try_from(quad: &dom::Quad) -> Result<Self, Self::Error> {
+        Rect::bounding_quad(quad.inner()).ok_or_else(|| {
+            anyhow::anyhow!(
+                "expected a quad of 8 coordinates, got {}",
+                quad.inner().len()
+            )
+        })
+    }
+}
+
+impl From<Rect> for dom::Quad {
+    fn from(val: Rect) -> Self {
+        dom::Quad::new(val.to_quad())
+    }
+}
+
 impl From<layout::Point> for Point {
     fn from(val: layout::Point) -> Self {
         Point { x: val.x, y: val.y }
@@ -18,3 +124,57 @@ impl From<Point> for layout::Point {
         layout::Point { x: val.x, y: val.y }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_center_of_quad() {
+        let center = Point::center_of_quad(&[
+            10.0, 20.0, 30.0, 20.0, 30.0, 60.0, 10.0, 60.0,
+        ])
+        .unwrap();
+        assert_eq!((center.x, center.y), (20.0, 40.0));
+    }
+
+    #[test]
+    fn test_center_of_quad_invalid() {
+        assert!(Point::center_of_quad(&[1.0, 2.0]).is_none());
+    }
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(Point::new(1.0, 2.0).distance(&Point::new(4.0, 6.0)), 5.0);
+    }
+
+    #[test]
+    fn test_rect_quad_round_trip() {
+        let rect = Rect::new(10.0, 20.0, 20.0, 40.0);
+        let quad = dom::Quad::from(rect);
+        assert_eq!(
+            quad.inner(),
+            &vec![10.0, 20.0, 30.0, 20.0, 30.0, 60.0, 10.0, 60.0]
+        );
+        assert_eq!(Rect::try_from(&quad).unwrap(), rect);
+        assert_eq!(Some(rect.center()), Point::center_of_quad(quad.inner()));
+    }
+
+    #[test]
+    fn test_rect_bounding_rotated_quad() {
+        let rect = Rect::bounding_quad(&[
+            20.0, 0.0, 40.0, 20.0, 20.0, 40.0, 0.0, 20.0,
+        ])
+        .unwrap();
+        assert_eq!(rect, Rect::new(0.0, 0.0, 40.0, 40.0));
+    }
+
+    #[test]
+    fn test_rect_contains() {
+        let rect = Rect::new(10.0, 20.0, 20.0, 40.0);
+        assert!(rect.contains(&Point::new(10.0, 60.0)));
+        assert!(rect.contains(&rect.center()));
+        assert!(!rect.contains(&Point::new(9.9, 30.0)));
+        assert!(!rect.contains(&Point::new(15.0, 60.1)));
+    }
+}
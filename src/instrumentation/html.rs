@@ -7,12 +7,18 @@ use markup5ever_rcdom::{Handle, NodeData, RcDom, SerializableHandle};
 use oxc::span::SourceType;
 use std::io::{BufReader, BufWriter};
 
-use crate::instrumentation::{js::instrument_source_code, source_id::SourceId};
+use crate::instrumentation::{
+    js::{Banner, instrument_source_code_with_banner},
+    source_id::SourceId,
+};
 
+/// Instruments the document's inline scripts, or returns `None` if it has some and they all opt
+/// out with [`DISABLE_PRAGMA`](crate::instrumentation::js::DISABLE_PRAGMA).
 pub fn instrument_inline_scripts(
     source_id: SourceId,
     input: &str,
-) -> Result<String> {
+    banner: &Banner,
+) -> Result<Option<String>> {
     let opts = ParseOpts {
         tree_builder: TreeBuilderOpts {
             // drop_doctype: true,
@@ -25,7 +31,11 @@ pub fn instrument_inline_scripts(
         .from_utf8()
         .read_from(&mut reader)?;
 
-    transform_inline_scripts(source_id, &dom)?;
+    let (instrumented, opted_out) =
+        transform_inline_scripts(source_id, &dom, banner)?;
+    if instrumented == 0 && opted_out > 0 {
+        return Ok(None);
+    }
 
     let document: SerializableHandle = dom.document.clone().into();
 
@@ -35,13 +45,21 @@ pub fn instrument_inline_scripts(
         serialize(&mut writer, &document, Default::default())?;
     }
 
-    String::from_utf8(buffer).map_err(|err| {
+    String::from_utf8(buffer).map(Some).map_err(|err| {
         anyhow!("failed to convert HTML into UTF8 string: {}", err)
     })
 }
 
-fn transform_inline_scripts(source_id: SourceId, dom: &RcDom) -> Result<()> {
+/// Instruments the inline scripts in place, returning how many were instrumented, and how many
+/// opted out.
+fn transform_inline_scripts(
+    source_id: SourceId,
+    dom: &RcDom,
+    banner: &Banner,
+) -> Result<(usize, usize)> {
     let mut scripts_count = 0;
+    let mut instrumented_count = 0;
+    let mut opted_out_count = 0;
     let mut stack: Vec<Handle> = Vec::new();
     stack.push(dom.document.clone());
 
@@ -62,7 +80,9 @@ fn transform_inline_scripts(source_id: SourceId, dom: &RcDom) -> Result<()> {
                 .unwrap_or("".to_string());
 
             let is_inline_javascript = script_src.is_none()
-                && (script_type.is_empty() || script_type == "text/javascript");
+                && (script_type.is_empty()
+                    || script_type == "text/javascript"
+                    || script_type == "module");
 
             let source_type = if script_type == "module" {
                 SourceType::mjs()
@@ -86,14 +106,21 @@ fn transform_inline_scripts(source_id: SourceId, dom: &RcDom) -> Result<()> {
                             c.to_string()
                         };
 
-                        let transformed = instrument_source_code(
+                        let transformed = instrument_source_code_with_banner(
                             // Every inline scripts needs a unique ID.
                             source_id.add(scripts_count),
                             &original,
                             source_type,
+                            banner,
                         )?;
 
-                        *contents.borrow_mut() = transformed.into();
+                        match transformed {
+                            Some(transformed) => {
+                                *contents.borrow_mut() = transformed.into();
+                                instrumented_count += 1;
+                            }
+                            None => opted_out_count += 1,
+                        }
                     }
                     scripts_count += 1;
                 }
@@ -105,7 +132,7 @@ fn transform_inline_scripts(source_id: SourceId, dom: &RcDom) -> Result<()> {
         }
     }
 
-    Ok(())
+    Ok((instrumented_count, opted_out_count))
 }
 
 #[cfg(test)]
@@ -130,7 +157,10 @@ mod tests {
         </html>
         "# };
 
-        let output = instrument_inline_scripts(SourceId(0), input).unwrap();
+        let output =
+            instrument_inline_scripts(SourceId(0), input, &Banner::default())
+                .unwrap()
+                .unwrap();
         assert_snapshot!(output);
     }
 
@@ -150,7 +180,31 @@ mod tests {
         </html>
         "# };
 
-        let output = instrument_inline_scripts(SourceId(0), input).unwrap();
+        let output =
+            instrument_inline_scripts(SourceId(0), input, &Banner::default())
+                .unwrap()
+                .unwrap();
+        assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_instrument_html_inline_script_module_type() {
+        let input = indoc! { r#"
+        <!DOCTYPE html>
+        <html>
+        <body>
+        <script type="module">
+        const config = await fetch("/config.json");
+        console.log(config.ok ? "loaded" : "failed");
+        </script>
+        </body>
+        </html>
+        "# };
+
+        let output =
+            instrument_inline_scripts(SourceId(0), input, &Banner::default())
+                .unwrap()
+                .unwrap();
         assert_snapshot!(output);
     }
 
@@ -169,7 +223,30 @@ mod tests {
         </html>
         "# };
 
-        let output = instrument_inline_scripts(SourceId(0), input).unwrap();
+        let output =
+            instrument_inline_scripts(SourceId(0), input, &Banner::default())
+                .unwrap()
+                .unwrap();
         assert_snapshot!(output);
     }
+
+    #[test]
+    fn test_instrument_html_inline_scripts_disable_pragma() {
+        let input = indoc! { r#"
+        <!DOCTYPE html>
+        <html>
+        <body>
+        <script>
+        // bombadil-disable-instrumentation
+        console.log(true ? 1 : 2);
+        </script>
+        </body>
+        </html>
+        "# };
+
+        let output =
+            instrument_inline_scripts(SourceId(0), input, &Banner::default())
+                .unwrap();
+        assert_eq!(output, None);
+    }
 }
@@ -1,4 +1,3 @@
-use anyhow::anyhow;
 use std::fmt;
 use std::hash::{Hash, Hasher};
 
@@ -27,7 +26,8 @@ pub enum InstrumentationError {
 
 impl From<InstrumentationError> for anyhow::Error {
     fn from(value: InstrumentationError) -> Self {
-        anyhow!(value)
+        // Not `anyhow!`, which would convert through this very impl.
+        anyhow::Error::msg(value)
     }
 }
 
@@ -44,6 +44,41 @@ impl fmt::Display for InstrumentationError {
     }
 }
 
+impl InstrumentationError {
+    /// A short part of the source text around the first error, for logging failures on large
+    /// (e.g. minified) files where the whole source would be unreadable.
+    pub fn snippet(&self, source_text: &str) -> String {
+        const BEFORE: usize = 40;
+        const LENGTH: usize = 120;
+        let (InstrumentationError::ParseErrors(errors)
+        | InstrumentationError::SemanticErrors(errors)) = self;
+        let offset = errors
+            .iter()
+            .filter_map(|error| error.labels.as_ref()?.first())
+            .map(|label| label.offset())
+            .next()
+            .unwrap_or(0)
+            .min(source_text.len());
+        let floor = |mut index: usize| {
+            while !source_text.is_char_boundary(index) {
+                index -= 1;
+            }
+            index
+        };
+        let start = floor(offset.saturating_sub(BEFORE));
+        let end = floor((start + LENGTH).min(source_text.len()));
+        let mut snippet = String::new();
+        if start > 0 {
+            snippet.push('…');
+        }
+        snippet.push_str(&source_text[start..end]);
+        if end < source_text.len() {
+            snippet.push('…');
+        }
+        snippet
+    }
+}
+
 pub type InstrumentationResult<T> = Result<T, InstrumentationError>;
 
 pub const NAMESPACE: &str = "__bombadil__";
@@ -66,19 +101,83 @@ const PRELUDE: &str = str_replace!(
     ""
 );
 
+/// A comment opting a file out of instrumentation, e.g. for vendored code that shouldn't count
+/// towards coverage. It must be among the comments at the very start of the file.
+pub const DISABLE_PRAGMA: &str = "bombadil-disable-instrumentation";
+
+/// Custom code placed around every instrumented script, like esbuild's banner and footer
+/// options. The banner runs right after the prelude, so it can refer to `window.__bombadil__`,
+/// e.g. to forward `edges_current` to an external coverage collector.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Banner {
+    pub banner: Option<String>,
+    pub footer: Option<String>,
+}
+
+/// Instruments the source, returning it unchanged if it opts out with [`DISABLE_PRAGMA`].
 pub fn instrument_source_code(
     source_id: SourceId,
     source_text: &str,
     source_type: SourceType,
 ) -> InstrumentationResult<String> {
+    let code = instrument_source_code_with_banner(
+        source_id,
+        source_text,
+        source_type,
+        &Banner::default(),
+    )?;
+    Ok(code.unwrap_or_else(|| source_text.to_string()))
+}
+
+/// Instruments the source, or returns `None` if it opts out with [`DISABLE_PRAGMA`].
+pub fn instrument_source_code_with_banner(
+    source_id: SourceId,
+    source_text: &str,
+    source_type: SourceType,
+    banner: &Banner,
+) -> InstrumentationResult<Option<String>> {
+    // Checked before parsing, so that files our parser can't handle can opt out too.
+    if has_disable_pragma(source_text) {
+        return Ok(None);
+    }
+
     let allocator = Allocator::default();
     let mut program = parse(&allocator, source_text, source_type)?;
     instrument_program(&allocator, &mut program, source_id)?;
 
     let program_codegen = Codegen::new().build(&program);
 
-    let code = format!("{PRELUDE}\n{}", program_codegen.code);
-    Ok(code)
+    let mut code = format!("{PRELUDE}\n");
+    if let Some(banner) = &banner.banner {
+        code.push_str(banner);
+        code.push('\n');
+    }
+    code.push_str(&program_codegen.code);
+    if let Some(footer) = &banner.footer {
+        code.push('\n');
+        code.push_str(footer);
+    }
+    Ok(Some(code))
+}
+
+fn has_disable_pragma(source_text: &str) -> bool {
+    let mut rest = source_text.trim_start();
+    loop {
+        let (comment, after) = if let Some(line) = rest.strip_prefix("//") {
+            line.split_once('\n').unwrap_or((line, ""))
+        } else if let Some(block) = rest.strip_prefix("/*") {
+            match block.split_once("*/") {
+                Some(split) => split,
+                None => return false,
+            }
+        } else {
+            return false;
+        };
+        if comment.trim() == DISABLE_PRAGMA {
+            return true;
+        }
+        rest = after.trim_start();
+    }
 }
 
 fn parse<'a>(
@@ -271,6 +370,26 @@ impl<'a> Traverse<'a, ()> for Instrumenter {
         self.wrap_expression_with_coverage_hook(ctx, &mut expression.alternate);
     }
 
+    /// Add coverage hooks to concise arrow function bodies, which have no block to insert them
+    /// into. The body stays an expression, so that `this` and `arguments` are unaffected.
+    fn exit_arrow_function_expression(
+        &mut self,
+        expression: &mut ast::ArrowFunctionExpression<'a>,
+        ctx: &mut TraverseCtx<'a, ()>,
+    ) {
+        if !expression.expression {
+            return;
+        }
+        if let Some(Statement::ExpressionStatement(statement)) =
+            expression.body.statements.first_mut()
+        {
+            self.wrap_expression_with_coverage_hook(
+                ctx,
+                &mut statement.expression,
+            );
+        }
+    }
+
     /// Add coverage hooks to if statement branches.
     fn exit_if_statement(
         &mut self,
@@ -346,6 +465,63 @@ mod tests {
         assert_snapshot!(code);
     }
 
+    #[test]
+    fn test_instrument_source_code_banner() {
+        let source_text = "let x = a ? b : c;";
+
+        let code = instrument_source_code_with_banner(
+            SourceId(0),
+            source_text,
+            SourceType::cjs(),
+            &Banner {
+                banner: Some(
+                    "reportCoverage(window.__bombadil__);".to_string(),
+                ),
+                footer: Some("// end of instrumented code".to_string()),
+            },
+        )
+        .unwrap()
+        .unwrap();
+        assert_snapshot!(code);
+    }
+
+    #[test]
+    fn test_instrument_source_code_disable_pragma() {
+        for source_text in [
+            "// bombadil-disable-instrumentation\nlet x = a ? b : c;",
+            "/* vendored */\n/* bombadil-disable-instrumentation */\nlet x = a ? b : c;",
+            // Not valid JavaScript, but never parsed.
+            "  // bombadil-disable-instrumentation\nlet x = a ? ;",
+        ] {
+            let code = instrument_source_code(
+                SourceId(0),
+                source_text,
+                SourceType::cjs(),
+            )
+            .unwrap();
+            assert_eq!(code, source_text);
+            assert_eq!(
+                instrument_source_code_with_banner(
+                    SourceId(0),
+                    source_text,
+                    SourceType::cjs(),
+                    &Banner::default(),
+                ),
+                Ok(None)
+            );
+        }
+    }
+
+    #[test]
+    fn test_instrument_source_code_disable_pragma_not_leading() {
+        let source_text =
+            "let x = a ? b : c;\n// bombadil-disable-instrumentation\n";
+        let code =
+            instrument_source_code(SourceId(0), source_text, SourceType::cjs())
+                .unwrap();
+        assert_ne!(code, source_text);
+    }
+
     #[test]
     fn test_instrument_source_code_if() {
         let source_text = r#"
@@ -431,6 +607,69 @@ mod tests {
         assert_snapshot!(code);
     }
 
+    #[test]
+    fn test_instrument_source_code_module_top_level_await() {
+        let source_text = r#"
+            const response = await fetch("/config.json");
+            export const config = response.ok ? await response.json() : {};
+        "#;
+
+        let code =
+            instrument_source_code(SourceId(0), source_text, SourceType::mjs())
+                .unwrap();
+        assert_snapshot!(code);
+    }
+
+    #[test]
+    fn test_instrumentation_error_snippet() {
+        let source_text =
+            format!("{}let x = a ? ;{}", "a();".repeat(50), "b();".repeat(50));
+        let error = instrument_source_code(
+            SourceId(0),
+            &source_text,
+            SourceType::cjs(),
+        )
+        .unwrap_err();
+        let snippet = error.snippet(&source_text);
+        assert!(snippet.starts_with('…'));
+        assert!(snippet.ends_with('…'));
+        assert!(snippet.contains("let x = a ? ;"));
+        assert!(snippet.chars().count() <= 122);
+
+        let error =
+            instrument_source_code(SourceId(0), "a ? ;", SourceType::cjs())
+                .unwrap_err();
+        assert_eq!(error.snippet("a ? ;"), "a ? ;");
+    }
+
+    #[test]
+    fn test_instrumentation_error_into_anyhow() {
+        let error = instrument_source_code(
+            SourceId(0),
+            "let = ;",
+            SourceType::unambiguous(),
+        )
+        .unwrap_err();
+        assert!(
+            anyhow::Error::from(error)
+                .to_string()
+                .starts_with("Parse errors")
+        );
+    }
+
+    #[test]
+    fn test_instrument_source_code_arrow_expression_body() {
+        let source_text = r#"
+            console.log([1, 2, 3].map(x => x * 2));
+            const handler = () => { return 1; };
+        "#;
+
+        let code =
+            instrument_source_code(SourceId(0), source_text, SourceType::cjs())
+                .unwrap();
+        assert_snapshot!(code);
+    }
+
     #[test]
     fn test_instrument_source_code_switch() {
         let source_text = r#"
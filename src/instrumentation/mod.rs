@@ -2,11 +2,16 @@ pub mod html;
 pub mod js;
 pub mod source_id;
 
+use js::Banner;
+use source_id::SourceIdStrategy;
+
 /// Configuration for which types of JavaScript to instrument
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct InstrumentationConfig {
     pub instrument_files: bool,
     pub instrument_inline: bool,
+    pub source_ids: SourceIdStrategy,
+    pub banner: Banner,
 }
 
 impl InstrumentationConfig {
@@ -14,6 +19,8 @@ impl InstrumentationConfig {
         Self {
             instrument_files: true,
             instrument_inline: true,
+            source_ids: SourceIdStrategy::default(),
+            banner: Banner::default(),
         }
     }
 
@@ -21,6 +28,8 @@ impl InstrumentationConfig {
         Self {
             instrument_files: false,
             instrument_inline: false,
+            source_ids: SourceIdStrategy::default(),
+            banner: Banner::default(),
         }
     }
 }
@@ -1,5 +1,7 @@
 use std::hash::{DefaultHasher, Hash, Hasher};
 
+use url::Url;
+
 #[derive(Copy, Clone)]
 pub struct SourceId(pub u64);
 
@@ -10,8 +12,36 @@ impl SourceId {
         SourceId(hasher.finish())
     }
 
+    pub fn from_etag(etag: &str) -> Self {
+        Self::hash(etag)
+    }
+
+    pub fn from_body(body: &str) -> Self {
+        Self::hash(body)
+    }
+
+    /// Only the path is used, so that the ID stays the same across hosts and cache-busting query
+    /// strings.
+    pub fn from_url_path(url: &Url) -> Self {
+        Self::hash(url.path())
+    }
+
     pub fn add<I: Hash>(&self, input: I) -> Self {
         // TODO: is this hash-chaining bad for distribution?
         Self::hash((self.0, input))
     }
 }
+
+/// How to derive the source IDs that coverage edges are computed from. Coverage is only
+/// comparable across runs where a script gets the same ID.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SourceIdStrategy {
+    /// From the response's etag, or its body if it has none.
+    #[default]
+    FromEtag,
+    /// From the response's body, so that IDs change whenever the code does.
+    FromBody,
+    /// From the URL's path, so that IDs stay the same across deploys where etags and bodies
+    /// change but paths don't.
+    FromUrlPath,
+}
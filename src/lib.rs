@@ -1,6 +1,8 @@
 pub mod browser;
+pub mod error;
 pub mod geometry;
 pub mod instrumentation;
+pub mod readiness;
 pub mod runner;
 pub mod specification;
 pub mod trace;
@@ -1,15 +1,55 @@
-use ::url::Url;
-use anyhow::Result;
-use clap::{Args, Parser};
-use std::{path::PathBuf, str::FromStr};
+use ::url::{Position, Url};
+use anyhow::{Context, Result};
+use clap::{
+    Args, CommandFactory, FromArgMatches, Parser, ValueEnum,
+    parser::ValueSource,
+};
+use oxc::span::SourceType;
+use serde::Serialize;
+use serde_json as json;
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    io::IsTerminal,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 use tempfile::TempDir;
 
 use bombadil::{
-    browser::{BrowserOptions, DebuggerOptions, Emulation, LaunchOptions},
-    instrumentation::InstrumentationConfig,
-    runner::{Runner, RunnerOptions},
-    specification::{render::render_violation, verifier::Specification},
-    trace::writer::TraceWriter,
+    browser::{
+        BrowserOptions, DebuggerOptions, Emulation, LaunchOptions, Readiness,
+        actions::BrowserAction,
+        instrumentation::{CoverageStats, RequestRule, StubResponse},
+        state::{CoverageMode, StateHash},
+    },
+    instrumentation::{
+        InstrumentationConfig,
+        js::Banner,
+        source_id::{SourceId, SourceIdStrategy},
+    },
+    runner::{
+        Clock, CoverageBelowThreshold, CoverageThreshold, Runner,
+        RunnerOptions, StartCondition,
+    },
+    specification::{
+        render::{
+            RenderOptions, TimeFormat, render_formula,
+            render_violation_summary, render_violation_with,
+        },
+        tester::{SpecTester, TestState},
+        verifier::{PropertyFilter, Snapshot, Specification, VerifierOptions},
+        worker::PropertyValue,
+    },
+    trace::{
+        summary::{
+            FirstViolation, OriginSummary, OriginsSummary, RunOutcome,
+            RunSummary, Verdict,
+        },
+        writer::TraceWriter,
+    },
+    url::{UrlPattern, UrlScope},
 };
 
 /// Property-based testing for web UIs
@@ -18,21 +58,200 @@ use bombadil::{
 struct Cli {
     #[command(subcommand)]
     command: Command,
+    #[clap(flatten)]
+    logging: LoggingOptions,
+    /// A TOML (or JSON) file of browser and runner options for testing (see the manual), which
+    /// options given on the command line override
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
+}
+
+impl Cli {
+    fn parse_with_config() -> Result<Self> {
+        Cli::parse_with_config_from(std::env::args_os())
+    }
+
+    /// Parses the command line, reading the configuration file if one is given, and noting
+    /// which options were given on the command line, so that only those override the file.
+    fn parse_with_config_from(
+        arguments: impl IntoIterator<Item = impl Into<OsString> + Clone>,
+    ) -> Result<Self> {
+        let matches = Cli::command()
+            .try_get_matches_from(arguments)
+            .unwrap_or_else(|error| error.exit());
+        let mut cli = Cli::from_arg_matches(&matches)
+            .unwrap_or_else(|error| error.exit());
+        let Some(path) = &cli.config else {
+            return Ok(cli);
+        };
+        let (Command::Test { shared, .. }
+        | Command::TestExternal { shared, .. }) = &mut cli.command
+        else {
+            anyhow::bail!("--config only applies to test and test-external");
+        };
+        let (_, subcommand_matches) =
+            matches.subcommand().expect("subcommand is required");
+        shared.config = Some(Config::read(path)?);
+        shared.given = subcommand_matches
+            .ids()
+            .filter(|id| {
+                subcommand_matches.value_source(id.as_str())
+                    == Some(ValueSource::CommandLine)
+            })
+            .map(|id| id.to_string())
+            .collect();
+        Ok(cli)
+    }
+}
+
+/// Options read from a configuration file, with those of the browser in a `browser` table
+/// and those of the runner in a `runner` table.
+#[derive(Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Config {
+    browser: BrowserOptions,
+    runner: RunnerOptions,
+}
+
+impl Config {
+    /// Reads a TOML file, or a JSON one when its name ends with `.json`, reporting keys that
+    /// aren't options.
+    fn read(path: &Path) -> Result<Config> {
+        let contents = std::fs::read_to_string(path).with_context(|| {
+            format!("failed reading config {}", path.display())
+        })?;
+        let value = if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            json::from_str(&contents).map_err(anyhow::Error::from)
+        } else {
+            contents
+                .parse::<toml_edit::Document<String>>()
+                .map_err(anyhow::Error::from)
+                .and_then(|document| toml_table_to_json(document.as_table()))
+        }
+        .with_context(|| format!("invalid config {}", path.display()))?;
+        json::from_value(value)
+            .with_context(|| format!("invalid config {}", path.display()))
+    }
+}
+
+/// Converts TOML to JSON, so that both kinds of configuration files deserialize the same way.
+fn toml_table_to_json(table: &toml_edit::Table) -> Result<json::Value> {
+    use toml_edit::{Item, Value};
+    fn value(toml: &Value) -> Result<json::Value> {
+        Ok(match toml {
+            Value::String(string) => string.value().clone().into(),
+            Value::Integer(integer) => (*integer.value()).into(),
+            Value::Float(float) => json::Number::from_f64(*float.value())
+                .map(json::Value::Number)
+                .ok_or_else(|| {
+                    anyhow::anyhow!("unsupported value: {}", float.value())
+                })?,
+            Value::Boolean(flag) => (*flag.value()).into(),
+            Value::Array(values) => json::Value::Array(
+                values.iter().map(value).collect::<Result<_>>()?,
+            ),
+            Value::InlineTable(table) => json::Value::Object(
+                table
+                    .iter()
+                    .map(|(key, item)| Ok((key.to_string(), value(item)?)))
+                    .collect::<Result<_>>()?,
+            ),
+            value => anyhow::bail!("unsupported value: {}", value.type_name()),
+        })
+    }
+    fn item(item: &Item) -> Result<json::Value> {
+        match item {
+            Item::Value(item) => value(item),
+            Item::Table(table) => toml_table_to_json(table),
+            Item::ArrayOfTables(tables) => Ok(json::Value::Array(
+                tables
+                    .iter()
+                    .map(toml_table_to_json)
+                    .collect::<Result<_>>()?,
+            )),
+            Item::None => Ok(json::Value::Null),
+        }
+    }
+    Ok(json::Value::Object(
+        table
+            .iter()
+            .map(|(key, value)| Ok((key.to_string(), item(value)?)))
+            .collect::<Result<_>>()?,
+    ))
+}
+
+#[derive(Args)]
+struct LoggingOptions {
+    /// Log more details (debug, or trace when given twice)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+    /// Log less (only warnings, errors when given twice, nothing when given three times)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    quiet: u8,
+    /// Log levels per module, in the `RUST_LOG` syntax (e.g. "bombadil::browser=debug"),
+    /// overriding the other flags and `RUST_LOG`
+    #[arg(long, global = true, value_name = "FILTER")]
+    log_filter: Option<String>,
+}
+
+impl LoggingOptions {
+    fn level(&self) -> log::LevelFilter {
+        use log::LevelFilter::*;
+        let levels = [Off, Error, Warn, Info, Debug, Trace];
+        let index =
+            (3 + self.verbose as usize).saturating_sub(self.quiet as usize);
+        levels[index.min(levels.len() - 1)]
+    }
+
+    fn init(&self) {
+        let mut builder = env_logger::Builder::new();
+        builder
+            .format_timestamp_millis()
+            .format_target(true)
+            .filter_level(self.level())
+            // Until we hav a fix for https://github.com/mattsse/chromiumoxide/issues/287
+            .filter_module("chromiumoxide::browser", log::LevelFilter::Error)
+            .filter_module("html5ever", log::LevelFilter::Info);
+        // Later directives for the same module take precedence, so these can
+        // override the level and the filters above.
+        if let Ok(filters) = std::env::var("RUST_LOG") {
+            builder.parse_filters(&filters);
+        }
+        if let Some(filters) = &self.log_filter {
+            builder.parse_filters(filters);
+        }
+        if let Ok(style) = std::env::var("RUST_LOG_STYLE") {
+            builder.parse_write_style(&style);
+        }
+        builder.init();
+    }
 }
 
 #[derive(Args)]
 struct TestSharedOptions {
     /// Starting URL of the test (also used as a boundary so that Bombadil doesn't navigate to
-    /// other websites)
-    origin: Origin,
+    /// other websites, unless --origin or --test-origin is given)
+    start_url: UrlArgument,
     /// A custom specification in TypeScript or JavaScript, using the `@antithesishq/bombadil`
     /// package on NPM
     specification_file: Option<PathBuf>,
+    /// Boundary URL so that Bombadil doesn't navigate to other websites, when it differs from the
+    /// starting URL (e.g. when the starting URL is a page within the website)
+    #[arg(long, value_name = "URL")]
+    origin: Option<UrlArgument>,
+    /// Test this origin, starting at the starting URL's path on it, instead of the starting
+    /// URL's origin. Can be given multiple times to test each origin in turn, e.g. staging and
+    /// production
+    #[arg(long = "test-origin", value_name = "URL", conflicts_with = "origin")]
+    test_origins: Vec<UrlArgument>,
     /// Where to store output data (trace, screenshots, etc)
     #[arg(long)]
     output_path: Option<PathBuf>,
     /// Whether to exit the test when first failing property is found (useful in development and CI)
-    #[arg(long)]
+    #[arg(long, action = clap::ArgAction::Set, num_args = 0..=1, require_equals = true, default_value_t = false, default_missing_value = "true")]
     exit_on_violation: bool,
     /// Browser viewport width in pixels
     #[arg(long, default_value_t = 1024)]
@@ -48,6 +267,590 @@ struct TestSharedOptions {
     /// Comma-separated list of: "files", "inline"
     #[arg(long, default_value = "files,inline", value_parser = parse_instrumentation_config)]
     instrument_javascript: InstrumentationConfig,
+    /// JavaScript file whose contents are added to every instrumented script, after the prelude
+    /// defining `window.__bombadil__`, e.g. to forward coverage to an external collector
+    #[arg(long, value_name = "FILE")]
+    instrumentation_banner: Option<PathBuf>,
+    /// JavaScript file whose contents are added to the end of every instrumented script
+    #[arg(long, value_name = "FILE")]
+    instrumentation_footer: Option<PathBuf>,
+    /// How to bucket hit counts of edges when comparing coverage between states
+    #[arg(long, value_enum, default_value_t = CoverageModeKind::HitCounts)]
+    coverage_mode: CoverageModeKind,
+    /// How to identify scripts when computing coverage
+    #[arg(long, value_enum, default_value_t = SourceIds::Etag)]
+    source_ids: SourceIds,
+    /// Block requests with URLs matching this pattern (`*` and `?` wildcards), can be given
+    /// multiple times
+    #[arg(long = "block", value_name = "PATTERN")]
+    block_urls: Vec<UrlPattern>,
+    /// Respond to requests with URLs matching the pattern with the JSON contents of the file,
+    /// can be given multiple times
+    #[arg(long = "stub", value_name = "PATTERN=FILE", value_parser = parse_stub)]
+    stub_urls: Vec<(UrlPattern, PathBuf)>,
+    /// Only explore URLs matching this pattern (`*` and `?` wildcards), can be given multiple
+    /// times
+    #[arg(long = "allow-url", value_name = "PATTERN")]
+    allow_urls: Vec<UrlPattern>,
+    /// Never explore URLs matching this pattern (`*` and `?` wildcards), can be given multiple
+    /// times
+    #[arg(long = "deny-url", value_name = "PATTERN")]
+    deny_urls: Vec<UrlPattern>,
+    /// Also explore URLs outside the origin's domain matching this pattern, e.g. to sign in
+    /// through another site, can be given multiple times
+    #[arg(long = "allow-external-url", value_name = "PATTERN")]
+    external_urls: Vec<UrlPattern>,
+    /// Wait this many milliseconds before each action, highlighting its target (useful for
+    /// following a test when not in headless mode)
+    #[arg(long, value_name = "MILLIS")]
+    slow_motion: Option<u64>,
+    /// Action to take when the specification offers no actions in the current state
+    #[arg(long, value_enum, default_value_t = FallbackAction::Reload)]
+    fallback_action: FallbackAction,
+    /// Show the last state checked for each violated `eventually`, and why it didn't hold
+    #[arg(long, action = clap::ArgAction::Set, num_args = 0..=1, require_equals = true, default_value_t = false, default_missing_value = "true")]
+    eventually_evidence: bool,
+    /// Only check the property with this name, can be given multiple times (combines with
+    /// `--tag`)
+    #[arg(long = "property", value_name = "NAME")]
+    properties: Vec<String>,
+    /// Only check properties tagged with this tag (using `.tag(...)` in the specification), can
+    /// be given multiple times
+    #[arg(long = "tag", value_name = "TAG")]
+    tags: Vec<String>,
+    /// Fail when the specification exports values that are neither properties nor action
+    /// generators, instead of ignoring them as helpers
+    #[arg(long, action = clap::ArgAction::Set, num_args = 0..=1, require_equals = true, default_value_t = false, default_missing_value = "true")]
+    strict_spec: bool,
+    /// Wait at most this many milliseconds after each action (by default, the wait depends on
+    /// the action, e.g. on the text length when typing)
+    #[arg(long, value_name = "MILLIS")]
+    max_action_timeout: Option<u64>,
+    /// Explore this many states before evaluating properties, which then start from the next
+    /// state
+    #[arg(long, value_name = "STEPS", default_value_t = 0)]
+    warmup_steps: u64,
+    /// Wait at least this many milliseconds between actions, e.g. to avoid overloading a shared
+    /// server
+    #[arg(long, value_name = "MILLIS")]
+    min_action_interval: Option<u64>,
+    /// Never act on elements matching this CSS selector (or their descendants), e.g. destructive
+    /// buttons, can be given multiple times
+    #[arg(long = "exclude-selector", value_name = "SELECTOR")]
+    exclude_selectors: Vec<String>,
+    /// Stop the test when this many consecutive states bring no new coverage (or, with
+    /// `--state-hash dom`, no DOM structure not seen before), as exploration has then plateaued
+    #[arg(long, value_name = "STATES")]
+    stop_after_no_new_coverage: Option<u64>,
+    /// Fail the test with exit code 3 when it ends with less coverage than this, e.g.
+    /// "edges:500", "files:3", or "percent:90" (of scripts instrumented rather than failing)
+    #[arg(long, value_name = "THRESHOLD")]
+    min_coverage: Option<CoverageThreshold>,
+    /// Keep exploring after violations, reporting each violated property once, so that a run
+    /// collects all distinct violations
+    #[arg(long, action = clap::ArgAction::Set, num_args = 0..=1, require_equals = true, default_value_t = false, default_missing_value = "true", conflicts_with = "exit_on_violation")]
+    collect_violations: bool,
+    /// Store the values extracted by the specification in the trace, so that specifications can
+    /// be checked against it later with `bombadil replay-spec`
+    #[arg(long, action = clap::ArgAction::Set, num_args = 0..=1, require_equals = true, default_value_t = false, default_missing_value = "true")]
+    record_snapshots: bool,
+    /// Store how the DOM changed between states in the trace (up to 100 changes per state), e.g.
+    /// to see why an extracted value changed
+    #[arg(long, action = clap::ArgAction::Set, num_args = 0..=1, require_equals = true, default_value_t = false, default_missing_value = "true")]
+    record_dom_changes: bool,
+    /// Log how much of the app's code was instrumented for coverage when the test ends, and how
+    /// many scripts failed to be (the summary always includes these)
+    #[arg(long)]
+    coverage_stats: bool,
+    /// Wait until an element matching this CSS selector is visible before exploring, e.g. to
+    /// skip a splash screen
+    #[arg(long, value_name = "SELECTOR", conflicts_with = "start_expression")]
+    start_selector: Option<String>,
+    /// Wait until this JavaScript expression is truthy in the page before exploring
+    #[arg(long, value_name = "EXPRESSION")]
+    start_expression: Option<String>,
+    /// Fail the test when the start condition isn't met within this many seconds
+    #[arg(long, value_name = "SECS", default_value_t = 60)]
+    start_timeout: u64,
+    /// JSON file mapping input types (e.g. "email" or "date") to arrays of strings to type in
+    /// such inputs, which are likelier than random text to pass validation
+    #[arg(long, value_name = "FILE")]
+    dictionary: Option<PathBuf>,
+    /// Give states virtual times instead of wall-clock ones, starting at the Unix epoch and
+    /// advancing this many milliseconds per state, so that time bounds are reproducible
+    #[arg(long, value_name = "MILLIS", value_parser = clap::value_parser!(u64).range(1..))]
+    virtual_clock: Option<u64>,
+    /// Which hash identifies states, in the trace and when deciding whether a state is new
+    #[arg(long, value_enum, default_value_t = StateHashKind::Coverage)]
+    state_hash: StateHashKind,
+    /// Fail the test when a script or document can't be instrumented for coverage, instead of
+    /// passing it through as-is
+    #[arg(long, action = clap::ArgAction::Set, num_args = 0..=1, require_equals = true, default_value_t = false, default_missing_value = "true")]
+    strict_instrumentation: bool,
+    /// Disable the pages' Content Security Policies, so that they don't block instrumented
+    /// scripts (this weakens the pages' security, so only use it against test environments)
+    #[arg(long, action = clap::ArgAction::Set, num_args = 0..=1, require_equals = true, default_value_t = false, default_missing_value = "true")]
+    bypass_csp: bool,
+    /// Accept untrusted certificates, e.g. self-signed ones of staging environments (only use
+    /// this against test environments)
+    #[arg(long, action = clap::ArgAction::Set, num_args = 0..=1, require_equals = true, default_value_t = false, default_missing_value = "true")]
+    ignore_certificate_errors: bool,
+    /// Pass scripts larger than this through without instrumenting them, to bound memory use
+    #[arg(long, value_name = "BYTES", default_value_t = 20_000_000)]
+    max_script_size: usize,
+    /// Fail the test when the page navigates more than this many times without settling into a
+    /// state, e.g. when redirecting in a loop
+    #[arg(long, value_name = "NAVIGATIONS", default_value_t = 20)]
+    redirect_loop_threshold: u32,
+    /// Give up on the browser after this many milliseconds without a response when capturing a
+    /// state, failing the test (or skipping the screenshot, coverage, and hashes) instead of
+    /// hanging
+    #[arg(long, value_name = "MILLIS", default_value_t = 30_000)]
+    cdp_timeout: u64,
+    /// Probe whether the page responds at this interval in milliseconds while acting, reporting
+    /// it as unresponsive when several probes in a row time out (0 disables probing)
+    #[arg(long, value_name = "MILLIS", default_value_t = 250)]
+    responsiveness_probe: u64,
+    /// How many browser events (DOM changes, console entries, and so on) to buffer, raise this
+    /// if warnings show events being dropped on pages that change a lot
+    #[arg(long, value_name = "EVENTS", default_value_t = 1024, value_parser = clap::value_parser!(u32).range(1..))]
+    event_buffer_size: u32,
+    /// Wait before capturing each state until this JavaScript expression is truthy in the page,
+    /// e.g. "!document.querySelector('[data-loading]')" for lazily rendered pages
+    #[arg(long, value_name = "EXPRESSION")]
+    ready_expression: Option<String>,
+    /// Capture the state anyway, with a warning, when the page isn't ready within this many
+    /// milliseconds
+    #[arg(long, value_name = "MILLIS", default_value_t = 5_000)]
+    ready_timeout: u64,
+    /// Wait up to this many seconds for the starting URL to respond with a status below 500
+    /// before starting the test, e.g. when the server is started concurrently in CI
+    #[arg(long, value_name = "SECS")]
+    wait_for_ready: Option<u64>,
+    /// Where to write a JSON summary of the run when it ends (defaults to stdout)
+    #[arg(long, value_name = "PATH")]
+    summary: Option<PathBuf>,
+    #[arg(skip)]
+    config: Option<Config>,
+    /// The options given on the command line, rather than left at their defaults.
+    #[arg(skip)]
+    given: HashSet<String>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SourceIds {
+    /// From the response's etag, or its body if it has none
+    Etag,
+    /// From the response's body
+    Body,
+    /// From the URL's path, keeping coverage comparable across deploys
+    UrlPath,
+}
+
+impl SourceIds {
+    fn to_strategy(self) -> SourceIdStrategy {
+        match self {
+            SourceIds::Etag => SourceIdStrategy::FromEtag,
+            SourceIds::Body => SourceIdStrategy::FromBody,
+            SourceIds::UrlPath => SourceIdStrategy::FromUrlPath,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CoverageModeKind {
+    /// Into buckets similar to AFL's, so that hitting an edge more often counts as new coverage
+    HitCounts,
+    /// Only whether an edge was hit, for stabler state hashes when hit counts vary
+    Presence,
+}
+
+impl CoverageModeKind {
+    fn to_coverage_mode(self) -> CoverageMode {
+        match self {
+            CoverageModeKind::HitCounts => CoverageMode::HitCounts,
+            CoverageModeKind::Presence => CoverageMode::Presence,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum StateHashKind {
+    /// From the coverage of the transition to the state
+    Coverage,
+    /// From the DOM's structure (tags and attribute names), which is more stable when coverage
+    /// is noisy
+    Dom,
+}
+
+impl StateHashKind {
+    fn to_state_hash(self) -> StateHash {
+        match self {
+            StateHashKind::Coverage => StateHash::Coverage,
+            StateHashKind::Dom => StateHash::Dom,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum FallbackAction {
+    Reload,
+    Back,
+    /// Fail the test with "no actions available"
+    None,
+}
+
+impl FallbackAction {
+    fn to_browser_action(self) -> Option<BrowserAction> {
+        match self {
+            FallbackAction::Reload => Some(BrowserAction::Reload),
+            FallbackAction::Back => Some(BrowserAction::Back),
+            FallbackAction::None => None,
+        }
+    }
+}
+
+impl TestSharedOptions {
+    /// The option's value from the command line if it was given there, or when there's no
+    /// configuration file, and otherwise the file's (or the default) value.
+    fn pick<T>(&self, id: &str, configured: T, given: T) -> T {
+        if self.config.is_none() || self.given.contains(id) {
+            given
+        } else {
+            configured
+        }
+    }
+
+    fn browser_options(&self) -> Result<BrowserOptions> {
+        let configured = self
+            .config
+            .as_ref()
+            .map(|config| config.browser.clone())
+            .unwrap_or_default();
+        Ok(BrowserOptions {
+            emulation: Emulation {
+                width: self.pick(
+                    "width",
+                    configured.emulation.width,
+                    self.width,
+                ),
+                height: self.pick(
+                    "height",
+                    configured.emulation.height,
+                    self.height,
+                ),
+                device_scale_factor: self.pick(
+                    "device_scale_factor",
+                    configured.emulation.device_scale_factor,
+                    self.device_scale_factor,
+                ),
+            },
+            instrumentation: self.instrumentation()?,
+            request_rules: self.request_rules()?,
+            strict_instrumentation: self.pick(
+                "strict_instrumentation",
+                configured.strict_instrumentation,
+                self.strict_instrumentation,
+            ),
+            bypass_csp: self.pick(
+                "bypass_csp",
+                configured.bypass_csp,
+                self.bypass_csp,
+            ),
+            ignore_certificate_errors: self.pick(
+                "ignore_certificate_errors",
+                configured.ignore_certificate_errors,
+                self.ignore_certificate_errors,
+            ),
+            max_script_size: self.pick(
+                "max_script_size",
+                configured.max_script_size,
+                Some(self.max_script_size),
+            ),
+            slow_motion: self.pick(
+                "slow_motion",
+                configured.slow_motion,
+                self.slow_motion.map(Duration::from_millis),
+            ),
+            redirect_loop_threshold: self.pick(
+                "redirect_loop_threshold",
+                configured.redirect_loop_threshold,
+                Some(self.redirect_loop_threshold),
+            ),
+            cdp_timeout: self.pick(
+                "cdp_timeout",
+                configured.cdp_timeout,
+                Some(Duration::from_millis(self.cdp_timeout)),
+            ),
+            responsiveness_probe: self.pick(
+                "responsiveness_probe",
+                configured.responsiveness_probe,
+                self.responsiveness_probe(),
+            ),
+            coverage_mode: self.pick(
+                "coverage_mode",
+                configured.coverage_mode,
+                self.coverage_mode.to_coverage_mode(),
+            ),
+            record_dom_changes: self.pick(
+                "record_dom_changes",
+                configured.record_dom_changes,
+                self.record_dom_changes,
+            ),
+            event_buffer_size: self.pick(
+                "event_buffer_size",
+                configured.event_buffer_size,
+                self.event_buffer_size as usize,
+            ),
+            readiness: self.readiness(configured.readiness),
+            ..configured
+        })
+    }
+
+    fn runner_options(&self) -> Result<RunnerOptions> {
+        let configured = self
+            .config
+            .as_ref()
+            .map(|config| config.runner.clone())
+            .unwrap_or_default();
+        Ok(RunnerOptions {
+            stop_on_violation: self.pick(
+                "exit_on_violation",
+                configured.stop_on_violation,
+                self.exit_on_violation,
+            ),
+            fallback_action: self.pick(
+                "fallback_action",
+                configured.fallback_action,
+                self.fallback_action.to_browser_action(),
+            ),
+            url_scope: UrlScope {
+                allow: self.pick(
+                    "allow_urls",
+                    configured.url_scope.allow,
+                    self.allow_urls.clone(),
+                ),
+                deny: self.pick(
+                    "deny_urls",
+                    configured.url_scope.deny,
+                    self.deny_urls.clone(),
+                ),
+                external: self.pick(
+                    "external_urls",
+                    configured.url_scope.external,
+                    self.external_urls.clone(),
+                ),
+            },
+            eventually_evidence: self.pick(
+                "eventually_evidence",
+                configured.eventually_evidence,
+                self.eventually_evidence,
+            ),
+            property_filter: PropertyFilter {
+                properties: self.pick(
+                    "properties",
+                    configured.property_filter.properties,
+                    self.properties.clone(),
+                ),
+                tags: self.pick(
+                    "tags",
+                    configured.property_filter.tags,
+                    self.tags.clone(),
+                ),
+            },
+            strict_spec: self.pick(
+                "strict_spec",
+                configured.strict_spec,
+                self.strict_spec,
+            ),
+            max_action_timeout: self.pick(
+                "max_action_timeout",
+                configured.max_action_timeout,
+                self.max_action_timeout.map(Duration::from_millis),
+            ),
+            warmup_steps: self.pick(
+                "warmup_steps",
+                configured.warmup_steps,
+                self.warmup_steps,
+            ),
+            min_action_interval: self.pick(
+                "min_action_interval",
+                configured.min_action_interval,
+                self.min_action_interval.map(Duration::from_millis),
+            ),
+            state_hash: self.pick(
+                "state_hash",
+                configured.state_hash,
+                self.state_hash.to_state_hash(),
+            ),
+            stop_after_no_new_coverage: self.pick(
+                "stop_after_no_new_coverage",
+                configured.stop_after_no_new_coverage,
+                self.stop_after_no_new_coverage,
+            ),
+            exclude_selectors: self.pick(
+                "exclude_selectors",
+                configured.exclude_selectors,
+                self.exclude_selectors.clone(),
+            ),
+            record_snapshots: self.pick(
+                "record_snapshots",
+                configured.record_snapshots,
+                self.record_snapshots,
+            ),
+            start_condition: if self.config.is_none()
+                || self.given.contains("start_selector")
+                || self.given.contains("start_expression")
+            {
+                self.start_condition()
+            } else {
+                configured.start_condition
+            },
+            start_timeout: self.pick(
+                "start_timeout",
+                configured.start_timeout,
+                Duration::from_secs(self.start_timeout),
+            ),
+            dictionary: self.pick(
+                "dictionary",
+                configured.dictionary,
+                self.dictionary()?,
+            ),
+            clock: match self.virtual_clock {
+                Some(step) => {
+                    Clock::Virtual(UNIX_EPOCH, Duration::from_millis(step))
+                }
+                None => Clock::Wall,
+            },
+            min_coverage: self.pick(
+                "min_coverage",
+                configured.min_coverage,
+                self.min_coverage,
+            ),
+            report_violations_once: self.pick(
+                "collect_violations",
+                configured.report_violations_once,
+                self.collect_violations,
+            ),
+            state_classifier: None,
+        })
+    }
+
+    /// The origins to test, each with the starting URL if the browser shouldn't start at the
+    /// origin. With test origins, the starting URL's path, query, and fragment are used on each
+    /// of them.
+    fn origins(&self) -> Vec<(Url, Option<Url>)> {
+        let start_url = &self.start_url.url;
+        match (&self.origin, self.test_origins.as_slice()) {
+            (Some(origin), _) => {
+                vec![(origin.url.clone(), Some(start_url.clone()))]
+            }
+            (None, []) => vec![(start_url.clone(), None)],
+            (None, origins) => origins
+                .iter()
+                .map(|origin| {
+                    let start_url = origin
+                        .url
+                        .join(&start_url[Position::BeforePath..])
+                        .unwrap_or_else(|_| origin.url.clone());
+                    (origin.url.clone(), Some(start_url))
+                })
+                .collect(),
+        }
+    }
+
+    fn start_condition(&self) -> Option<StartCondition> {
+        match (&self.start_selector, &self.start_expression) {
+            (Some(selector), _) => {
+                Some(StartCondition::SelectorVisible(selector.clone()))
+            }
+            (None, Some(expression)) => {
+                Some(StartCondition::Expression(expression.clone()))
+            }
+            (None, None) => None,
+        }
+    }
+
+    fn readiness(&self, configured: Option<Readiness>) -> Option<Readiness> {
+        let (expression, timeout) = match configured {
+            Some(readiness) => (Some(readiness.expression), readiness.timeout),
+            None => (None, Duration::from_millis(self.ready_timeout)),
+        };
+        self.pick(
+            "ready_expression",
+            expression,
+            self.ready_expression.clone(),
+        )
+        .map(|expression| Readiness {
+            expression,
+            timeout: self.pick(
+                "ready_timeout",
+                timeout,
+                Duration::from_millis(self.ready_timeout),
+            ),
+        })
+    }
+
+    fn responsiveness_probe(&self) -> Option<Duration> {
+        (self.responsiveness_probe > 0)
+            .then(|| Duration::from_millis(self.responsiveness_probe))
+    }
+
+    fn dictionary(&self) -> Result<HashMap<String, Vec<String>>> {
+        let Some(path) = &self.dictionary else {
+            return Ok(HashMap::new());
+        };
+        let contents = std::fs::read_to_string(path).with_context(|| {
+            format!("failed reading dictionary {}", path.display())
+        })?;
+        json::from_str(&contents)
+            .with_context(|| format!("invalid dictionary {}", path.display()))
+    }
+
+    fn instrumentation(&self) -> Result<InstrumentationConfig> {
+        let read = |path: &Option<PathBuf>| {
+            path.as_ref()
+                .map(|path| {
+                    std::fs::read_to_string(path).with_context(|| {
+                        format!(
+                            "failed reading instrumentation file {}",
+                            path.display()
+                        )
+                    })
+                })
+                .transpose()
+        };
+        Ok(InstrumentationConfig {
+            source_ids: self.source_ids.to_strategy(),
+            banner: Banner {
+                banner: read(&self.instrumentation_banner)?,
+                footer: read(&self.instrumentation_footer)?,
+            },
+            ..self.instrument_javascript.clone()
+        })
+    }
+
+    fn request_rules(&self) -> Result<Vec<RequestRule>> {
+        let mut rules = vec![];
+        if !self.block_urls.is_empty() {
+            rules.push(RequestRule::BlockUrls(self.block_urls.clone()));
+        }
+        if !self.stub_urls.is_empty() {
+            let mut stubs = Vec::with_capacity(self.stub_urls.len());
+            for (pattern, path) in &self.stub_urls {
+                let body =
+                    std::fs::read_to_string(path).with_context(|| {
+                        format!("failed reading stub file {}", path.display())
+                    })?;
+                stubs.push((
+                    pattern.clone(),
+                    StubResponse { status: 200, body },
+                ));
+            }
+            rules.push(RequestRule::StubUrls(stubs));
+        }
+        Ok(rules)
+    }
 }
 
 #[derive(clap::Subcommand)]
@@ -62,6 +865,27 @@ enum Command {
         /// Disable Chromium sandboxing
         #[arg(long, default_value_t = false)]
         no_sandbox: bool,
+        /// The Chrome or Chromium executable to launch (by default, the one found on the system)
+        #[arg(long, value_name = "PATH")]
+        browser_executable: Option<PathBuf>,
+        /// Argument to pass to the browser, e.g. `--browser-arg=--lang=de`, can be given multiple
+        /// times
+        #[arg(
+            long = "browser-arg",
+            value_name = "ARG",
+            allow_hyphen_values = true
+        )]
+        browser_args: Vec<String>,
+        /// How many times to try launching the browser before giving up
+        #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..))]
+        launch_attempts: u32,
+        /// Milliseconds to wait after the first failed launch attempt, doubling after each one
+        #[arg(long, value_name = "MILLIS", default_value_t = 500)]
+        launch_backoff: u64,
+        /// Browser profile directory to reuse across runs, e.g. to keep a logged-in session
+        /// (created if missing, and must not be used by multiple browsers at once)
+        #[arg(long, value_name = "PATH")]
+        user_data_dir: Option<PathBuf>,
     },
     /// Run a test with an externally managed browser or Electron app (e.g. `chromium
     /// --remote-debugging-port=9992`)
@@ -71,19 +895,86 @@ enum Command {
         /// Address to the remote debugger's server, e.g. http://localhost:9222
         #[arg(long)]
         remote_debugger: Url,
+        /// Header to send when connecting to the remote debugger, e.g. for authenticating with a
+        /// hosted browser (requires a ws:// or wss:// remote debugger URL), can be given multiple
+        /// times
+        #[arg(long = "remote-debugger-header", value_name = "NAME:VALUE", value_parser = parse_header)]
+        remote_debugger_headers: Vec<(String, String)>,
+        /// How many times to try connecting to the remote debugger when starting before giving up
+        /// (a connection lost during the test isn't re-established, and fails it)
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        initial_connect_attempts: u32,
+        /// Milliseconds to wait after the first failed connection attempt, doubling after each
+        /// one
+        #[arg(long, value_name = "MILLIS", default_value_t = 500)]
+        initial_connect_backoff: u64,
         /// Whether Bombadil should create a new tab and navigate to the origin URL in it, as part
         /// of starting the test (this should probably be false if you test an Electron app)
         #[arg(long)]
         create_target: bool,
+        /// Whether Bombadil should navigate to the origin URL when starting the test, rather than
+        /// exploring from the currently open page (defaults to the value of --create-target)
+        #[arg(long)]
+        navigate_to_origin: Option<bool>,
+    },
+    /// Print a JavaScript file with coverage instrumentation added, for debugging the
+    /// instrumentation
+    Instrument {
+        /// The JavaScript file to instrument
+        file: PathBuf,
+        /// How to parse the file (defaults to module for `.mjs` files, script for `.cjs` files,
+        /// and otherwise deciding by its syntax)
+        #[arg(long, value_enum)]
+        source_type: Option<SourceTypeOption>,
+    },
+    /// List the properties of a specification with their formulas, without running a test
+    ListProperties {
+        /// The specification to list the properties of
+        specification_file: PathBuf,
+    },
+    /// Check a specification's properties against states given in a JSON file, without a
+    /// browser, for unit testing properties
+    CheckSpec {
+        /// The specification to check
+        specification_file: PathBuf,
+        /// JSON file with the states to check, as an array of objects with a `timestamp` (in
+        /// milliseconds) and the `extracted` value of each extractor by name
+        #[arg(long, value_name = "FILE")]
+        trace: PathBuf,
     },
+    /// Check a specification's properties against a trace recorded with `--record-snapshots`,
+    /// without a browser, e.g. to see how a changed specification judges past runs
+    ReplaySpec {
+        /// The trace directory (or its `trace.jsonl` file)
+        #[arg(long, value_name = "DIR")]
+        trace: PathBuf,
+        /// The specification to check
+        #[arg(long, value_name = "FILE")]
+        specification_file: PathBuf,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SourceTypeOption {
+    Script,
+    Module,
+}
+
+impl SourceTypeOption {
+    fn to_source_type(self) -> SourceType {
+        match self {
+            SourceTypeOption::Script => SourceType::cjs(),
+            SourceTypeOption::Module => SourceType::mjs(),
+        }
+    }
 }
 
 #[derive(Clone)]
-struct Origin {
+struct UrlArgument {
     url: Url,
 }
 
-impl FromStr for Origin {
+impl FromStr for UrlArgument {
     type Err = url::ParseError;
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
@@ -95,7 +986,7 @@ impl FromStr for Origin {
                     .to_str()
                     .expect("invalid path")
             )))
-            .map(|url| Origin { url })
+            .map(|url| UrlArgument { url })
     }
 }
 
@@ -127,44 +1018,71 @@ fn parse_instrumentation_config(
     Ok(InstrumentationConfig {
         instrument_files,
         instrument_inline,
+        ..InstrumentationConfig::none()
     })
 }
 
+fn parse_stub(s: &str) -> std::result::Result<(UrlPattern, PathBuf), String> {
+    let (pattern, path) = s
+        .rsplit_once('=')
+        .ok_or_else(|| format!("expected PATTERN=FILE, got '{}'", s))?;
+    Ok((pattern.parse()?, PathBuf::from(path)))
+}
+
+fn parse_header(s: &str) -> std::result::Result<(String, String), String> {
+    let (name, value) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected NAME:VALUE, got '{}'", s))?;
+    Ok((name.trim().to_string(), value.trim().to_string()))
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
-    let env = env_logger::Env::default().default_filter_or("info");
-    env_logger::Builder::from_env(env)
-        .format_timestamp_millis()
-        .format_target(true)
-        // Until we hav a fix for https://github.com/mattsse/chromiumoxide/issues/287
-        .filter_module("chromiumoxide::browser", log::LevelFilter::Error)
-        .filter_module("html5ever", log::LevelFilter::Info)
-        .init();
-    let cli = Cli::parse();
+    let cli = Cli::parse_with_config()?;
+    cli.logging.init();
     match cli.command {
         Command::Test {
             shared,
             headless,
             no_sandbox,
+            browser_executable,
+            browser_args,
+            launch_attempts,
+            launch_backoff,
+            user_data_dir,
         } => {
-            let user_data_directory = TempDir::with_prefix("user_data_")?;
+            // Deleted when dropped, after the test.
+            let mut temporary_user_data_directory = None;
+            let user_data_directory = match user_data_dir {
+                Some(path) => {
+                    std::fs::create_dir_all(&path).with_context(|| {
+                        format!(
+                            "failed to create user data directory {}",
+                            path.display()
+                        )
+                    })?;
+                    path
+                }
+                None => temporary_user_data_directory
+                    .insert(TempDir::with_prefix("user_data_")?)
+                    .path()
+                    .to_path_buf(),
+            };
 
             let browser_options = BrowserOptions {
                 create_target: true,
-                emulation: Emulation {
-                    width: shared.width,
-                    height: shared.height,
-                    device_scale_factor: shared.device_scale_factor,
-                },
-                instrumentation: shared.instrument_javascript.clone(),
+                navigate_to_origin: true,
+                ..shared.browser_options()?
             };
             let debugger_options = DebuggerOptions::Managed {
                 launch_options: LaunchOptions {
                     headless,
-                    user_data_directory: user_data_directory
-                        .path()
-                        .to_path_buf(),
+                    user_data_directory,
                     no_sandbox,
+                    executable: browser_executable,
+                    extra_args: browser_args,
+                    launch_attempts,
+                    launch_backoff: Duration::from_millis(launch_backoff),
                 },
             };
             test(shared, browser_options, debugger_options).await
@@ -172,24 +1090,201 @@ async fn main() -> Result<()> {
         Command::TestExternal {
             shared,
             remote_debugger,
+            remote_debugger_headers,
+            initial_connect_attempts,
+            initial_connect_backoff,
             create_target,
+            navigate_to_origin,
         } => {
             let browser_options = BrowserOptions {
                 create_target,
-                emulation: Emulation {
-                    width: shared.width,
-                    height: shared.height,
-                    device_scale_factor: shared.device_scale_factor,
-                },
-                instrumentation: shared.instrument_javascript.clone(),
+                navigate_to_origin: navigate_to_origin.unwrap_or(create_target),
+                ..shared.browser_options()?
+            };
+            let debugger_options = DebuggerOptions::External {
+                remote_debugger,
+                headers: remote_debugger_headers.into_iter().collect(),
+                initial_connect_attempts,
+                initial_connect_backoff: Duration::from_millis(
+                    initial_connect_backoff,
+                ),
             };
-            let debugger_options =
-                DebuggerOptions::External { remote_debugger };
             test(shared, browser_options, debugger_options).await
         }
+        Command::Instrument { file, source_type } => {
+            instrument(&file, source_type)
+        }
+        Command::ListProperties { specification_file } => {
+            list_properties(&specification_file).await
+        }
+        Command::CheckSpec {
+            specification_file,
+            trace,
+        } => check_spec(&specification_file, &trace).await,
+        Command::ReplaySpec {
+            trace,
+            specification_file,
+        } => replay_spec(&specification_file, &trace).await,
     }
 }
 
+fn instrument(
+    path: &Path,
+    source_type: Option<SourceTypeOption>,
+) -> Result<()> {
+    let source_text = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let source_type = match source_type {
+        Some(source_type) => source_type.to_source_type(),
+        None => match path.extension().and_then(|extension| extension.to_str())
+        {
+            Some("mjs") => SourceType::mjs(),
+            Some("cjs") => SourceType::cjs(),
+            _ => SourceType::unambiguous(),
+        },
+    };
+    let instrumented = bombadil::instrumentation::js::instrument_source_code(
+        SourceId(0),
+        &source_text,
+        source_type,
+    )
+    .map_err(anyhow::Error::from)
+    .with_context(|| format!("failed to instrument {}", path.display()))?;
+    println!("{instrumented}");
+    Ok(())
+}
+
+fn specification_from_file(path: &Path) -> Specification {
+    let path = if path.is_relative() && !path.starts_with(".") {
+        PathBuf::from(".").join(path)
+    } else {
+        path.to_path_buf()
+    };
+    log::info!("loading specification from file: {}", path.display());
+    Specification {
+        module_specifier: path.display().to_string(),
+    }
+}
+
+async fn list_properties(specification_file: &Path) -> Result<()> {
+    let introspection = specification_from_file(specification_file)
+        .introspect()
+        .await?;
+    for (name, formula) in
+        introspection.properties.iter().zip(&introspection.formulas)
+    {
+        println!("{}: {}", name, render_formula(formula));
+    }
+    Ok(())
+}
+
+async fn check_spec(specification_file: &Path, trace: &Path) -> Result<()> {
+    let states: Vec<TestState> = serde_json::from_str(
+        &tokio::fs::read_to_string(trace)
+            .await
+            .with_context(|| format!("failed to read {}", trace.display()))?,
+    )
+    .with_context(|| format!("failed to parse {}", trace.display()))?;
+    check_states(specification_file, &states).await
+}
+
+async fn replay_spec(specification_file: &Path, trace: &Path) -> Result<()> {
+    #[derive(serde::Deserialize)]
+    struct RecordedState {
+        timestamp: SystemTime,
+        snapshots: Option<Vec<Snapshot>>,
+    }
+
+    // Traces are directories with a `trace.jsonl` file, but allow the file too.
+    let trace_path = if trace.is_dir() {
+        trace.join("trace.jsonl")
+    } else {
+        trace.to_path_buf()
+    };
+    let contents = tokio::fs::read_to_string(&trace_path)
+        .await
+        .with_context(|| format!("failed to read {}", trace_path.display()))?;
+    let mut states = vec![];
+    for (index, line) in contents.lines().enumerate() {
+        let recorded: RecordedState =
+            serde_json::from_str(line).with_context(|| {
+                format!(
+                    "failed to parse {}:{}",
+                    trace_path.display(),
+                    index + 1
+                )
+            })?;
+        let snapshots = recorded.snapshots.ok_or_else(|| {
+            anyhow::anyhow!(
+                "state {} has no extractor snapshots, record them with --record-snapshots",
+                index
+            )
+        })?;
+        let extracted = snapshots
+            .into_iter()
+            .map(|snapshot| {
+                let name = snapshot.name.ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "state {} has a snapshot of an unnamed extractor",
+                        index
+                    )
+                })?;
+                Ok((name, snapshot.value))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        states.push(TestState::new(recorded.timestamp, extracted));
+    }
+    check_states(specification_file, &states).await
+}
+
+/// Prints the value of every property in each state and the violations, exiting with status 2
+/// if there are any.
+async fn check_states(
+    specification_file: &Path,
+    states: &[TestState],
+) -> Result<()> {
+    let mut tester = SpecTester::new(
+        &specification_from_file(specification_file),
+        VerifierOptions::default(),
+    )
+    .await?;
+
+    let start = states.first().map(|state| state.timestamp);
+    // Violated properties stay false, so each violation is only shown the first time.
+    let mut violated = HashSet::new();
+    for (index, state) in states.iter().enumerate() {
+        for (name, value) in tester.step(state)? {
+            let verdict = match &value {
+                PropertyValue::True => "true",
+                PropertyValue::False(_) => "false",
+                PropertyValue::Residual => "pending",
+            };
+            println!("state {}: {}: {}", index, name, verdict);
+            if let PropertyValue::False(violation) = value
+                && violated.insert(name)
+            {
+                println!(
+                    "{}",
+                    render_violation_with(
+                        &violation,
+                        RenderOptions::human(
+                            TimeFormat::Relative {
+                                start: start.unwrap_or(state.timestamp),
+                            },
+                            std::io::stdout().is_terminal(),
+                        )
+                    )
+                );
+            }
+        }
+    }
+
+    if !violated.is_empty() {
+        std::process::exit(2);
+    }
+    Ok(())
+}
+
 async fn test(
     shared_options: TestSharedOptions,
     browser_options: BrowserOptions,
@@ -197,15 +1292,7 @@ async fn test(
 ) -> Result<()> {
     // Load a user-provided specification, or use the defaults provided by Bombadil.
     let specification = if let Some(path) = &shared_options.specification_file {
-        let path = if path.is_relative() && !path.starts_with(".") {
-            PathBuf::from(".").join(path)
-        } else {
-            path.clone()
-        };
-        log::info!("loading specification from file: {}", path.display());
-        Specification {
-            module_specifier: path.display().to_string(),
-        }
+        specification_from_file(path)
     } else {
         log::info!("using default specification");
         Specification {
@@ -213,51 +1300,261 @@ async fn test(
         }
     };
 
-    let output_path = match shared_options.output_path {
-        Some(path) => path,
+    let output_path = match &shared_options.output_path {
+        Some(path) => path.clone(),
         None => TempDir::with_prefix("states_")?.keep().to_path_buf(),
     };
 
+    let origins = shared_options.origins();
+    if let [(origin, start_url)] = origins.as_slice() {
+        let (summary, exit_code) = test_origin(
+            &shared_options,
+            specification,
+            origin.clone(),
+            BrowserOptions {
+                start_url: start_url.clone(),
+                ..browser_options
+            },
+            debugger_options,
+            output_path,
+        )
+        .await?;
+        write_summary(shared_options.summary.as_deref(), &summary).await?;
+        if let Some(exit_code) = exit_code? {
+            std::process::exit(exit_code);
+        }
+        return Ok(());
+    }
+
+    // With several origins, each is tested in turn with its own browser and trace, in a
+    // subdirectory of the output path.
+    let count = origins.len();
+    let mut summaries = Vec::with_capacity(count);
+    for (index, (origin, start_url)) in origins.into_iter().enumerate() {
+        log::info!("testing origin {} ({} of {})", origin, index + 1, count);
+        let started = Instant::now();
+        let summary = match test_origin(
+            &shared_options,
+            specification.clone(),
+            origin.clone(),
+            BrowserOptions {
+                start_url,
+                ..browser_options.clone()
+            },
+            debugger_options.clone(),
+            output_path.join(format!(
+                "{}-{}",
+                index + 1,
+                origin.host_str().unwrap_or("local")
+            )),
+        )
+        .await
+        {
+            Ok((summary, Ok(_))) => summary,
+            Ok((summary, Err(error))) => {
+                log::error!("testing {} failed: {:#}", origin, error);
+                summary
+            }
+            Err(error) => {
+                log::error!("testing {} failed: {:#}", origin, error);
+                RunSummary {
+                    outcome: RunOutcome::Error,
+                    error: Some(format!("{:#}", error)),
+                    steps: 0,
+                    duration_ms: started.elapsed().as_millis() as u64,
+                    properties: Default::default(),
+                    coverage_edges: 0,
+                    instrumentation_failures: 0,
+                    coverage_stats: Default::default(),
+                    violations: vec![],
+                    violation_trace: None,
+                }
+            }
+        };
+        let interrupted = summary.outcome == RunOutcome::Interrupted;
+        summaries.push(OriginSummary { origin, summary });
+        if interrupted {
+            break;
+        }
+    }
+
+    for OriginSummary { origin, summary } in &summaries {
+        match summary.outcome {
+            RunOutcome::Success => log::info!("{}: passed", origin),
+            RunOutcome::Violation => {
+                log::error!("{}: violated properties", origin)
+            }
+            RunOutcome::Error => {
+                log::error!("{}: failed with an error", origin)
+            }
+            RunOutcome::Interrupted => log::warn!("{}: interrupted", origin),
+            RunOutcome::InsufficientCoverage => {
+                log::error!("{}: coverage below the threshold", origin)
+            }
+        }
+    }
+
+    let summary = OriginsSummary::new(summaries);
+    write_summary(shared_options.summary.as_deref(), &summary).await?;
+    match summary.outcome {
+        RunOutcome::Success => Ok(()),
+        RunOutcome::Violation => std::process::exit(2),
+        RunOutcome::Error => std::process::exit(1),
+        RunOutcome::Interrupted => std::process::exit(130),
+        RunOutcome::InsufficientCoverage => std::process::exit(3),
+    }
+}
+
+/// Tests a single origin, returning the run's summary along with the code to exit with, if any,
+/// or the error that ended the run. Fails without a summary when the test couldn't start.
+async fn test_origin(
+    shared_options: &TestSharedOptions,
+    specification: Specification,
+    origin: Url,
+    browser_options: BrowserOptions,
+    debugger_options: DebuggerOptions,
+    output_path: PathBuf,
+) -> Result<(RunSummary, Result<Option<i32>>)> {
+    if let Some(timeout) = shared_options.wait_for_ready {
+        bombadil::readiness::wait_for_ready(
+            browser_options.start_url.as_ref().unwrap_or(&origin),
+            Duration::from_secs(timeout),
+            browser_options.ignore_certificate_errors,
+        )
+        .await?;
+    }
+
+    let runner_options = shared_options.runner_options()?;
+    let state_hash = runner_options.state_hash;
+    let stop_on_violation = runner_options.stop_on_violation;
+    let report_violations_once = runner_options.report_violations_once;
+
     let runner = Runner::new(
-        shared_options.origin.url,
+        origin,
         specification,
-        RunnerOptions {
-            stop_on_violation: shared_options.exit_on_violation,
-        },
+        runner_options,
         browser_options,
         debugger_options,
     )
     .await?;
+    let started = Instant::now();
+    let coverage_files = runner.coverage_files();
+    let instrumentation_failure_counts = runner.instrumentation_failures();
     let mut events = runner.start();
-    let mut writer = TraceWriter::initialize(output_path).await?;
+    let mut writer = TraceWriter::initialize(output_path, state_hash).await?;
 
+    let mut states = 0;
+    let mut edges = HashSet::new();
+    let mut instrumentation_failures = 0;
+    let mut verdicts: Vec<(String, Verdict)> = vec![];
+    let mut violated = false;
+    let mut first_violations: Vec<FirstViolation> = vec![];
+    let mut event_error = None;
+    let mut interrupted = false;
+    let mut exit_on_interrupt = None;
     let exit_code: anyhow::Result<Option<i32>> = async {
+        let mut run_start = None;
+        // Installing the handler replaces the default one, so we get to shut
+        // down the runner and flush the trace when interrupted.
+        let ctrl_c = tokio::signal::ctrl_c();
+        tokio::pin!(ctrl_c);
         loop {
-            match events.next().await {
+            let event = tokio::select! {
+                result = &mut ctrl_c => {
+                    result?;
+                    log::warn!(
+                        "interrupted, shutting down (interrupt again to exit right away)"
+                    );
+                    interrupted = true;
+                    // Shutting down can hang on an unresponsive browser, so another interrupt
+                    // exits without waiting for it, or for the trace to be flushed.
+                    exit_on_interrupt = Some(tokio::spawn(async {
+                        if tokio::signal::ctrl_c().await.is_ok() {
+                            eprintln!("interrupted again, exiting");
+                            std::process::exit(130);
+                        }
+                    }));
+                    break Ok(Some(130));
+                }
+                event = events.next() => event,
+            };
+            match event {
                 Ok(Some(bombadil::runner::RunEvent::NewState {
                     state,
                     last_action,
                     violations,
+                    properties,
+                    snapshots,
+                    labels,
                 })) => {
                     let has_violations = !violations.is_empty();
+                    let start = *run_start.get_or_insert(state.timestamp);
+                    states += 1;
+                    edges.extend(
+                        state
+                            .coverage
+                            .edges_new
+                            .iter()
+                            .map(|(index, _)| *index),
+                    );
+                    instrumentation_failures =
+                        state.coverage.instrumentation_failures;
+                    verdicts = properties;
+                    violated |= has_violations;
 
                     for violation in &violations {
+                        if !first_violations
+                            .iter()
+                            .any(|first| first.property == violation.name)
+                        {
+                            first_violations.push(FirstViolation {
+                                property: violation.name.clone(),
+                                step: states,
+                            });
+                        }
                         log::error!(
-                            "violation of property `{}`:\n{}",
-                            violation.name,
-                            render_violation(&violation.violation)
+                            "{}:\n{}",
+                            render_violation_summary(
+                                &violation.name,
+                                states,
+                                state
+                                    .timestamp
+                                    .duration_since(start)
+                                    .unwrap_or_default(),
+                            ),
+                            render_violation_with(
+                                &violation.violation,
+                                // Logs are written to stderr.
+                                RenderOptions::human(
+                                    TimeFormat::Relative { start },
+                                    std::io::stderr().is_terminal(),
+                                )
+                            )
                         );
                     }
 
-                    writer.write(last_action, state, violations).await?;
+                    writer
+                        .write(
+                            last_action,
+                            state,
+                            violations,
+                            snapshots,
+                            labels,
+                        )
+                        .await?;
 
-                    if has_violations && shared_options.exit_on_violation {
+                    if has_violations && stop_on_violation {
                         break Ok(Some(2));
                     }
                 }
+                Ok(Some(bombadil::runner::RunEvent::ActionFailed {
+                    ..
+                })) => {}
+                Ok(Some(bombadil::runner::RunEvent::Plateaued { .. })) => {}
                 Ok(None) => break Ok(None),
                 Err(err) => {
                     eprintln!("next run event failure: {}", err);
+                    event_error = Some(err.to_string());
                     break Ok(Some(1));
                 }
             }
@@ -265,11 +1562,262 @@ async fn test(
     }
     .await;
 
-    events.shutdown().await?;
+    let shutdown_result = events.shutdown().await;
+    // Falling short of the coverage threshold isn't an error, but an outcome of its own.
+    let (shutdown_result, coverage_shortfall) = match shutdown_result {
+        Err(error) => match error.downcast::<CoverageBelowThreshold>() {
+            Ok(shortfall) => {
+                log::error!("{}", shortfall);
+                (Ok(()), Some(shortfall))
+            }
+            Err(error) => (Err(error), None),
+        },
+        Ok(()) => (Ok(()), None),
+    };
+    let flush_result = writer.flush().await;
+    if let Some(exit_on_interrupt) = exit_on_interrupt {
+        exit_on_interrupt.abort();
+    }
 
-    if let Some(exit_code) = exit_code? {
-        std::process::exit(exit_code);
+    let error = match (&exit_code, &shutdown_result, &flush_result) {
+        (Err(error), _, _) | (_, Err(error), _) | (_, _, Err(error)) => {
+            Some(error.to_string())
+        }
+        _ => event_error,
+    };
+    let coverage_stats = CoverageStats::collect(
+        &coverage_files,
+        &instrumentation_failure_counts,
+    );
+    if shared_options.coverage_stats {
+        log::info!(
+            "instrumented {} files ({} bytes, {} with instrumentation), skipped {} opting \
+             out, failed on {} parse errors, {} semantic errors, and {} other failures",
+            coverage_stats.files,
+            coverage_stats.skipped,
+            coverage_stats.original_bytes,
+            coverage_stats.instrumented_bytes,
+            coverage_stats.parse_failures,
+            coverage_stats.semantic_failures,
+            coverage_stats.other_failures
+        );
+    }
+    if report_violations_once && !first_violations.is_empty() {
+        log::error!(
+            "{} properties violated: {}",
+            first_violations.len(),
+            first_violations
+                .iter()
+                .map(|first| format!(
+                    "{} (state {})",
+                    first.property, first.step
+                ))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
     }
+    let summary = RunSummary {
+        outcome: if error.is_some() {
+            RunOutcome::Error
+        } else if violated {
+            RunOutcome::Violation
+        } else if interrupted {
+            RunOutcome::Interrupted
+        } else if coverage_shortfall.is_some() {
+            RunOutcome::InsufficientCoverage
+        } else {
+            RunOutcome::Success
+        },
+        error,
+        steps: states,
+        duration_ms: started.elapsed().as_millis() as u64,
+        properties: verdicts.into_iter().collect(),
+        coverage_edges: edges.len(),
+        instrumentation_failures,
+        coverage_stats,
+        violations: first_violations,
+        violation_trace: violated.then(|| writer.trace_path().to_path_buf()),
+    };
+
+    let result =
+        shutdown_result
+            .and(flush_result)
+            .and(exit_code)
+            .map(|exit_code| match summary.outcome {
+                RunOutcome::InsufficientCoverage => exit_code.or(Some(3)),
+                _ => exit_code,
+            });
+    Ok((summary, result))
+}
 
+async fn write_summary(
+    path: Option<&Path>,
+    summary: &impl Serialize,
+) -> Result<()> {
+    let json = serde_json::to_string_pretty(summary)?;
+    match path {
+        Some(path) => {
+            tokio::fs::write(path, json).await.with_context(|| {
+                format!("failed writing summary to {}", path.display())
+            })?
+        }
+        None => println!("{}", json),
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_file(suffix: &str, contents: &str) -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::with_suffix(suffix).unwrap();
+        std::io::Write::write_all(&mut file, contents.as_bytes()).unwrap();
+        file
+    }
+
+    fn parse_shared(arguments: &[&str]) -> Result<TestSharedOptions> {
+        let cli = Cli::parse_with_config_from(
+            ["bombadil", "test", "http://localhost:8000"]
+                .iter()
+                .chain(arguments),
+        )?;
+        let Command::Test { shared, .. } = cli.command else {
+            unreachable!()
+        };
+        Ok(shared)
+    }
+
+    #[test]
+    fn test_config_overrides() {
+        let file = config_file(
+            ".toml",
+            r#"
+                [browser]
+                bypass_csp = true
+                cdp_timeout = { ms = 10000 }
+
+                [browser.emulation]
+                width = 1280
+
+                [runner]
+                exclude_selectors = [".delete"]
+                max_action_timeout = { ms = 500 }
+            "#,
+        );
+        let path = file.path().to_str().unwrap();
+
+        let shared = parse_shared(&["--config", path]).unwrap();
+        let browser = shared.browser_options().unwrap();
+        let runner = shared.runner_options().unwrap();
+        assert_eq!(browser.emulation.width, 1280);
+        assert_eq!(browser.emulation.height, 768);
+        assert!(browser.bypass_csp);
+        assert_eq!(browser.cdp_timeout, Some(Duration::from_secs(10)));
+        assert_eq!(runner.exclude_selectors, vec![".delete".to_string()]);
+        assert_eq!(runner.max_action_timeout, Some(Duration::from_millis(500)));
+
+        let shared = parse_shared(&[
+            "--config",
+            path,
+            "--bypass-csp=false",
+            "--height",
+            "900",
+            "--exclude-selector",
+            ".logout",
+        ])
+        .unwrap();
+        let browser = shared.browser_options().unwrap();
+        let runner = shared.runner_options().unwrap();
+        assert_eq!(browser.emulation.width, 1280);
+        assert_eq!(browser.emulation.height, 900);
+        assert!(!browser.bypass_csp);
+        assert_eq!(runner.exclude_selectors, vec![".logout".to_string()]);
+        assert_eq!(runner.max_action_timeout, Some(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn test_config_json() {
+        let file = config_file(
+            ".json",
+            r#"{ "runner": { "url_scope": { "deny": ["*/admin/*"] } } }"#,
+        );
+        let shared =
+            parse_shared(&["--config", file.path().to_str().unwrap()]).unwrap();
+        assert_eq!(
+            shared.runner_options().unwrap().url_scope.deny,
+            vec!["*/admin/*".parse::<UrlPattern>().unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_config_unknown_keys() {
+        let file = config_file(".toml", "[browser.emulation]\nwidht = 800\n");
+        let error = parse_shared(&["--config", file.path().to_str().unwrap()])
+            .err()
+            .unwrap();
+        assert!(
+            format!("{:#}", error).contains("unknown field `widht`"),
+            "{:#}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_origins() {
+        let origins = |arguments: &[&str]| {
+            let cli = Cli::try_parse_from(
+                ["bombadil", "test", "https://staging.example.com/app?id=1"]
+                    .iter()
+                    .chain(arguments),
+            )
+            .unwrap();
+            let Command::Test { shared, .. } = cli.command else {
+                unreachable!()
+            };
+            shared
+                .origins()
+                .into_iter()
+                .map(|(origin, start_url)| {
+                    (origin.to_string(), start_url.map(|url| url.to_string()))
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            origins(&[]),
+            vec![("https://staging.example.com/app?id=1".to_string(), None)]
+        );
+        assert_eq!(
+            origins(&["--origin", "https://staging.example.com"]),
+            vec![(
+                "https://staging.example.com/".to_string(),
+                Some("https://staging.example.com/app?id=1".to_string())
+            )]
+        );
+        assert_eq!(
+            origins(&["--test-origin", "https://example.com"]),
+            vec![(
+                "https://example.com/".to_string(),
+                Some("https://example.com/app?id=1".to_string())
+            )]
+        );
+        assert_eq!(
+            origins(&[
+                "--test-origin",
+                "https://staging.example.com",
+                "--test-origin",
+                "https://example.com",
+            ]),
+            vec![
+                (
+                    "https://staging.example.com/".to_string(),
+                    Some("https://staging.example.com/app?id=1".to_string())
+                ),
+                (
+                    "https://example.com/".to_string(),
+                    Some("https://example.com/app?id=1".to_string())
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use tokio::time::Instant;
+use url::Url;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const REQUEST_TIMEOUT_MAX: Duration = Duration::from_secs(5);
+
+/// Polls the URL until the server responds with a status below 500, or fails
+/// with "origin not reachable" when the timeout elapses.
+///
+/// Untrusted certificates are accepted when the browser is told to ignore
+/// certificate errors, as the server is then expected to have one.
+pub async fn wait_for_ready(
+    url: &Url,
+    timeout: Duration,
+    ignore_certificate_errors: bool,
+) -> Result<()> {
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(ignore_certificate_errors)
+        .build()
+        .context("failed creating HTTP client for readiness probe")?;
+    let deadline = Instant::now() + timeout;
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let result = tokio::time::timeout(
+            remaining.min(REQUEST_TIMEOUT_MAX),
+            probe(&client, url),
+        )
+        .await
+        .unwrap_or_else(|_| Err("timed out".to_string()));
+        match result {
+            Ok(()) => {
+                log::info!("{} is ready", url);
+                return Ok(());
+            }
+            Err(reason) => {
+                log::debug!("{} is not ready yet: {}", url, reason);
+                if Instant::now() + POLL_INTERVAL >= deadline {
+                    bail!(
+                        "origin not reachable: {} after {} attempts in {:?} ({})",
+                        url,
+                        attempts,
+                        timeout,
+                        reason
+                    );
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+}
+
+async fn probe(
+    client: &reqwest::Client,
+    url: &Url,
+) -> std::result::Result<(), String> {
+    let response = client
+        .get(url.clone())
+        .send()
+        .await
+        .map_err(|error| error.to_string())?;
+    if response.status().is_server_error() {
+        Err(format!("status {}", response.status()))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Router, http::StatusCode, routing::get};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    async fn serve(status: StatusCode) -> Url {
+        let app = Router::new().route("/", get(move || async move { status }));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Url::parse(&format!("http://{}/", address)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_ready() {
+        let url = serve(StatusCode::NOT_FOUND).await;
+        wait_for_ready(&url, Duration::from_secs(5), false)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_server_error() {
+        let url = serve(StatusCode::SERVICE_UNAVAILABLE).await;
+        let error = wait_for_ready(&url, Duration::from_secs(1), false)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().starts_with("origin not reachable"));
+    }
+
+    #[tokio::test]
+    async fn test_unreachable() {
+        // Bind and drop a listener to get a port that nothing listens on.
+        let address = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let url = Url::parse(&format!("http://{}/", address)).unwrap();
+        let error = wait_for_ready(&url, Duration::from_secs(1), false)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().starts_with("origin not reachable"));
+    }
+}
@@ -1,33 +1,371 @@
 use crate::browser::actions::BrowserAction;
+use crate::browser::instrumentation::{
+    CoverageStats, InstrumentationFailures, InstrumentedFiles,
+};
 use crate::browser::{BrowserEvent, BrowserOptions};
+use crate::error::BombadilError;
 use crate::instrumentation::js::EDGE_MAP_SIZE;
 use crate::specification::bundler::bundle;
-use crate::specification::verifier::{Snapshot, Specification};
-use crate::specification::worker::{PropertyValue, VerifierWorker};
+use crate::specification::js::JsAction;
+use crate::specification::verifier::{
+    PropertyFilter, Snapshot, Specification, VerifierOptions,
+};
+use crate::specification::worker::{PropertyValue, StepResult, VerifierWorker};
 use crate::trace::PropertyViolation;
+use crate::trace::summary::Verdict;
+use crate::tree::Tree;
 use ::url::Url;
+use serde::{Deserialize, Serialize};
 use serde_json as json;
 use std::cmp::max;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use tokio::sync::{broadcast, oneshot};
+use tokio::time::{Instant, sleep_until};
 use tokio::{select, spawn};
 
-use crate::browser::state::{BrowserState, Coverage};
+use crate::browser::state::{BrowserState, Coverage, StateHash};
 use crate::browser::{Browser, DebuggerOptions};
-use crate::url::is_within_domain;
+use crate::url::UrlScope;
 
+/// Deserialized from configuration files, where omitted fields keep their defaults (those of
+/// the command line) and durations are given as `{ "ms": N }`. Fields that only make sense
+/// programmatically can't be deserialized.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct RunnerOptions {
     pub stop_on_violation: bool,
+    /// Action to pick when the specification offers no actions in the current state, so that
+    /// exploration doesn't dead-end on pages without discovered actions. With `None`, the test
+    /// fails with "no actions available" instead.
+    pub fallback_action: Option<BrowserAction>,
+    /// Restricts exploration to URLs in scope. Navigations out of scope are cancelled, and if
+    /// the browser still ends up out of scope, the only action allowed is going back.
+    pub url_scope: UrlScope,
+    /// Keeps the most recent counterexample of each `eventually`, so that violations can show
+    /// how close the test came. Off by default to bound memory use.
+    pub eventually_evidence: bool,
+    /// Which of the specification's properties to check, all of them by default.
+    pub property_filter: PropertyFilter,
+    /// See [`VerifierOptions::strict_exports`].
+    pub strict_spec: bool,
+    /// Caps how long to wait after each action, as typing actions wait for as long as the
+    /// specification's text and delay take, which may stall the test.
+    #[serde(deserialize_with = "deserialize_optional_millis")]
+    pub max_action_timeout: Option<Duration>,
+    /// Explores this many states before evaluating properties, which then start from the next
+    /// state. Useful for reaching deeper states without the warm-up tripping properties.
+    pub warmup_steps: u64,
+    /// Waits at least this long between applying actions, so as not to hammer shared servers.
+    /// Time spent waiting for states after actions counts towards it.
+    #[serde(deserialize_with = "deserialize_optional_millis")]
+    pub min_action_interval: Option<Duration>,
+    /// Which hash identifies states when deciding whether a state is new. The DOM hash is more
+    /// stable when the coverage of equivalent states differs, e.g. because of timers, and is
+    /// only computed when selected.
+    pub state_hash: StateHash,
+    /// Ends the run when this many consecutive states brought no new coverage, i.e. no edge hit
+    /// more often (by bucket) than in any previous state, as exploration has then plateaued.
+    /// With [`StateHash::Dom`], states count as new when their DOM hash wasn't seen before
+    /// instead.
+    pub stop_after_no_new_coverage: Option<u64>,
+    /// CSS selectors of elements that must never be acted upon, e.g. "Delete account" buttons.
+    /// Clicks on matching elements (or their descendants) are removed from the candidates, as
+    /// are key presses and typing while one is focused.
+    pub exclude_selectors: Vec<String>,
+    /// Includes the extractor snapshots of each state in [`RunEvent::NewState`], for storing them
+    /// in the trace. Off by default as they can be large.
+    pub record_snapshots: bool,
+    /// Waits for the page to meet this condition before exploring, e.g. when a splash or login
+    /// screen comes first. Until then, the runner only waits, and no properties are checked.
+    pub start_condition: Option<StartCondition>,
+    /// Fails the test when the start condition isn't met within this long.
+    #[serde(deserialize_with = "deserialize_millis")]
+    pub start_timeout: Duration,
+    /// See [`VerifierOptions::dictionary`].
+    pub dictionary: HashMap<String, Vec<String>>,
+    /// Where the times of states come from, which the specification sees in `time` and in
+    /// the bounds of temporal operators.
+    #[serde(skip)]
+    pub clock: Clock,
+    /// Fails the run with [`CoverageBelowThreshold`] when it ends with less coverage, e.g. as
+    /// instrumentation broke and the run passed without exercising anything.
+    pub min_coverage: Option<CoverageThreshold>,
+    /// Reports each property's violation only in the first state it's violated in, instead of in
+    /// every state after, as a violated property stays false. Without `stop_on_violation`, a run
+    /// then collects each distinct violation once while exploring on.
+    pub report_violations_once: bool,
+    /// Labels each state, e.g. with the logical page it's on, for the labels to be included in
+    /// [`RunEvent::NewState`] and the trace. Only set programmatically, see
+    /// [`RunnerOptions::with_state_classifier`].
+    #[serde(skip)]
+    pub state_classifier: Option<StateClassifier>,
 }
 
+/// Computes labels for a state, like "login page" or "checkout".
+pub type StateClassifier =
+    Arc<dyn Fn(&BrowserState) -> Vec<String> + Send + Sync>;
+
+impl Default for RunnerOptions {
+    fn default() -> Self {
+        RunnerOptions {
+            stop_on_violation: false,
+            fallback_action: Some(BrowserAction::Reload),
+            url_scope: UrlScope::default(),
+            eventually_evidence: false,
+            property_filter: PropertyFilter::default(),
+            strict_spec: false,
+            max_action_timeout: None,
+            warmup_steps: 0,
+            min_action_interval: None,
+            state_hash: StateHash::default(),
+            stop_after_no_new_coverage: None,
+            exclude_selectors: vec![],
+            record_snapshots: false,
+            start_condition: None,
+            start_timeout: Duration::from_secs(60),
+            dictionary: HashMap::new(),
+            clock: Clock::default(),
+            min_coverage: None,
+            report_violations_once: false,
+            state_classifier: None,
+        }
+    }
+}
+
+impl RunnerOptions {
+    pub fn with_state_classifier(
+        self,
+        classifier: impl Fn(&BrowserState) -> Vec<String> + Send + Sync + 'static,
+    ) -> Self {
+        RunnerOptions {
+            state_classifier: Some(Arc::new(classifier)),
+            ..self
+        }
+    }
+}
+
+/// The least coverage a run must reach, checked when it ends. Deserialized from the same
+/// strings as it's parsed from, e.g. `"edges:500"`.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum CoverageThreshold {
+    /// Unique edges hit, in any state.
+    Edges(usize),
+    /// Unique scripts and documents instrumented, not counting those opting out.
+    Files(usize),
+    /// Percentage of the intercepted scripts and documents that were instrumented rather than
+    /// failing to be. Those opting out are left out of both the instrumented ones and the total.
+    Percent(f64),
+}
+
+impl CoverageThreshold {
+    /// Checks the coverage of a run, given the unique edges it hit. Files opting out of
+    /// instrumentation (`stats.skipped`) count for nothing, so a run where they all did fails a
+    /// `files` or `percent` threshold.
+    pub fn check(
+        &self,
+        edges: usize,
+        stats: &CoverageStats,
+    ) -> Result<(), CoverageBelowThreshold> {
+        let failures = stats.parse_failures
+            + stats.semantic_failures
+            + stats.other_failures;
+        let (actual, required) = match self {
+            CoverageThreshold::Edges(min) => (edges as f64, *min as f64),
+            CoverageThreshold::Files(min) => (stats.files as f64, *min as f64),
+            CoverageThreshold::Percent(min) => (
+                if stats.files + failures == 0 {
+                    0.0
+                } else {
+                    100.0 * stats.files as f64 / (stats.files + failures) as f64
+                },
+                *min,
+            ),
+        };
+        if actual < required {
+            return Err(CoverageBelowThreshold {
+                threshold: *self,
+                actual,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<String> for CoverageThreshold {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl std::str::FromStr for CoverageThreshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid coverage threshold {:?}, expected \"edges:N\", \"files:N\", or \
+                 \"percent:P\"",
+                s
+            )
+        };
+        let (kind, value) = s.split_once(':').ok_or_else(invalid)?;
+        match kind {
+            "edges" => Ok(CoverageThreshold::Edges(
+                value.parse().map_err(|_| invalid())?,
+            )),
+            "files" => Ok(CoverageThreshold::Files(
+                value.parse().map_err(|_| invalid())?,
+            )),
+            "percent" => {
+                let percent: f64 = value.parse().map_err(|_| invalid())?;
+                if !(0.0..=100.0).contains(&percent) {
+                    return Err(invalid());
+                }
+                Ok(CoverageThreshold::Percent(percent))
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl std::fmt::Display for CoverageThreshold {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CoverageThreshold::Edges(min) => write!(f, "{} edges", min),
+            CoverageThreshold::Files(min) => write!(f, "{} files", min),
+            CoverageThreshold::Percent(min) => {
+                write!(f, "{}% of files instrumented", min)
+            }
+        }
+    }
+}
+
+/// The error ending a run that didn't reach [`RunnerOptions::min_coverage`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoverageBelowThreshold {
+    pub threshold: CoverageThreshold,
+    /// The run's coverage, in the unit of the threshold.
+    pub actual: f64,
+}
+
+impl std::fmt::Display for CoverageBelowThreshold {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "coverage below the threshold of {}, got {}",
+            self.threshold, self.actual
+        )
+    }
+}
+
+impl std::error::Error for CoverageBelowThreshold {}
+
+/// The source of state timestamps.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Clock {
+    /// The wall-clock time at which each state was captured.
+    #[default]
+    Wall,
+    /// Starts at the given time and advances by the given step in each explored state,
+    /// regardless of how long things actually took, so that time bounds and `time` are
+    /// reproducible across runs.
+    Virtual(SystemTime, Duration),
+}
+
+impl Clock {
+    /// The time of the explored state with the given index, captured at `captured`.
+    pub fn time(&self, index: u64, captured: SystemTime) -> SystemTime {
+        match self {
+            Clock::Wall => captured,
+            Clock::Virtual(start, step) => {
+                *start
+                    + step.saturating_mul(
+                        u32::try_from(index).unwrap_or(u32::MAX),
+                    )
+            }
+        }
+    }
+}
+
+/// A condition for starting exploration, checked in each state until it holds.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub enum StartCondition {
+    /// An element matching the CSS selector is visible.
+    SelectorVisible(String),
+    /// The JavaScript expression, evaluated in the page, is truthy.
+    Expression(String),
+}
+
+impl StartCondition {
+    async fn holds(&self, state: &BrowserState) -> anyhow::Result<bool> {
+        match self {
+            StartCondition::SelectorVisible(selector) => {
+                state
+                    .evaluate_function_call(
+                        "(selector) => document.querySelector(selector)?.checkVisibility() ?? false",
+                        vec![json::json!(selector)],
+                    )
+                    .await
+            }
+            StartCondition::Expression(expression) => {
+                state
+                    .evaluate_function_call(
+                        "(expression) => Boolean((0, eval)(expression))",
+                        vec![json::json!(expression)],
+                    )
+                    .await
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for StartCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StartCondition::SelectorVisible(selector) => {
+                write!(f, "{} visible", selector)
+            }
+            StartCondition::Expression(expression) => {
+                write!(f, "{}", expression)
+            }
+        }
+    }
+}
+
+/// How long to wait between checks of the start condition.
+const START_CONDITION_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
 #[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum RunEvent {
     NewState {
         state: BrowserState,
         last_action: Option<BrowserAction>,
+        /// The properties that are false as of this state.
         violations: Vec<PropertyViolation>,
+        /// The verdict of every property as of this state, including the ones still pending, e.g.
+        /// bounded `eventually`s before their deadline.
+        properties: Vec<(String, Verdict)>,
+        /// The values extracted from this state by the specification's extractors, when
+        /// [`RunnerOptions::record_snapshots`] is set.
+        snapshots: Option<Vec<Snapshot>>,
+        /// The labels of [`RunnerOptions::state_classifier`], empty without one.
+        labels: Vec<String>,
     },
+    /// Applying the action failed, and the runner moves on to the next state without waiting
+    /// for the action's timeout.
+    ActionFailed {
+        action: BrowserAction,
+        error: Arc<anyhow::Error>,
+    },
+    /// No new coverage (or new states, by [`RunnerOptions::state_hash`]) was found in this many
+    /// consecutive states, and the run ends.
+    Plateaued { states: u64 },
 }
 
 pub struct Runner {
@@ -40,6 +378,9 @@ pub struct Runner {
     shutdown_receiver: oneshot::Receiver<()>,
     done_sender: oneshot::Sender<anyhow::Result<()>>,
     done_receiver: oneshot::Receiver<anyhow::Result<()>>,
+    /// For [`Runner::step_once`], what to do in the next step, or `None` before the first.
+    next: Option<Next>,
+    exploration: Exploration,
 }
 
 impl Runner {
@@ -47,22 +388,46 @@ impl Runner {
         origin: Url,
         specification: Specification,
         options: RunnerOptions,
-        browser_options: BrowserOptions,
+        mut browser_options: BrowserOptions,
         debugger_options: DebuggerOptions,
-    ) -> anyhow::Result<Self> {
+    ) -> Result<Self, BombadilError> {
         let (events, _) = broadcast::channel(16);
         let (done_sender, done_receiver) = oneshot::channel();
         let (shutdown_sender, shutdown_receiver) = oneshot::channel();
 
-        let verifier = VerifierWorker::start(specification.clone()).await?;
+        let verifier = VerifierWorker::start(
+            specification.clone(),
+            VerifierOptions {
+                eventually_evidence: options.eventually_evidence,
+                property_filter: options.property_filter.clone(),
+                strict_exports: options.strict_spec,
+                dictionary: options.dictionary.clone(),
+            },
+        )
+        .await?;
 
-        let browser =
-            Browser::new(origin.clone(), browser_options, debugger_options)
-                .await?;
+        browser_options.dom_hash |= options.state_hash == StateHash::Dom;
+
+        let property_focus = verifier.property_focus().await?;
+        for selector in property_focus.values() {
+            if !browser_options.element_screenshots.contains(selector) {
+                browser_options.element_screenshots.push(selector.clone());
+            }
+        }
+
+        let browser = Browser::new(
+            origin.clone(),
+            options.url_scope.clone(),
+            browser_options,
+            debugger_options,
+        )
+        .await?;
 
         browser
             .ensure_script_evaluated(
-                &bundle(".", &specification.module_specifier).await?,
+                &bundle(".", &specification.module_specifier)
+                    .await
+                    .map_err(BombadilError::Specification)?,
             )
             .await?;
 
@@ -76,9 +441,26 @@ impl Runner {
             shutdown_receiver,
             done_sender,
             done_receiver,
+            next: None,
+            exploration: Exploration {
+                property_focus,
+                ..Exploration::default()
+            },
         })
     }
 
+    /// The sources instrumented for coverage, which stays updated after [`Runner::start`] as new
+    /// scripts load.
+    pub fn coverage_files(&self) -> Arc<InstrumentedFiles> {
+        self.browser.instrumented_files()
+    }
+
+    /// The sources that failed to be instrumented, which stays updated like
+    /// [`Runner::coverage_files`].
+    pub fn instrumentation_failures(&self) -> Arc<InstrumentationFailures> {
+        self.browser.instrumentation_failures()
+    }
+
     pub fn start(self) -> RunEvents {
         let Runner {
             origin,
@@ -90,6 +472,8 @@ impl Runner {
             shutdown_receiver,
             done_sender,
             done_receiver,
+            exploration,
+            ..
         } = self;
 
         log::info!("starting test of {}", origin);
@@ -106,6 +490,7 @@ impl Runner {
                     verifier,
                     events,
                     shutdown_receiver,
+                    exploration,
                 )
                 .await
             };
@@ -136,88 +521,33 @@ impl Runner {
         verifier: Arc<VerifierWorker>,
         events: broadcast::Sender<RunEvent>,
         mut shutdown: oneshot::Receiver<()>,
+        mut exploration: Exploration,
     ) -> anyhow::Result<()> {
-        let mut last_action: Option<BrowserAction> = None;
-        let mut edges = [0u8; EDGE_MAP_SIZE];
-
         loop {
-            let verifier = verifier.clone();
             select! {
-                _ = &mut shutdown => {
-                    return Ok(())
-                },
+                _ = &mut shutdown => break,
                 event = browser.next_event() => match event {
                     Some(event) => match event {
                         BrowserEvent::StateChanged(state) => {
-                            // Step formulas and collect violations.
-                            let snapshots = run_extractors(&state, &last_action).await?;
-                            for value in &snapshots {
-                                log::debug!(
-                                    "snapshot {}: {}",
-                                    value.name.as_deref().unwrap_or("<unnamed>"),
-                                    value.value
-                                );
+                            let (state_events, next) = exploration
+                                .on_state(origin, &options, &verifier, state)
+                                .await?;
+                            for event in state_events {
+                                events.send(event)?;
                             }
-                            let step_result = verifier.step::<crate::specification::js::JsAction>(snapshots, state.timestamp).await?;
-
-                            // Convert JsAction tree to BrowserAction tree
-                            let action_tree = step_result.actions.try_map(&mut |js_action| {
-                                js_action.to_browser_action()
-                            })?;
-
-                            let mut violations = Vec::with_capacity(step_result.properties.len());
-                            let mut all_properties_definite = true;
-                            for (name, value) in step_result.properties {
-                                match value {
-                                    PropertyValue::False(violation) => {
-                                        violations.push(PropertyViolation{ name, violation });
-                                    }
-                                    PropertyValue::Residual => {
-                                        all_properties_definite = false;
-                                    }
-                                    PropertyValue::True => {
-                                        // Property is satisfied
-                                    }
+                            match next {
+                                Next::Act(action, timeout) => {
+                                    exploration.apply(browser, &options, action, timeout).await?
                                 }
+                                Next::Wait => {
+                                    browser.apply(BrowserAction::Wait, START_CONDITION_POLL_INTERVAL)?
+                                }
+                                Next::Stop => break,
                             }
-                            let has_violations = !violations.is_empty();
-
-                            // Make sure we stay within origin.
-                            let action_tree = if !is_within_domain(&state.url, origin) {
-                                action_tree.filter(&|a| matches!(a, BrowserAction::Back))
-                            } else {
-                                action_tree
-                            };
-
-                            // Update global edges.
-                            for (index, bucket) in &state.coverage.edges_new {
-                                edges[*index as usize] =
-                                    max(edges[*index as usize], *bucket);
-                            }
-                            log_coverage_stats_increment(&state.coverage);
-                            log_coverage_stats_total(&edges);
-
-                            events.send(RunEvent::NewState {
-                                state,
-                                last_action,
-                                violations,
-                            })?;
-                            if has_violations && options.stop_on_violation {
-                                return Ok(())
-                            }
-                            if all_properties_definite {
-                                log::info!("all properties are definite, stopping");
-                                return Ok(())
-                            }
-
-                            let action_tree = action_tree.prune()
-                                .ok_or_else(|| anyhow::anyhow!("no actions available"))?;
-
-                            let action = action_tree.pick(&mut rand::rng())?.clone();
-                            let timeout = action_timeout(&action);
-                            log::info!("picked action: {:?}", action);
-                            browser.apply(action.clone(), timeout)?;
-                            last_action = Some(action);
+                        }
+                        BrowserEvent::ActionFailed(action, error) => {
+                            log::warn!("action {:?} failed: {}", action, error);
+                            events.send(RunEvent::ActionFailed { action, error })?;
                         }
                         BrowserEvent::Error(error) => {
                             anyhow::bail!("state machine error: {}", error)
@@ -229,6 +559,361 @@ impl Runner {
                 }
             }
         }
+
+        if let Some(threshold) = &options.min_coverage {
+            let edges =
+                exploration.edges.iter().filter(|hits| **hits > 0).count();
+            let stats = CoverageStats::collect(
+                &browser.instrumented_files(),
+                &browser.instrumentation_failures(),
+            );
+            threshold.check(edges, &stats)?;
+        }
+        Ok(())
+    }
+
+    /// Drives the test one action at a time, e.g. from a REPL, instead of running it with
+    /// [`Runner::start`]. The first call navigates to the starting page and returns its state,
+    /// and every later call applies the action picked in the previous state and returns the
+    /// next one. Call [`Runner::terminate`] when done.
+    pub async fn step_once(&mut self) -> anyhow::Result<Step> {
+        match self.next.take() {
+            None => self.browser.initiate().await?,
+            Some(Next::Act(action, timeout)) => {
+                self.exploration
+                    .apply(&mut self.browser, &self.options, action, timeout)
+                    .await?
+            }
+            Some(Next::Wait) => self
+                .browser
+                .apply(BrowserAction::Wait, START_CONDITION_POLL_INTERVAL)?,
+            Some(Next::Stop) => {
+                self.next = Some(Next::Stop);
+                anyhow::bail!("the test is over, there are no more steps");
+            }
+        }
+
+        let mut events = vec![];
+        loop {
+            match self.browser.next_event().await {
+                Some(BrowserEvent::StateChanged(state)) => {
+                    let (state_events, next) = self
+                        .exploration
+                        .on_state(
+                            &self.origin,
+                            &self.options,
+                            &self.verifier,
+                            state,
+                        )
+                        .await?;
+                    events.extend(state_events);
+                    if let Next::Wait = next {
+                        self.browser.apply(
+                            BrowserAction::Wait,
+                            START_CONDITION_POLL_INTERVAL,
+                        )?;
+                        continue;
+                    }
+                    let done = matches!(next, Next::Stop);
+                    self.next = Some(next);
+                    return Ok(Step { events, done });
+                }
+                Some(BrowserEvent::ActionFailed(action, error)) => {
+                    log::warn!("action {:?} failed: {}", action, error);
+                    events.push(RunEvent::ActionFailed { action, error });
+                }
+                Some(BrowserEvent::Error(error)) => {
+                    anyhow::bail!("state machine error: {}", error)
+                }
+                None => anyhow::bail!("browser closed"),
+            }
+        }
+    }
+
+    /// Closes the browser after stepping through the test with [`Runner::step_once`].
+    pub async fn terminate(self) -> anyhow::Result<()> {
+        self.browser.terminate().await
+    }
+}
+
+/// The outcome of [`Runner::step_once`].
+#[derive(Debug, Clone)]
+pub struct Step {
+    /// The events of the step, ending with the new state.
+    pub events: Vec<RunEvent>,
+    /// Whether the test is over, e.g. after a violation, in which case there are no more steps.
+    pub done: bool,
+}
+
+/// What to do after a state.
+#[derive(Debug)]
+enum Next {
+    Act(BrowserAction, Duration),
+    /// Wait for the start condition to hold.
+    Wait,
+    Stop,
+}
+
+/// What exploring has found so far, carried from state to state.
+struct Exploration {
+    last_action: Option<BrowserAction>,
+    edges: [u8; EDGE_MAP_SIZE],
+    steps: u64,
+    seen_states: HashSet<u64>,
+    states_without_novelty: u64,
+    last_action_applied: Option<Instant>,
+    /// Whether the start condition has held, after which it's not checked anymore.
+    started: bool,
+    waiting_since: Option<Instant>,
+    /// The selector each property focuses on, by property name.
+    property_focus: HashMap<String, String>,
+    /// The properties whose violations have been reported, with
+    /// [`RunnerOptions::report_violations_once`].
+    reported_violations: HashSet<String>,
+}
+
+impl Default for Exploration {
+    fn default() -> Self {
+        Exploration {
+            last_action: None,
+            edges: [0u8; EDGE_MAP_SIZE],
+            steps: 0,
+            seen_states: HashSet::new(),
+            states_without_novelty: 0,
+            last_action_applied: None,
+            started: false,
+            waiting_since: None,
+            property_focus: HashMap::new(),
+            reported_violations: HashSet::new(),
+        }
+    }
+}
+
+impl Exploration {
+    /// Steps the properties in a new state and picks what to do next, returning the events to
+    /// report.
+    async fn on_state(
+        &mut self,
+        origin: &Url,
+        options: &RunnerOptions,
+        verifier: &VerifierWorker,
+        mut state: BrowserState,
+    ) -> anyhow::Result<(Vec<RunEvent>, Next)> {
+        if !self.started {
+            match &options.start_condition {
+                Some(condition) => {
+                    let since =
+                        *self.waiting_since.get_or_insert_with(Instant::now);
+                    if condition.holds(&state).await? {
+                        log::info!(
+                            "start condition met ({}), exploring",
+                            condition
+                        );
+                        self.started = true;
+                    } else if since.elapsed() > options.start_timeout {
+                        anyhow::bail!(
+                            "start condition not met within {:?}: {}",
+                            options.start_timeout,
+                            condition
+                        );
+                    } else {
+                        log::debug!(
+                            "waiting for start condition: {}",
+                            condition
+                        );
+                        return Ok((vec![], Next::Wait));
+                    }
+                }
+                None => self.started = true,
+            }
+        }
+
+        state.timestamp = options.clock.time(self.steps, state.timestamp);
+
+        // Step formulas and collect violations.
+        let snapshots = run_extractors(&state, &self.last_action).await?;
+        let recorded_snapshots =
+            options.record_snapshots.then(|| snapshots.clone());
+        for value in &snapshots {
+            log::debug!(
+                "snapshot {}: {}",
+                value.name.as_deref().unwrap_or("<unnamed>"),
+                value.value
+            );
+        }
+        let warming_up = self.steps < options.warmup_steps;
+        self.steps += 1;
+        let step_result = if warming_up {
+            log::debug!(
+                "warming up ({}/{}), not evaluating properties",
+                self.steps,
+                options.warmup_steps
+            );
+            StepResult {
+                properties: vec![],
+                actions: verifier
+                    .actions::<JsAction>(snapshots, state.timestamp)
+                    .await?,
+            }
+        } else {
+            verifier
+                .step::<JsAction>(snapshots, state.timestamp)
+                .await?
+        };
+        let action_tree = restrict_actions(
+            origin,
+            &options.url_scope,
+            &state.url,
+            step_result.actions,
+        )?;
+        let action_tree =
+            exclude_actions(&state, &options.exclude_selectors, action_tree)
+                .await?;
+
+        let mut violations = Vec::with_capacity(step_result.properties.len());
+        let mut properties = Vec::with_capacity(step_result.properties.len());
+        let mut all_properties_definite = true;
+        for (name, value) in step_result.properties {
+            match value {
+                PropertyValue::False(violation) => {
+                    properties.push((name.clone(), Verdict::False));
+                    violations.push(PropertyViolation {
+                        focus: self.property_focus.get(&name).cloned(),
+                        screenshot: None,
+                        name,
+                        violation,
+                    });
+                }
+                PropertyValue::Residual => {
+                    properties.push((name, Verdict::Pending));
+                    all_properties_definite = false;
+                }
+                PropertyValue::True => {
+                    // Property is satisfied
+                    properties.push((name, Verdict::True));
+                }
+            }
+        }
+        if options.report_violations_once {
+            violations.retain(|violation| {
+                self.reported_violations.insert(violation.name.clone())
+            });
+        }
+        let has_violations = !violations.is_empty();
+
+        let new_coverage = state
+            .coverage
+            .edges_new
+            .iter()
+            .any(|(index, bucket)| *bucket > self.edges[*index as usize]);
+
+        // Update global edges.
+        for (index, bucket) in &state.coverage.edges_new {
+            self.edges[*index as usize] =
+                max(self.edges[*index as usize], *bucket);
+        }
+        log_coverage_stats_increment(&state.coverage);
+        log_coverage_stats_total(&self.edges);
+        let new_state = match state.hash(options.state_hash) {
+            Some(hash) if self.seen_states.insert(hash) => {
+                log::debug!(
+                    "new state {:016x} ({} seen)",
+                    hash,
+                    self.seen_states.len()
+                );
+                true
+            }
+            Some(hash) => {
+                log::debug!("revisited state {:016x}", hash);
+                false
+            }
+            None => false,
+        };
+        // Transition hashes differ whenever different code ran, so coverage tells new states
+        // apart better, while DOM hashes are meant to identify states regardless of it.
+        let novel = match options.state_hash {
+            StateHash::Coverage => new_coverage,
+            StateHash::Dom => new_state,
+        };
+        if novel {
+            self.states_without_novelty = 0;
+        } else {
+            self.states_without_novelty += 1;
+        }
+
+        let labels = options
+            .state_classifier
+            .as_ref()
+            .map(|classifier| classifier(&state))
+            .unwrap_or_default();
+        let mut events = vec![RunEvent::NewState {
+            state,
+            last_action: self.last_action.clone(),
+            violations,
+            properties,
+            snapshots: recorded_snapshots,
+            labels,
+        }];
+        if has_violations && options.stop_on_violation {
+            return Ok((events, Next::Stop));
+        }
+        if all_properties_definite && !warming_up {
+            log::info!("all properties are definite, stopping");
+            return Ok((events, Next::Stop));
+        }
+        if let Some(limit) = options.stop_after_no_new_coverage
+            && self.states_without_novelty >= limit
+        {
+            log::info!(
+                "nothing new in {} states, stopping",
+                self.states_without_novelty
+            );
+            events.push(RunEvent::Plateaued {
+                states: self.states_without_novelty,
+            });
+            return Ok((events, Next::Stop));
+        }
+
+        let action = match action_tree.prune() {
+            Some(action_tree) => action_tree.pick(&mut rand::rng())?.clone(),
+            None => {
+                let fallback_action = options
+                    .fallback_action
+                    .clone()
+                    .ok_or_else(|| anyhow::anyhow!("no actions available"))?;
+                log::info!(
+                    "no actions available, falling back to {:?}",
+                    fallback_action
+                );
+                fallback_action
+            }
+        };
+        let timeout = match options.max_action_timeout {
+            Some(max_action_timeout) => {
+                action_timeout(&action).min(max_action_timeout)
+            }
+            None => action_timeout(&action),
+        };
+        log::info!("picked action: {:?}", action);
+        Ok((events, Next::Act(action, timeout)))
+    }
+
+    async fn apply(
+        &mut self,
+        browser: &mut Browser,
+        options: &RunnerOptions,
+        action: BrowserAction,
+        timeout: Duration,
+    ) -> anyhow::Result<()> {
+        if let (Some(interval), Some(applied)) =
+            (options.min_action_interval, self.last_action_applied)
+        {
+            sleep_until(applied + interval).await;
+        }
+        browser.apply(action.clone(), timeout)?;
+        self.last_action_applied = Some(Instant::now());
+        self.last_action = Some(action);
+        Ok(())
     }
 }
 
@@ -256,6 +941,181 @@ impl RunEvents {
     }
 }
 
+/// An action that the runner can pick in some state.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionCandidate {
+    pub action: BrowserAction,
+    /// The probability of this action being picked.
+    pub weight: f64,
+    /// Serialized as `{ "ms": N }`, so that the unit is clear to whoever reads it.
+    #[serde(
+        serialize_with = "serialize_millis",
+        deserialize_with = "deserialize_millis"
+    )]
+    pub timeout: Duration,
+}
+
+fn serialize_millis<S: serde::Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct Millis {
+        ms: u64,
+    }
+    Millis {
+        ms: duration.as_millis() as u64,
+    }
+    .serialize(serializer)
+}
+
+#[derive(Deserialize)]
+struct Millis {
+    ms: u64,
+}
+
+pub(crate) fn deserialize_millis<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Duration, D::Error> {
+    Ok(Duration::from_millis(Millis::deserialize(deserializer)?.ms))
+}
+
+pub(crate) fn deserialize_optional_millis<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    Ok(Option::<Millis>::deserialize(deserializer)?
+        .map(|millis| Duration::from_millis(millis.ms)))
+}
+
+/// Computes the actions that the specification offers in the given state, restricted in the same
+/// way as when running a test. Properties are not stepped.
+pub async fn available_actions(
+    origin: &Url,
+    url_scope: &UrlScope,
+    exclude_selectors: &[String],
+    state: &BrowserState,
+    last_action: &Option<BrowserAction>,
+    verifier: &VerifierWorker,
+) -> anyhow::Result<Tree<BrowserAction>> {
+    let snapshots = run_extractors(state, last_action).await?;
+    let actions = verifier
+        .actions::<JsAction>(snapshots, state.timestamp)
+        .await?;
+    let actions = restrict_actions(origin, url_scope, &state.url, actions)?;
+    exclude_actions(state, exclude_selectors, actions).await
+}
+
+/// Flattens an action tree into the list of actions that could be picked from it.
+pub fn action_candidates(
+    actions: &Tree<BrowserAction>,
+) -> Vec<ActionCandidate> {
+    match actions.clone().prune() {
+        Some(actions) => actions
+            .flatten()
+            .into_iter()
+            .map(|(weight, action)| ActionCandidate {
+                action: action.clone(),
+                weight,
+                timeout: action_timeout(action),
+            })
+            .collect(),
+        None => vec![],
+    }
+}
+
+fn restrict_actions(
+    origin: &Url,
+    url_scope: &UrlScope,
+    url: &Url,
+    actions: Tree<JsAction>,
+) -> anyhow::Result<Tree<BrowserAction>> {
+    let actions =
+        actions.try_map(&mut |js_action| js_action.to_browser_action())?;
+
+    // Make sure we stay within origin and scope.
+    Ok(if !url_scope.permits(url, origin) {
+        actions.filter(&|a| matches!(a, BrowserAction::Back))
+    } else {
+        actions
+    })
+}
+
+/// Removes actions targeting elements that match any of the excluded selectors, checked in the
+/// browser as the specification's actions only carry points and selectors of their own.
+async fn exclude_actions(
+    state: &BrowserState,
+    exclude_selectors: &[String],
+    actions: Tree<BrowserAction>,
+) -> anyhow::Result<Tree<BrowserAction>> {
+    if exclude_selectors.is_empty() {
+        return Ok(actions);
+    }
+    let clicks: Vec<&BrowserAction> =
+        actions.leaves().flat_map(nested_clicks).collect();
+    let targets: Vec<json::Value> = clicks
+        .iter()
+        .map(|action| match action {
+            BrowserAction::Click {
+                point, selector, ..
+            } => json::json!({ "point": point, "selector": selector }),
+            _ => unreachable!("only clicks are collected"),
+        })
+        .collect();
+
+    #[derive(Deserialize)]
+    struct Excluded {
+        targets: Vec<bool>,
+        focused: bool,
+    }
+    let excluded: Excluded = state
+        .evaluate_function_call(
+            "(selectors, targets) => {
+                const excluded = (element) =>
+                    element !== null && selectors.some((selector) => element.closest(selector) !== null);
+                const find = ({ point, selector }) =>
+                    (selector && document.querySelector(selector)) || document.elementFromPoint(point.x, point.y);
+                return {
+                    targets: targets.map((target) => excluded(find(target))),
+                    focused: excluded(document.activeElement),
+                };
+            }",
+            vec![json::to_value(exclude_selectors)?, json::Value::Array(targets)],
+        )
+        .await?;
+
+    let excluded_clicks: Vec<BrowserAction> = clicks
+        .into_iter()
+        .zip(excluded.targets)
+        .filter(|(_, excluded)| *excluded)
+        .map(|(action, _)| action.clone())
+        .collect();
+    for action in &excluded_clicks {
+        log::debug!("excluding action on excluded element: {:?}", action);
+    }
+    Ok(actions.filter(&|action| match action {
+        BrowserAction::Click { .. } | BrowserAction::Sequence(_) => {
+            !nested_clicks(action)
+                .into_iter()
+                .any(|click| excluded_clicks.contains(click))
+        }
+        BrowserAction::TypeText { .. } | BrowserAction::PressKey { .. } => {
+            !excluded.focused
+        }
+        _ => true,
+    }))
+}
+
+/// The clicks an action performs, including those in sequences.
+fn nested_clicks(action: &BrowserAction) -> Vec<&BrowserAction> {
+    match action {
+        BrowserAction::Click { .. } => vec![action],
+        BrowserAction::Sequence(actions) => {
+            actions.iter().flat_map(nested_clicks).collect()
+        }
+        _ => vec![],
+    }
+}
+
 async fn run_extractors(
     state: &BrowserState,
     last_action: &Option<BrowserAction>,
@@ -279,6 +1139,9 @@ async fn run_extractors(
         "console": console_entries,
         "navigationHistory": &state.navigation_history,
         "lastAction": json::to_value(last_action)?,
+        "scroll": state.scroll,
+        "viewport": state.viewport,
+        "unresponsive": state.unresponsive,
     });
 
     // Update time cell in browser runtime before running extractors
@@ -304,23 +1167,32 @@ async fn run_extractors(
     Ok(results)
 }
 
-fn action_timeout(action: &BrowserAction) -> Duration {
+pub fn action_timeout(action: &BrowserAction) -> Duration {
     match action {
         BrowserAction::Back => Duration::from_secs(2),
         BrowserAction::Forward => Duration::from_secs(2),
         BrowserAction::Reload => Duration::from_secs(2),
         BrowserAction::Click { .. } => Duration::from_millis(500),
         BrowserAction::TypeText {
-            text, delay_millis, ..
+            text,
+            delay_millis,
+            jitter,
         } => {
             // We'll wait for the text to be entered, and an extra 100ms.
+            let max_delay_millis = jitter
+                .as_ref()
+                .map_or(*delay_millis, |jitter| jitter.max_delay_millis);
             let text_entry_millis =
-                (*delay_millis).saturating_mul(text.len() as u64);
+                max_delay_millis.saturating_mul(text.len() as u64);
             Duration::from_millis(text_entry_millis.saturating_add(100u64))
         }
         BrowserAction::PressKey { .. } => Duration::from_millis(50),
         BrowserAction::ScrollUp { .. } => Duration::from_millis(100),
         BrowserAction::ScrollDown { .. } => Duration::from_millis(100),
+        BrowserAction::Wait => START_CONDITION_POLL_INTERVAL,
+        BrowserAction::Sequence(actions) => {
+            actions.iter().map(action_timeout).sum()
+        }
     }
 }
 
@@ -364,3 +1236,119 @@ fn log_coverage_stats_total(edges: &[u8; EDGE_MAP_SIZE]) {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action_candidate_timeout_serialization() {
+        let candidate = ActionCandidate {
+            action: BrowserAction::Reload,
+            weight: 1.0,
+            timeout: Duration::from_millis(1500),
+        };
+        assert_eq!(
+            json::to_value(&candidate).unwrap()["timeout"],
+            json::json!({ "ms": 1500 })
+        );
+        let deserialized: ActionCandidate =
+            json::from_value(json::to_value(&candidate).unwrap()).unwrap();
+        assert_eq!(deserialized, candidate);
+    }
+
+    #[test]
+    fn test_coverage_threshold() {
+        let stats = CoverageStats {
+            files: 3,
+            skipped: 5,
+            parse_failures: 1,
+            ..CoverageStats::default()
+        };
+        let check = |threshold: &str| {
+            threshold
+                .parse::<CoverageThreshold>()
+                .unwrap()
+                .check(100, &stats)
+        };
+        assert!(check("edges:100").is_ok());
+        assert_eq!(
+            check("edges:101"),
+            Err(CoverageBelowThreshold {
+                threshold: CoverageThreshold::Edges(101),
+                actual: 100.0,
+            })
+        );
+        assert!(check("files:3").is_ok());
+        assert!(check("files:4").is_err());
+        assert!(check("percent:75").is_ok());
+        assert!(check("percent:80").is_err());
+        let skipped_only = CoverageStats {
+            skipped: 5,
+            ..CoverageStats::default()
+        };
+        assert!(CoverageThreshold::Files(1).check(0, &skipped_only).is_err());
+        assert!(
+            CoverageThreshold::Percent(1.0)
+                .check(0, &skipped_only)
+                .is_err()
+        );
+        assert!("percent:101".parse::<CoverageThreshold>().is_err());
+        assert!("lines:10".parse::<CoverageThreshold>().is_err());
+        assert!("edges".parse::<CoverageThreshold>().is_err());
+    }
+
+    #[test]
+    fn test_virtual_clock() {
+        let start = std::time::UNIX_EPOCH + Duration::from_secs(100);
+        let captured = SystemTime::now();
+        let clock = Clock::Virtual(start, Duration::from_millis(250));
+        assert_eq!(clock.time(0, captured), start);
+        assert_eq!(clock.time(4, captured), start + Duration::from_secs(1));
+        assert_eq!(Clock::Wall.time(4, captured), captured);
+    }
+
+    fn restricted(url: &str) -> Vec<BrowserAction> {
+        let actions = Tree::Branch {
+            branches: vec![
+                (
+                    1,
+                    Tree::Leaf {
+                        value: JsAction::Back,
+                    },
+                ),
+                (
+                    1,
+                    Tree::Leaf {
+                        value: JsAction::Reload,
+                    },
+                ),
+            ],
+        };
+        restrict_actions(
+            &Url::parse("https://example.com/").unwrap(),
+            &UrlScope::default(),
+            &Url::parse(url).unwrap(),
+            actions,
+        )
+        .unwrap()
+        .leaves()
+        .cloned()
+        .collect()
+    }
+
+    #[test]
+    fn test_restrict_actions_blob_and_data_urls() {
+        let all = vec![BrowserAction::Back, BrowserAction::Reload];
+        assert_eq!(restricted("https://example.com/app"), all);
+        assert_eq!(restricted("blob:https://example.com/0b1e2f6a"), all);
+        assert_eq!(
+            restricted("blob:https://other.example.com/0b1e2f6a"),
+            vec![BrowserAction::Back]
+        );
+        assert_eq!(
+            restricted("data:text/html,<h1>Preview</h1>"),
+            vec![BrowserAction::Back]
+        );
+    }
+}
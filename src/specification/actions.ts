@@ -7,6 +7,7 @@ export {
   emails,
   integers,
   keycodes,
+  dictionary,
   randomRange,
 } from "@antithesishq/bombadil/random";
 
@@ -19,11 +20,83 @@ export type Action =
   | "Back"
   | "Forward"
   | "Reload"
-  | { Click: { name: string; content?: string; point: Point } }
-  | { TypeText: { text: string; delayMillis: number } }
+  | {
+      Click: {
+        name: string;
+        content?: string;
+        point: Point;
+        selector?: string;
+      };
+    }
+  | {
+      TypeText: {
+        text: string;
+        delayMillis: number;
+        /**
+         * Makes the delay before each character random, between `delayMillis`
+         * and this.
+         */
+        maxDelayMillis?: number;
+        /** Seeds the random delays, which are otherwise seeded randomly. */
+        seed?: number;
+      };
+    }
+  | { PressKey: { code: number } }
+  | { ScrollUp: { origin: Point; distance: number } }
+  | { ScrollDown: { origin: Point; distance: number } }
+  /**
+   * Performs the actions in order as a single step, without capturing states
+   * in between.
+   */
+  | { Sequence: Action[] };
+
+/**
+ * An action Bombadil took, as in `state.lastAction`. Unlike `Action`, which
+ * generators return, fields are snake_case and missing optional values are
+ * null, mirroring how the runner serializes actions in traces.
+ */
+export type ActionTaken =
+  | "Back"
+  | "Forward"
+  | "Reload"
+  | {
+      Click: {
+        name: string;
+        content: string | null;
+        point: Point;
+        selector: string | null;
+      };
+    }
+  | {
+      TypeText: {
+        text: string;
+        delay_millis: number;
+        /** Missing when the delays weren't random. */
+        jitter?: { max_delay_millis: number; seed: number };
+      };
+    }
   | { PressKey: { code: number } }
   | { ScrollUp: { origin: Point; distance: number } }
-  | { ScrollDown: { origin: Point; distance: number } };
+  | { ScrollDown: { origin: Point; distance: number } }
+  | { Sequence: ActionTaken[] };
+
+export type ActionKind =
+  | "Back"
+  | "Forward"
+  | "Reload"
+  | "Click"
+  | "TypeText"
+  | "PressKey"
+  | "ScrollUp"
+  | "ScrollDown"
+  | "Sequence";
+
+/** The kind of an action, e.g. "Click" for `{ Click: { ... } }`. */
+export function actionKind(action: Action | ActionTaken): ActionKind {
+  return typeof action === "string"
+    ? action
+    : (Object.keys(action)[0] as ActionKind);
+}
 
 // Tree
 
@@ -1,8 +1,10 @@
 export {
   noHttpErrorCodes,
+  noNetworkErrors,
   noUncaughtExceptions,
   noUnhandledPromiseRejections,
   noConsoleErrors,
+  noUnresponsivePage,
 } from "@antithesishq/bombadil/defaults/properties";
 
 export {
@@ -6,6 +6,8 @@ import {
   emails,
   integers,
   keycodes,
+  dictionary,
+  actionKind,
   type Action,
 } from "@antithesishq/bombadil";
 
@@ -25,64 +27,32 @@ const canGoForwardSameOrigin = extract((state) => {
   }
 });
 
-const lastAction = extract((state) => {
-  const action = state.lastAction;
-  if (action === null) return null;
-  if (typeof action === "string") return action;
-  return Object.keys(action)[0] ?? null;
-});
+const lastAction = extract((state) =>
+  state.lastAction === null ? null : actionKind(state.lastAction),
+);
 
-const body = extract((state) => {
-  return state.document.body
-    ? { scrollHeight: state.document.body.scrollHeight }
-    : null;
-});
-
-const window = extract((state) => {
-  return {
-    scroll: {
-      x: state.window.scrollX,
-      y: state.window.scrollY,
-    },
-    inner: {
-      width: state.window.innerWidth,
-      height: state.window.innerHeight,
-    },
-  };
-});
+const scrollPosition = extract((state) => ({
+  scroll: state.scroll,
+  viewport: state.viewport,
+}));
 
 export const scroll = actions(() => {
   if (contentType.current !== "text/html") return [];
 
-  if (!body.current) return [];
-
-  const scrollYMax = body.current.scrollHeight - window.current.inner.height;
-  const scrollYMaxDiff = scrollYMax - window.current.scroll.y;
+  const { scroll, viewport } = scrollPosition.current;
+  const origin = { x: viewport.width / 2, y: viewport.height / 2 };
+  const below = scroll.maxY - scroll.y;
 
-  if (scrollYMaxDiff >= 1) {
-    return [
-      {
-        ScrollDown: {
-          origin: {
-            x: window.current.inner.width / 2,
-            y: window.current.inner.height / 2,
-          },
-          distance: Math.min(window.current.inner.height / 2, scrollYMaxDiff),
-        },
-      } as Action,
-    ];
-  } else if (window.current.scroll.y > 0) {
+  if (below >= 1) {
+    const step = Math.min(viewport.height / 2, below);
+    // Besides half a viewport at a time, jump straight to the bottom, which
+    // would otherwise take many steps to explore (e.g. with infinite scroll).
     return [
-      {
-        ScrollUp: {
-          origin: {
-            x: window.current.inner.width / 2,
-            y: window.current.inner.height / 2,
-          },
-          distance: window.current.scroll.y,
-        },
-      } as Action,
-    ];
+      { ScrollDown: { origin, distance: step } },
+      ...(below > step ? [{ ScrollDown: { origin, distance: below } }] : []),
+    ] as Action[];
+  } else if (scroll.y > 0) {
+    return [{ ScrollUp: { origin, distance: scroll.y } } as Action];
   }
 
   return [];
@@ -109,6 +79,7 @@ const clickablePoints = extract((state) => {
     name: string;
     content: string;
     point: { x: number; y: number };
+    selector?: string;
   };
   const targets: ClickTarget[] = [];
   const added = new Set<Element>();
@@ -130,6 +101,41 @@ const clickablePoints = extract((state) => {
     );
   }
 
+  // A selector that identifies the element in its document, used to find it again if the
+  // layout changes before the click. Elements in shadow roots and iframes can't be found by
+  // a selector on the main document, so they only get a point.
+  function uniqueSelector(element: Element): string | undefined {
+    if (element.getRootNode() !== state.document) return undefined;
+    const parts: string[] = [];
+    let current: Element | null = element;
+    while (current && current !== state.document.documentElement) {
+      if (current.id) {
+        parts.unshift(`#${CSS.escape(current.id)}`);
+        break;
+      }
+      const tag = current.tagName.toLowerCase();
+      const sameTag = current.parentElement
+        ? Array.from(current.parentElement.children).filter(
+            (sibling) => sibling.tagName === current!.tagName,
+          )
+        : [current];
+      parts.unshift(
+        sameTag.length > 1
+          ? `${tag}:nth-of-type(${sameTag.indexOf(current) + 1})`
+          : tag,
+      );
+      current = current.parentElement;
+    }
+    const selector = parts.join(" > ");
+    try {
+      return state.document.querySelector(selector) === element
+        ? selector
+        : undefined;
+    } catch {
+      return undefined;
+    }
+  }
+
   function inViewport(point: { x: number; y: number }): boolean {
     return (
       point.x >= 0 &&
@@ -197,6 +203,7 @@ const clickablePoints = extract((state) => {
       name: anchor.nodeName,
       content: (anchor.textContent ?? "").trim().replace(/\s+/g, " "),
       point,
+      selector: uniqueSelector(anchor),
     });
     added.add(anchor);
   }
@@ -227,6 +234,7 @@ const clickablePoints = extract((state) => {
       name: element.nodeName,
       content: (element.textContent ?? "").trim().replace(/\s+/g, " "),
       point,
+      selector: uniqueSelector(element),
     });
     added.add(element);
   }
@@ -247,6 +255,7 @@ const clickablePoints = extract((state) => {
       name: element.nodeName,
       content: (element.textContent ?? "").trim().replace(/\s+/g, " "),
       point,
+      selector: uniqueSelector(element),
     });
     added.add(element);
   }
@@ -257,9 +266,9 @@ const clickablePoints = extract((state) => {
 export const clicks = actions(() => {
   if (contentType.current !== "text/html") return [];
   return clickablePoints.current.map(
-    ({ name, content, point }) =>
+    ({ name, content, point, selector }) =>
       ({
-        Click: { name, content, point },
+        Click: { name, content, point, selector },
       }) as Action,
   );
 });
@@ -281,50 +290,43 @@ const activeInput = extract((state) => {
   return null;
 });
 
+function randomText(type: string): string | null {
+  switch (type) {
+    case "textarea":
+    case "text":
+      return strings().minSize(1).generate();
+    case "email":
+      return emails().generate();
+    case "number":
+      return integers().min(0).max(10000).generate().toString();
+    default:
+      return null;
+  }
+}
+
 export const inputs = actions(() => {
   if (contentType.current !== "text/html") return [];
   const type = activeInput.current;
   if (!type) return [];
 
   const delayMillis = integers().min(1).max(100).generate();
+  const text = randomText(type);
+  const known = dictionary(type);
+  if (text === null && known === null) return [];
 
-  if (type === "textarea") {
-    return weighted([
-      [1, { PressKey: { code: keycodes().generate() } }],
-      [3, { TypeText: { text: strings().minSize(1).generate(), delayMillis } }],
-    ]).generate();
+  const typed: [number, Action][] = [];
+  if (text !== null) {
+    typed.push([3, { TypeText: { text, delayMillis } }]);
   }
-
-  switch (type) {
-    case "text":
-      return weighted([
-        [1, { PressKey: { code: keycodes().generate() } }],
-        [
-          3,
-          { TypeText: { text: strings().minSize(1).generate(), delayMillis } },
-        ],
-      ]).generate();
-    case "email":
-      return weighted([
-        [1, { PressKey: { code: keycodes().generate() } }],
-        [3, { TypeText: { text: emails().generate(), delayMillis } }],
-      ]).generate();
-    case "number":
-      return weighted([
-        [1, { PressKey: { code: keycodes().generate() } }],
-        [
-          3,
-          {
-            TypeText: {
-              text: integers().min(0).max(10000).generate().toString(),
-              delayMillis,
-            },
-          },
-        ],
-      ]).generate();
-    default:
-      return [];
+  // Dictionary values are likelier to pass validation, reaching the code
+  // behind it, so they're preferred.
+  if (known !== null) {
+    typed.push([6, { TypeText: { text: known.generate(), delayMillis } }]);
   }
+  return weighted([
+    [1, { PressKey: { code: keycodes().generate() } }],
+    ...typed,
+  ]).generate();
 });
 
 // Navigation
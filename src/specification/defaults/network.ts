@@ -0,0 +1,51 @@
+import { always, extract, type Formula } from "@antithesishq/bombadil";
+
+export interface NetworkErrorOptions {
+  /** Responses with at least this status (and at least 400) are errors. */
+  minStatus?: number;
+  /** Requests to ignore, by URL substring or regular expression. */
+  exclude?: (string | RegExp)[];
+}
+
+const failedRequests = extract((state) => {
+  const performance = state.window.performance;
+  // The browser stops recording resource timing entries once its buffer is
+  // full, which holds 250 by default, so it's raised to keep seeing errors on
+  // long-running pages. (Extractors run in the page, so this can't refer to a
+  // constant outside of the function.)
+  performance.setResourceTimingBufferSize(100_000);
+  return performance
+    .getEntriesByType("resource")
+    .filter(
+      (entry): entry is PerformanceResourceTiming =>
+        entry instanceof PerformanceResourceTiming &&
+        entry.responseStatus >= 400,
+    )
+    .map((entry) => ({ url: entry.name, status: entry.responseStatus }));
+});
+
+/**
+ * No subresource request (scripts, stylesheets, images, fetches, etc) gets
+ * an error response, except for the excluded ones.
+ *
+ * Responses are read from the page's resource timing entries, which have blind
+ * spots: cross-origin responses without a `Timing-Allow-Origin` header have no
+ * status, so their errors go unnoticed, and a page loading more than 250
+ * subresources before its first state is captured loses the entries after
+ * those, until the buffer is raised.
+ */
+export function noNetworkErrorsWith(
+  options: NetworkErrorOptions = {},
+): Formula {
+  const minStatus = options.minStatus ?? 400;
+  const exclude = options.exclude ?? [];
+  const isExcluded = (url: string) =>
+    exclude.some((pattern) =>
+      typeof pattern === "string" ? url.includes(pattern) : pattern.test(url),
+    );
+  return always(() =>
+    failedRequests.current.every(
+      (request) => request.status < minStatus || isExcluded(request.url),
+    ),
+  );
+}
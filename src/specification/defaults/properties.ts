@@ -1,4 +1,5 @@
 import { always, extract } from "@antithesishq/bombadil";
+import { noNetworkErrorsWith } from "@antithesishq/bombadil/defaults/network";
 
 const responseStatus = extract((state) => {
   const first = state.window.performance.getEntriesByType("navigation")[0];
@@ -11,6 +12,8 @@ export const noHttpErrorCodes = always(
   () => (responseStatus.current ?? 0) < 400,
 );
 
+export const noNetworkErrors = noNetworkErrorsWith();
+
 const uncaughtExceptions = extract((state) => state.errors.uncaughtExceptions);
 
 export const noUncaughtExceptions = always(() =>
@@ -28,3 +31,29 @@ const consoleErrors = extract((state) =>
 export const noConsoleErrors = always(
   () => consoleErrors.current?.length === 0,
 );
+
+const unresponsive = extract((state) => state.unresponsive);
+
+export const noUnresponsivePage = always(() => !unresponsive.current);
+
+// The following properties are opt-in, and not exported from
+// `@antithesishq/bombadil/defaults`.
+
+const page = extract((state) => ({
+  isHtml: state.document.contentType === "text/html",
+  title: state.document.title,
+  path: state.window.location.pathname,
+}));
+
+export const noEmptyTitles = always(
+  () => !page.current.isHtml || page.current.title.trim() !== "",
+);
+
+export const noErrorPages = always(
+  () =>
+    !page.current.isHtml ||
+    !(
+      /\b(404|500)\b/.test(page.current.title) ||
+      /\/(404|500)(\.html)?$/.test(page.current.path)
+    ),
+);
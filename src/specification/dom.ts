@@ -0,0 +1,72 @@
+import { extract, type Cell } from "@antithesishq/bombadil";
+
+export interface HtmlOptions {
+  /** Serialized HTML longer than this is truncated. */
+  maxLength?: number;
+}
+
+export type HtmlSnapshot = {
+  html: string;
+  /** Whether `html` was cut off at `maxLength`. */
+  truncated: boolean;
+};
+
+/**
+ * Extracts the HTML of the first element matching the selector, or null if
+ * there's none. The HTML is normalized so that only meaningful changes show:
+ * attributes are sorted, comments are left out, and whitespace is collapsed.
+ */
+export function extractHtml(
+  selector: string,
+  options: HtmlOptions = {},
+): Cell<HtmlSnapshot | null> {
+  const maxLength = options.maxLength ?? 100_000;
+  return extract((state) => {
+    const element = state.document.querySelector(selector);
+    if (element === null) {
+      return null;
+    }
+    const parts: string[] = [];
+    let length = 0;
+    const push = (part: string) => {
+      parts.push(part);
+      length += part.length;
+      return length <= maxLength;
+    };
+    const escape = (text: string) =>
+      text.replace(/&/g, "&amp;").replace(/</g, "&lt;").replace(/>/g, "&gt;");
+    const serialize = (node: Node): boolean => {
+      if (node.nodeType === Node.TEXT_NODE) {
+        const text = (node.textContent ?? "").replace(/\s+/g, " ").trim();
+        return text === "" || push(escape(text));
+      }
+      if (node.nodeType !== Node.ELEMENT_NODE) {
+        return true;
+      }
+      const element = node as Element;
+      const tag = element.tagName.toLowerCase();
+      const attributes = element
+        .getAttributeNames()
+        .sort()
+        .map(
+          (name) =>
+            ` ${name}="${escape(element.getAttribute(name) ?? "").replace(/"/g, "&quot;")}"`,
+        )
+        .join("");
+      if (!push(`<${tag}${attributes}>`)) {
+        return false;
+      }
+      for (const child of element.childNodes) {
+        if (!serialize(child)) {
+          return false;
+        }
+      }
+      return push(`</${tag}>`);
+    };
+    const complete = serialize(element);
+    const html = parts.join("");
+    return complete
+      ? { html, truncated: false }
+      : { html: html.slice(0, maxLength), truncated: true };
+  });
+}
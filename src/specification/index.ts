@@ -15,6 +15,9 @@ export {
   actions,
   weighted,
   type Action,
+  type ActionTaken,
+  type ActionKind,
+  actionKind,
   type Generator,
   type Point,
   ActionGenerator,
@@ -23,11 +26,45 @@ export {
   emails,
   integers,
   keycodes,
+  dictionary,
 } from "@antithesishq/bombadil/actions";
 
-import type { Action } from "@antithesishq/bombadil/actions";
+import type { ActionTaken } from "@antithesishq/bombadil/actions";
 
 export class Formula {
+  /** Tags for selecting which properties to check, see `tag`. */
+  tags: readonly string[] = [];
+
+  /**
+   * Returns this formula with the given tags added. A test can be restricted
+   * to the properties with some tag, e.g. `--tag smoke`.
+   */
+  tag(...tags: string[]): this {
+    const tagged = Object.assign(
+      Object.create(Object.getPrototypeOf(this)),
+      this,
+    );
+    tagged.tags = [...this.tags, ...tags];
+    return tagged;
+  }
+
+  /** The element that violations are about, see `focus`. */
+  focusSelector: string | null = null;
+
+  /**
+   * Returns this formula focused on the first element matching the selector,
+   * so that its violations come with a screenshot of just that element rather
+   * than of the whole page.
+   */
+  focus(selector: string): this {
+    const focused = Object.assign(
+      Object.create(Object.getPrototypeOf(this)),
+      this,
+    );
+    focused.focusSelector = selector;
+    return focused;
+  }
+
   not(): Formula {
     return new Not(this);
   }
@@ -40,6 +77,12 @@ export class Formula {
   implies(that: IntoFormula): Formula {
     return new Implies(this, now(that));
   }
+  xor(that: IntoFormula): Formula {
+    return new Xor(this, now(that));
+  }
+  iff(that: IntoFormula): Formula {
+    return new Iff(this, now(that));
+  }
 }
 
 export class Pure extends Formula {
@@ -90,6 +133,32 @@ export class Implies extends Formula {
   }
 }
 
+export class Xor extends Formula {
+  constructor(
+    public left: Formula,
+    public right: Formula,
+  ) {
+    super();
+  }
+
+  override toString() {
+    return `${this.left}.xor(${this.right})`;
+  }
+}
+
+export class Iff extends Formula {
+  constructor(
+    public left: Formula,
+    public right: Formula,
+  ) {
+    super();
+  }
+
+  override toString() {
+    return `${this.left}.iff(${this.right})`;
+  }
+}
+
 export class Not extends Formula {
   constructor(public subformula: Formula) {
     super();
@@ -109,6 +178,19 @@ export class Next extends Formula {
   }
 }
 
+export class NextN extends Formula {
+  constructor(
+    public count: number,
+    public subformula: Formula,
+  ) {
+    super();
+  }
+
+  override toString() {
+    return `nextN(${this.count}, ${this.subformula})`;
+  }
+}
+
 export class Always extends Formula {
   constructor(
     public boundMillis: number | null,
@@ -171,6 +253,36 @@ export class Eventually extends Formula {
   }
 }
 
+export class Once extends Formula {
+  constructor(public subformula: Formula) {
+    super();
+  }
+
+  override toString() {
+    return `once(${this.subformula})`;
+  }
+}
+
+export class Historically extends Formula {
+  constructor(public subformula: Formula) {
+    super();
+  }
+
+  override toString() {
+    return `historically(${this.subformula})`;
+  }
+}
+
+export class Stable extends Eventually {
+  constructor(public settled: Formula) {
+    super(null, new Always(null, settled));
+  }
+
+  override toString() {
+    return `stable(${this.settled})`;
+  }
+}
+
 export class Thunk extends Formula {
   constructor(
     private pretty: string,
@@ -190,6 +302,14 @@ export function not(value: IntoFormula) {
   return new Not(now(value));
 }
 
+export function xor(left: IntoFormula, right: IntoFormula): Formula {
+  return new Xor(now(left), now(right));
+}
+
+export function iff(left: IntoFormula, right: IntoFormula): Formula {
+  return new Iff(now(left), now(right));
+}
+
 export function now(x: IntoFormula): Formula {
   if (typeof x === "function") {
     const pretty = x
@@ -211,6 +331,13 @@ export function next(x: IntoFormula): Formula {
   return new Next(now(x));
 }
 
+export function nextN(n: number, x: IntoFormula): Formula {
+  if (!Number.isInteger(n) || n < 0) {
+    throw new RangeError(`invalid count ${n}, expected non-negative integer`);
+  }
+  return new NextN(n, now(x));
+}
+
 export function always(x: IntoFormula): Always {
   return new Always(null, now(x));
 }
@@ -219,6 +346,31 @@ export function eventually(x: IntoFormula): Eventually {
   return new Eventually(null, now(x));
 }
 
+/**
+ * Holds if `x` eventually holds and keeps holding, i.e. `eventually(always(x))`.
+ */
+export function stable(x: IntoFormula): Eventually {
+  return new Stable(now(x));
+}
+
+export const eventuallyAlways = stable;
+
+/**
+ * Holds if `x` held in this or any earlier state, e.g. "the logout button is
+ * only shown once logged in". `x` can't be about the future.
+ */
+export function once(x: IntoFormula): Once {
+  return new Once(now(x));
+}
+
+/**
+ * Holds if `x` held in this and every earlier state. `x` can't be about the
+ * future.
+ */
+export function historically(x: IntoFormula): Historically {
+  return new Historically(now(x));
+}
+
 export function extract<T extends JSON>(query: (state: State) => T): Cell<T> {
   return new ExtractorCell<T, State>(runtime, query);
 }
@@ -243,7 +395,17 @@ export interface State {
     }[];
   };
   console: ConsoleEntry[];
-  lastAction: Action | null;
+  /** The action that led to this state, or null in the first state. */
+  lastAction: ActionTaken | null;
+  /**
+   * The document's scroll position in CSS pixels, and the furthest it scrolls
+   * in each direction (0 when it fits in the viewport).
+   */
+  scroll: { x: number; y: number; maxX: number; maxY: number };
+  /** The size of the viewport in CSS pixels. */
+  viewport: { width: number; height: number };
+  /** Whether the page stopped responding while acting, e.g. stuck in a loop. */
+  unresponsive: boolean;
 }
 
 export type NavigationEntry = {
@@ -65,6 +65,10 @@ export class ExtractorCell<T extends JSON, S> implements Cell<T> {
   run(state: S): T {
     return this.extract(state);
   }
+
+  get pretty(): string {
+    return this.extract.toString();
+  }
 }
 
 export class TimeCell implements Cell<Time> {
@@ -101,7 +105,15 @@ export class Runtime<S> {
     return this.extractors.map((extractor) => {
       this.extractingDepth++;
       try {
-        return { name: extractor.name, value: extractor.run(state) };
+        const value = extractor.run(state);
+        // Extractors run while the page is paused, so promises can't settle.
+        if (value instanceof Promise) {
+          throw new Error(
+            `extractor ${extractor.name ?? extractor.pretty} returned a promise, ` +
+              "but extractors must be synchronous as they run while the page is paused",
+          );
+        }
+        return { name: extractor.name, value };
       } finally {
         this.extractingDepth--;
       }
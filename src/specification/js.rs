@@ -9,9 +9,10 @@ use serde::{Deserialize, Serialize};
 use serde_json as json;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::browser::actions::BrowserAction;
+use crate::browser::actions::{BrowserAction, TypingJitter};
 use crate::geometry::Point;
 use crate::specification::{
+    ltl,
     result::{Result, SpecificationError},
     syntax::Syntax,
     verifier::Snapshot,
@@ -28,11 +29,20 @@ pub enum JsAction {
         name: String,
         content: Option<String>,
         point: Point,
+        #[serde(default)]
+        selector: Option<String>,
     },
     #[serde(rename_all = "camelCase")]
     TypeText {
         text: String,
         delay_millis: f64,
+        /// With this, the delay before each character is random, between `delay_millis` and
+        /// this.
+        #[serde(default)]
+        max_delay_millis: Option<f64>,
+        /// Seeds the random delays, which are otherwise seeded randomly.
+        #[serde(default)]
+        seed: Option<f64>,
     },
     #[serde(rename_all = "camelCase")]
     PressKey {
@@ -49,6 +59,7 @@ pub enum JsAction {
         distance: f64,
     },
     Reload,
+    Sequence(Vec<JsAction>),
 }
 
 impl JsAction {
@@ -64,21 +75,62 @@ impl JsAction {
                 name,
                 content,
                 point,
+                selector,
             } => BrowserAction::Click {
                 name,
                 content,
                 point,
+                selector,
             },
-            JsAction::TypeText { text, delay_millis } => {
+            JsAction::TypeText {
+                text,
+                delay_millis,
+                max_delay_millis,
+                seed,
+            } => {
                 if !delay_millis.is_finite() || delay_millis < 0.0 {
                     bail!(
                         "delayMillis must be a non-negative finite number, got {}",
                         delay_millis
                     );
                 }
+                let jitter = match max_delay_millis {
+                    Some(max_delay_millis) => {
+                        if !max_delay_millis.is_finite()
+                            || max_delay_millis < delay_millis
+                        {
+                            bail!(
+                                "maxDelayMillis must be a finite number no less than delayMillis, got {}",
+                                max_delay_millis
+                            );
+                        }
+                        let seed = match seed {
+                            Some(seed) => {
+                                if !seed.is_finite()
+                                    || !(0.0..=u32::MAX as f64).contains(&seed)
+                                    || seed.fract() != 0.0
+                                {
+                                    bail!(
+                                        "seed must be an integer between 0 and {}, got {}",
+                                        u32::MAX,
+                                        seed
+                                    );
+                                }
+                                seed as u64
+                            }
+                            None => rand::random::<u32>() as u64,
+                        };
+                        Some(TypingJitter {
+                            max_delay_millis: max_delay_millis as u64,
+                            seed,
+                        })
+                    }
+                    None => None,
+                };
                 BrowserAction::TypeText {
                     text,
                     delay_millis: delay_millis as u64,
+                    jitter,
                 }
             }
             JsAction::PressKey { code } => {
@@ -99,6 +151,17 @@ impl JsAction {
             JsAction::ScrollDown { origin, distance } => {
                 BrowserAction::ScrollDown { origin, distance }
             }
+            JsAction::Sequence(actions) => {
+                if actions.is_empty() {
+                    bail!("a sequence must have at least one action");
+                }
+                BrowserAction::Sequence(
+                    actions
+                        .into_iter()
+                        .map(JsAction::to_browser_action)
+                        .collect::<anyhow::Result<_>>()?,
+                )
+            }
         })
     }
 }
@@ -190,6 +253,22 @@ impl Syntax<RuntimeFunction> {
             return Ok(Implies(Box::new(left), Box::new(right)));
         }
 
+        if value.instance_of(&bombadil.xor, context)? {
+            let left_value = object.get(js_string!("left"), context)?;
+            let right_value = object.get(js_string!("right"), context)?;
+            let left = Self::from_value(&left_value, bombadil, context)?;
+            let right = Self::from_value(&right_value, bombadil, context)?;
+            return Ok(Xor(Box::new(left), Box::new(right)));
+        }
+
+        if value.instance_of(&bombadil.iff, context)? {
+            let left_value = object.get(js_string!("left"), context)?;
+            let right_value = object.get(js_string!("right"), context)?;
+            let left = Self::from_value(&left_value, bombadil, context)?;
+            let right = Self::from_value(&right_value, bombadil, context)?;
+            return Ok(Iff(Box::new(left), Box::new(right)));
+        }
+
         if value.instance_of(&bombadil.next, context)? {
             let subformula_value =
                 object.get(js_string!("subformula"), context)?;
@@ -198,6 +277,24 @@ impl Syntax<RuntimeFunction> {
             return Ok(Next(Box::new(subformula)));
         }
 
+        if value.instance_of(&bombadil.next_n, context)? {
+            let count_value = object.get(js_string!("count"), context)?;
+            let count = count_value
+                .as_number()
+                .filter(|count| {
+                    count.is_finite() && *count >= 0.0 && count.fract() == 0.0
+                })
+                .ok_or(SpecificationError::OtherError(format!(
+                    "NextN.count is not a non-negative integer: {}",
+                    count_value.display()
+                )))?;
+            let subformula_value =
+                object.get(js_string!("subformula"), context)?;
+            let subformula =
+                Self::from_value(&subformula_value, bombadil, context)?;
+            return Ok(NextN(count as u64, Box::new(subformula)));
+        }
+
         if value.instance_of(&bombadil.always, context)? {
             let subformula_value =
                 object.get(js_string!("subformula"), context)?;
@@ -220,6 +317,22 @@ impl Syntax<RuntimeFunction> {
             return Ok(Eventually(Box::new(subformula), bound));
         }
 
+        if value.instance_of(&bombadil.once, context)? {
+            let subformula_value =
+                object.get(js_string!("subformula"), context)?;
+            let subformula =
+                Self::from_value(&subformula_value, bombadil, context)?;
+            return Ok(Once(Box::new(subformula)));
+        }
+
+        if value.instance_of(&bombadil.historically, context)? {
+            let subformula_value =
+                object.get(js_string!("subformula"), context)?;
+            let subformula =
+                Self::from_value(&subformula_value, bombadil, context)?;
+            return Ok(Historically(Box::new(subformula)));
+        }
+
         Err(SpecificationError::OtherError(format!(
             "can't convert to formula: {}",
             value.display()
@@ -250,7 +363,15 @@ fn optional_duration_from_js(value: JsValue) -> Result<Option<Duration>> {
             value.display()
         )));
     }
-    Ok(Some(Duration::from_millis(millis as u64)))
+    let duration = Duration::from_millis(millis as u64);
+    if duration > ltl::MAX_BOUND {
+        return Err(SpecificationError::OtherError(format!(
+            "bound of {}ms is longer than the maximum of {}ms (a year)",
+            value.display(),
+            ltl::MAX_BOUND.as_millis()
+        )));
+    }
+    Ok(Some(duration))
 }
 
 #[derive(Debug)]
@@ -262,9 +383,14 @@ pub struct BombadilExports {
     pub and: JsValue,
     pub or: JsValue,
     pub implies: JsValue,
+    pub xor: JsValue,
+    pub iff: JsValue,
     pub next: JsValue,
+    pub next_n: JsValue,
     pub always: JsValue,
     pub eventually: JsValue,
+    pub once: JsValue,
+    pub historically: JsValue,
     pub runtime: JsObject,
     pub time: JsObject,
     pub action_generator: JsValue,
@@ -290,9 +416,14 @@ impl BombadilExports {
             and: get_export("And")?,
             or: get_export("Or")?,
             implies: get_export("Implies")?,
+            xor: get_export("Xor")?,
+            iff: get_export("Iff")?,
             next: get_export("Next")?,
+            next_n: get_export("NextN")?,
             always: get_export("Always")?,
             eventually: get_export("Eventually")?,
+            once: get_export("Once")?,
+            historically: get_export("Historically")?,
             runtime: get_export("runtime")?.as_object().ok_or(
                 SpecificationError::OtherError(
                     "runtime is not an object".to_string(),
@@ -324,9 +455,14 @@ impl BombadilExports {
             and: get_export("And")?,
             or: get_export("Or")?,
             implies: get_export("Implies")?,
+            xor: get_export("Xor")?,
+            iff: get_export("Iff")?,
             next: get_export("Next")?,
+            next_n: get_export("NextN")?,
             always: get_export("Always")?,
             eventually: get_export("Eventually")?,
+            once: get_export("Once")?,
+            historically: get_export("Historically")?,
             runtime: get_export("runtime")?.as_object().ok_or(
                 SpecificationError::OtherError(
                     "runtime is not an object".to_string(),
@@ -375,6 +511,45 @@ impl Extractors {
         self.instances.get(index)
     }
 
+    /// The source of each extractor's function, in registration order.
+    pub fn pretty(&self, context: &mut Context) -> Result<Vec<String>> {
+        self.instances
+            .iter()
+            .map(|extractor| {
+                let value = extractor.get(js_string!("pretty"), context)?;
+                Ok(value
+                    .as_string()
+                    .ok_or(SpecificationError::OtherError(format!(
+                        "extractor pretty is not a string: {}",
+                        value.display()
+                    )))?
+                    .to_std_string_escaped())
+            })
+            .collect()
+    }
+
+    /// The name of each extractor given with `named`, in registration order.
+    pub fn names(&self, context: &mut Context) -> Result<Vec<Option<String>>> {
+        self.instances
+            .iter()
+            .map(|extractor| {
+                let value = extractor.get(js_string!("name"), context)?;
+                if value.is_null_or_undefined() {
+                    return Ok(None);
+                }
+                Ok(Some(
+                    value
+                        .as_string()
+                        .ok_or(SpecificationError::OtherError(format!(
+                            "extractor name is not a string: {}",
+                            value.display()
+                        )))?
+                        .to_std_string_escaped(),
+                ))
+            })
+            .collect()
+    }
+
     pub fn update_from_snapshots(
         &self,
         snapshots: Vec<Snapshot>,
@@ -400,17 +575,7 @@ impl Extractors {
             Ok(())
         };
 
-        let time = JsValue::from_json(
-            &json::Value::Number(
-                json::Number::from_u128(
-                    time.duration_since(UNIX_EPOCH)?.as_millis(),
-                )
-                .ok_or(SpecificationError::OtherError(
-                    "conversion from SystemTime to number failed".to_string(),
-                ))?,
-            ),
-            context,
-        )?;
+        let time = time_to_js(time, context)?;
 
         update(&self.time, JsValue::null(), time.clone(), context)?;
 
@@ -424,10 +589,41 @@ impl Extractors {
     }
 }
 
+fn time_to_js(time: SystemTime, context: &mut Context) -> Result<JsValue> {
+    Ok(JsValue::from_json(
+        &json::Value::Number(
+            json::Number::from_u128(
+                time.duration_since(UNIX_EPOCH)?.as_millis(),
+            )
+            .ok_or(SpecificationError::OtherError(
+                "conversion from SystemTime to number failed".to_string(),
+            ))?,
+        ),
+        context,
+    )?)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_duration_bounds() {
+        assert_eq!(
+            optional_duration_from_js(JsValue::from(1000)).unwrap(),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            optional_duration_from_js(JsValue::undefined()).unwrap(),
+            None
+        );
+        assert!(optional_duration_from_js(JsValue::from(-1)).is_err());
+        assert!(
+            optional_duration_from_js(JsValue::from(u64::MAX as f64 - 1.0))
+                .is_err()
+        );
+    }
+
     #[test]
     fn test_deserialize_js_action_with_float_integers() {
         // TypeText with delayMillis as float (PascalCase variant, camelCase fields)
@@ -456,6 +652,8 @@ mod tests {
         let js_action = JsAction::TypeText {
             text: "hello".to_string(),
             delay_millis: 43.9,
+            max_delay_millis: None,
+            seed: None,
         };
         let browser_action = js_action.to_browser_action().unwrap();
         match browser_action {
@@ -489,6 +687,8 @@ mod tests {
         let js_action = JsAction::TypeText {
             text: "hello".to_string(),
             delay_millis: -10.0,
+            max_delay_millis: None,
+            seed: None,
         };
         let result = js_action.to_browser_action();
         assert!(result.is_err());
@@ -497,9 +697,118 @@ mod tests {
         let js_action = JsAction::TypeText {
             text: "hello".to_string(),
             delay_millis: f64::NAN,
+            max_delay_millis: None,
+            seed: None,
         };
         let result = js_action.to_browser_action();
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("finite"));
     }
+
+    #[test]
+    fn test_to_browser_action_sequence() {
+        let json = r#"{"Sequence": [{"TypeText": {"text": "hi", "delayMillis": 20}}, {"PressKey": {"code": 13}}]}"#;
+        let action: JsAction = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            action.to_browser_action().unwrap(),
+            BrowserAction::Sequence(vec![
+                BrowserAction::TypeText {
+                    text: "hi".to_string(),
+                    delay_millis: 20,
+                    jitter: None,
+                },
+                BrowserAction::PressKey { code: 13 },
+            ])
+        );
+
+        let js_action =
+            JsAction::Sequence(vec![JsAction::PressKey { code: 256.0 }]);
+        assert!(js_action.to_browser_action().is_err());
+        assert!(JsAction::Sequence(vec![]).to_browser_action().is_err());
+    }
+
+    #[test]
+    fn test_to_browser_action_jitter() {
+        let json = r#"{"TypeText": {"text": "hi", "delayMillis": 20, "maxDelayMillis": 300.0, "seed": 7}}"#;
+        let action: JsAction = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            action.to_browser_action().unwrap(),
+            BrowserAction::TypeText {
+                text: "hi".to_string(),
+                delay_millis: 20,
+                jitter: Some(TypingJitter {
+                    max_delay_millis: 300,
+                    seed: 7,
+                }),
+            }
+        );
+
+        let js_action = JsAction::TypeText {
+            text: "hi".to_string(),
+            delay_millis: 20.0,
+            max_delay_millis: Some(300.0),
+            seed: None,
+        };
+        match js_action.to_browser_action().unwrap() {
+            BrowserAction::TypeText { jitter, .. } => assert!(jitter.is_some()),
+            _ => panic!("expected TypeText"),
+        }
+
+        let js_action = JsAction::TypeText {
+            text: "hi".to_string(),
+            delay_millis: 20.0,
+            max_delay_millis: Some(10.0),
+            seed: None,
+        };
+        assert!(js_action.to_browser_action().is_err());
+
+        let js_action = JsAction::TypeText {
+            text: "hi".to_string(),
+            delay_millis: 20.0,
+            max_delay_millis: Some(300.0),
+            seed: Some(-1.0),
+        };
+        assert!(js_action.to_browser_action().is_err());
+    }
+
+    #[test]
+    fn test_update_from_snapshots_large_string() {
+        let mut context = Context::default();
+        let cell = |context: &mut Context| {
+            context
+                .eval(boa_engine::Source::from_bytes(
+                    "({ update(value, time) { this.value = value; } })",
+                ))
+                .unwrap()
+                .as_object()
+                .unwrap()
+                .clone()
+        };
+        let extractors = Extractors {
+            instances: vec![cell(&mut context)],
+            time: cell(&mut context),
+        };
+        let value = json::json!({
+            "html": "<p>a</p>".repeat(1 << 17),
+            "truncated": false,
+        });
+
+        extractors
+            .update_from_snapshots(
+                vec![Snapshot {
+                    name: None,
+                    value: value.clone(),
+                }],
+                SystemTime::now(),
+                &mut context,
+            )
+            .unwrap();
+
+        let updated = extractors.instances[0]
+            .get(js_string!("value"), &mut context)
+            .unwrap()
+            .to_json(&mut context)
+            .unwrap();
+        assert_eq!(updated, Some(value));
+    }
 }
@@ -1,20 +1,35 @@
 use std::time::{Duration, SystemTime};
 
 use crate::specification::result::{Result, SpecificationError};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-/// A formula in negation normal form (NNF), up to thunks. Note that `Implies` is preserved for
-/// better error messages.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+/// A formula in negation normal form (NNF), up to thunks. Note that `Implies`, `Xor` and `Iff`
+/// are preserved for better error messages, and so that the operands of `Xor` and `Iff` aren't
+/// lowered both as is and negated.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Formula<Function> {
-    Pure { value: bool, pretty: String },
-    Thunk { function: Function, negated: bool },
+    Pure {
+        value: bool,
+        pretty: String,
+    },
+    Thunk {
+        function: Function,
+        negated: bool,
+    },
     And(Box<Formula<Function>>, Box<Formula<Function>>),
     Or(Box<Formula<Function>>, Box<Formula<Function>>),
     Implies(Box<Formula<Function>>, Box<Formula<Function>>),
+    Xor(Box<Formula<Function>>, Box<Formula<Function>>),
+    Iff(Box<Formula<Function>>, Box<Formula<Function>>),
     Next(Box<Formula<Function>>),
+    /// `Next` repeated a number of times, stepping down one at a time instead of nesting.
+    NextN(u64, Box<Formula<Function>>),
     Always(Box<Formula<Function>>, Option<Duration>),
     Eventually(Box<Formula<Function>>, Option<Duration>),
+    /// Held in some state up to and including the current one.
+    Once(Box<Formula<Function>>),
+    /// Held in every state up to and including the current one.
+    Historically(Box<Formula<Function>>),
 }
 
 impl<Function: Clone> Formula<Function> {
@@ -50,9 +65,21 @@ impl<Function: Clone> Formula<Function> {
                 Box::new(left.clone().map_function_ref(f)),
                 Box::new(right.clone().map_function_ref(f)),
             ),
+            Formula::Xor(left, right) => Formula::Xor(
+                Box::new(left.clone().map_function_ref(f)),
+                Box::new(right.clone().map_function_ref(f)),
+            ),
+            Formula::Iff(left, right) => Formula::Iff(
+                Box::new(left.clone().map_function_ref(f)),
+                Box::new(right.clone().map_function_ref(f)),
+            ),
             Formula::Next(formula) => {
                 Formula::Next(Box::new(formula.clone().map_function_ref(f)))
             }
+            Formula::NextN(count, formula) => Formula::NextN(
+                *count,
+                Box::new(formula.clone().map_function_ref(f)),
+            ),
             Formula::Always(formula, bound) => Formula::Always(
                 Box::new(formula.clone().map_function_ref(f)),
                 *bound,
@@ -61,12 +88,32 @@ impl<Function: Clone> Formula<Function> {
                 Box::new(formula.clone().map_function_ref(f)),
                 *bound,
             ),
+            Formula::Once(formula) => {
+                Formula::Once(Box::new(formula.clone().map_function_ref(f)))
+            }
+            Formula::Historically(formula) => Formula::Historically(Box::new(
+                formula.clone().map_function_ref(f),
+            )),
         }
     }
 }
 
 pub type Time = SystemTime;
 
+/// The longest bound of `always` and `eventually`, far longer than any test runs. Longer bounds
+/// are rejected when converting formulas, so that bounds can't overflow when added to times.
+pub const MAX_BOUND: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// When a bound starting at the time ends.
+fn bound_end(time: Time, bound: Duration) -> Result<Time> {
+    time.checked_add(bound)
+        .ok_or(SpecificationError::OtherError(format!(
+            "bound of {}ms overflows when added to time {:?}",
+            bound.as_millis(),
+            time
+        )))
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Value<Function> {
     True,
@@ -74,7 +121,18 @@ pub enum Value<Function> {
     Residual(Residual<Function>),
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize)]
+impl<Function> Value<Function> {
+    /// The value as a residual, decided ones stepping to themselves.
+    fn into_residual(self) -> Residual<Function> {
+        match self {
+            Value::True => Residual::True,
+            Value::False(violation) => Residual::False(violation),
+            Value::Residual(residual) => residual,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Violation<Function> {
     False {
         time: Time,
@@ -83,6 +141,9 @@ pub enum Violation<Function> {
     Eventually {
         subformula: Box<Formula<Function>>,
         reason: EventuallyViolation,
+        /// Only kept when the evaluator is asked for evidence, see
+        /// [`Evaluator::with_eventually_evidence`].
+        evidence: Option<EventuallyEvidence<Function>>,
     },
     Always {
         violation: Box<Violation<Function>>,
@@ -103,10 +164,57 @@ pub enum Violation<Function> {
         left: Formula<Function>,
         right: Box<Violation<Function>>,
     },
+    /// Both sides of an `xor` held.
+    XorBoth {
+        left: Formula<Function>,
+        right: Formula<Function>,
+    },
+    /// Neither side of an `xor` held.
+    XorNeither {
+        left: Box<Violation<Function>>,
+        right: Box<Violation<Function>>,
+    },
+    /// One side of an `iff` held, but not the other.
+    Iff {
+        held: Formula<Function>,
+        violation: Box<Violation<Function>>,
+    },
+    /// The subformula of a `once` held in none of the states up to `time`.
+    Once {
+        subformula: Box<Formula<Function>>,
+        time: Time,
+    },
+    /// The subformula of a `historically` didn't hold in the state at `time`, the earliest one
+    /// in which it didn't.
+    Historically {
+        violation: Box<Violation<Function>>,
+        subformula: Box<Formula<Function>>,
+        time: Time,
+    },
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+/// The most recent state in which the subformula of an `eventually` was checked and found false.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EventuallyEvidence<Function> {
+    pub time: Time,
+    pub violation: Box<Violation<Function>>,
+}
+
+impl<Function: Clone> EventuallyEvidence<Function> {
+    fn map_function_ref<Result>(
+        &self,
+        f: &impl Fn(&Function) -> Result,
+    ) -> EventuallyEvidence<Result> {
+        EventuallyEvidence {
+            time: self.time,
+            violation: Box::new(self.violation.map_function_ref(f)),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum EventuallyViolation {
+    /// The deadline passed without the subformula holding.
     TimedOut(Time),
     TestEnded,
 }
@@ -128,12 +236,17 @@ impl<Function: Clone> Violation<Function> {
                 time: *time,
                 condition: condition.clone(),
             },
-            Violation::Eventually { subformula, reason } => {
-                Violation::Eventually {
-                    subformula: Box::new(subformula.map_function_ref(f)),
-                    reason: *reason,
-                }
-            }
+            Violation::Eventually {
+                subformula,
+                reason,
+                evidence,
+            } => Violation::Eventually {
+                subformula: Box::new(subformula.map_function_ref(f)),
+                reason: *reason,
+                evidence: evidence
+                    .as_ref()
+                    .map(|evidence| evidence.map_function_ref(f)),
+            },
             Violation::Always {
                 violation,
                 subformula,
@@ -159,17 +272,42 @@ impl<Function: Clone> Violation<Function> {
                 left: left.map_function_ref(f),
                 right: Box::new(right.map_function_ref(f)),
             },
+            Violation::XorBoth { left, right } => Violation::XorBoth {
+                left: left.map_function_ref(f),
+                right: right.map_function_ref(f),
+            },
+            Violation::XorNeither { left, right } => Violation::XorNeither {
+                left: Box::new(left.map_function_ref(f)),
+                right: Box::new(right.map_function_ref(f)),
+            },
+            Violation::Iff { held, violation } => Violation::Iff {
+                held: held.map_function_ref(f),
+                violation: Box::new(violation.map_function_ref(f)),
+            },
+            Violation::Once { subformula, time } => Violation::Once {
+                subformula: Box::new(subformula.map_function_ref(f)),
+                time: *time,
+            },
+            Violation::Historically {
+                violation,
+                subformula,
+                time,
+            } => Violation::Historically {
+                violation: Box::new(violation.map_function_ref(f)),
+                subformula: Box::new(subformula.map_function_ref(f)),
+                time: *time,
+            },
         }
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Leaning<Function> {
     AssumeTrue,
     AssumeFalse(Violation<Function>),
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Residual<Function> {
     True,
     False(Violation<Function>),
@@ -187,6 +325,18 @@ pub enum Residual<Function> {
         left: Box<Residual<Function>>,
         right: Box<Residual<Function>>,
     },
+    Xor {
+        left_formula: Formula<Function>,
+        right_formula: Formula<Function>,
+        left: Box<Residual<Function>>,
+        right: Box<Residual<Function>>,
+    },
+    Iff {
+        left_formula: Formula<Function>,
+        right_formula: Formula<Function>,
+        left: Box<Residual<Function>>,
+        right: Box<Residual<Function>>,
+    },
     OrEventually {
         subformula: Box<Formula<Function>>,
         start: Time,
@@ -203,7 +353,7 @@ pub enum Residual<Function> {
     },
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Derived<Function> {
     Once {
         start: Time,
@@ -218,19 +368,390 @@ pub enum Derived<Function> {
         start: Time,
         end: Option<Time>,
         subformula: Box<Formula<Function>>,
+        evidence: Option<EventuallyEvidence<Function>>,
     },
 }
 
+impl<Function: Clone> Residual<Function> {
+    pub fn map_function<Result>(
+        &self,
+        f: impl Fn(&Function) -> Result,
+    ) -> Residual<Result> {
+        self.map_function_ref(&f)
+    }
+
+    fn map_function_ref<Result>(
+        &self,
+        f: &impl Fn(&Function) -> Result,
+    ) -> Residual<Result> {
+        let map = |residual: &Residual<Function>| {
+            Box::new(residual.map_function_ref(f))
+        };
+        match self {
+            Residual::True => Residual::True,
+            Residual::False(violation) => {
+                Residual::False(violation.map_function_ref(f))
+            }
+            Residual::Derived(derived, leaning) => Residual::Derived(
+                derived.map_function_ref(f),
+                leaning.map_function_ref(f),
+            ),
+            Residual::And { left, right } => Residual::And {
+                left: map(left),
+                right: map(right),
+            },
+            Residual::Or { left, right } => Residual::Or {
+                left: map(left),
+                right: map(right),
+            },
+            Residual::Implies {
+                left_formula,
+                left,
+                right,
+            } => Residual::Implies {
+                left_formula: left_formula.map_function_ref(f),
+                left: map(left),
+                right: map(right),
+            },
+            Residual::Xor {
+                left_formula,
+                right_formula,
+                left,
+                right,
+            } => Residual::Xor {
+                left_formula: left_formula.map_function_ref(f),
+                right_formula: right_formula.map_function_ref(f),
+                left: map(left),
+                right: map(right),
+            },
+            Residual::Iff {
+                left_formula,
+                right_formula,
+                left,
+                right,
+            } => Residual::Iff {
+                left_formula: left_formula.map_function_ref(f),
+                right_formula: right_formula.map_function_ref(f),
+                left: map(left),
+                right: map(right),
+            },
+            Residual::OrEventually {
+                subformula,
+                start,
+                end,
+                left,
+                right,
+            } => Residual::OrEventually {
+                subformula: Box::new(subformula.map_function_ref(f)),
+                start: *start,
+                end: *end,
+                left: map(left),
+                right: map(right),
+            },
+            Residual::AndAlways {
+                subformula,
+                start,
+                end,
+                left,
+                right,
+            } => Residual::AndAlways {
+                subformula: Box::new(subformula.map_function_ref(f)),
+                start: *start,
+                end: *end,
+                left: map(left),
+                right: map(right),
+            },
+        }
+    }
+}
+
+impl<Function: Clone> Derived<Function> {
+    fn map_function_ref<Result>(
+        &self,
+        f: &impl Fn(&Function) -> Result,
+    ) -> Derived<Result> {
+        match self {
+            Derived::Once { start, subformula } => Derived::Once {
+                start: *start,
+                subformula: Box::new(subformula.map_function_ref(f)),
+            },
+            Derived::Always {
+                start,
+                end,
+                subformula,
+            } => Derived::Always {
+                start: *start,
+                end: *end,
+                subformula: Box::new(subformula.map_function_ref(f)),
+            },
+            Derived::Eventually {
+                start,
+                end,
+                subformula,
+                evidence,
+            } => Derived::Eventually {
+                start: *start,
+                end: *end,
+                subformula: Box::new(subformula.map_function_ref(f)),
+                evidence: evidence
+                    .as_ref()
+                    .map(|evidence| evidence.map_function_ref(f)),
+            },
+        }
+    }
+}
+
+impl<Function: Clone> Leaning<Function> {
+    fn map_function_ref<Result>(
+        &self,
+        f: &impl Fn(&Function) -> Result,
+    ) -> Leaning<Result> {
+        match self {
+            Leaning::AssumeTrue => Leaning::AssumeTrue,
+            Leaning::AssumeFalse(violation) => {
+                Leaning::AssumeFalse(violation.map_function_ref(f))
+            }
+        }
+    }
+}
+
+/// The past-time operators in a formula, stepped through every state from the first, so that
+/// they're decided in the current state without looking back:
+///
+///   once(φ)ₜ ⇔ φₜ ∨ once(φ)ₜ₋₁
+///   historically(φ)ₜ ⇔ φₜ ∧ historically(φ)ₜ₋₁
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PastTime<Function> {
+    /// Each operator after the ones nested in it, so they're stepped first.
+    operators: Vec<PastOperator<Function>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+enum PastOperator<Function> {
+    /// Whether the subformula held in any state so far.
+    Once {
+        subformula: Formula<Function>,
+        held: bool,
+    },
+    /// The violation of the subformula in the earliest state it didn't hold in, if any.
+    Historically {
+        subformula: Formula<Function>,
+        violation: Option<Violation<Function>>,
+    },
+}
+
+impl<Function: Clone + PartialEq> PastTime<Function> {
+    /// Finds the past-time operators in the formula, which can't contain future-time operators,
+    /// as their subformulas must be decided in each state.
+    pub fn new(formula: &Formula<Function>) -> Result<Self> {
+        let mut past_time = PastTime { operators: vec![] };
+        past_time.register(formula, false)?;
+        Ok(past_time)
+    }
+
+    fn register(
+        &mut self,
+        formula: &Formula<Function>,
+        in_past: bool,
+    ) -> Result<()> {
+        match formula {
+            Formula::Pure { .. } | Formula::Thunk { .. } => Ok(()),
+            Formula::And(left, right)
+            | Formula::Or(left, right)
+            | Formula::Implies(left, right)
+            | Formula::Xor(left, right)
+            | Formula::Iff(left, right) => {
+                self.register(left, in_past)?;
+                self.register(right, in_past)
+            }
+            Formula::Next(subformula)
+            | Formula::NextN(_, subformula)
+            | Formula::Always(subformula, _)
+            | Formula::Eventually(subformula, _) => {
+                if in_past {
+                    return Err(future_in_past_operator());
+                }
+                self.register(subformula, in_past)
+            }
+            Formula::Once(subformula) => {
+                self.register(subformula, true)?;
+                if find_once(&self.operators, subformula).is_none() {
+                    self.operators.push(PastOperator::Once {
+                        subformula: (**subformula).clone(),
+                        held: false,
+                    });
+                }
+                Ok(())
+            }
+            Formula::Historically(subformula) => {
+                self.register(subformula, true)?;
+                if find_historically(&self.operators, subformula).is_none() {
+                    self.operators.push(PastOperator::Historically {
+                        subformula: (**subformula).clone(),
+                        violation: None,
+                    });
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Steps the operators into the state at the time, evaluating their subformulas in it.
+    pub fn step(
+        &mut self,
+        evaluate_thunk: EvaluateThunk<'_, Function>,
+        time: Time,
+    ) -> Result<()> {
+        for index in 0..self.operators.len() {
+            let (stepped, operators) = self.operators.split_at_mut(index);
+            let mut evaluator = Evaluator {
+                evaluate_thunk: &mut *evaluate_thunk,
+                eventually_evidence: false,
+                past: stepped,
+            };
+            match &mut operators[0] {
+                PastOperator::Once {
+                    subformula,
+                    held: held @ false,
+                } => {
+                    if let Value::True =
+                        evaluator.evaluate_in_state(subformula, time)?
+                    {
+                        *held = true;
+                    }
+                }
+                PastOperator::Historically {
+                    subformula,
+                    violation: violation @ None,
+                } => {
+                    if let Value::False(subformula_violation) =
+                        evaluator.evaluate_in_state(subformula, time)?
+                    {
+                        *violation = Some(Violation::Historically {
+                            violation: Box::new(subformula_violation),
+                            subformula: Box::new(subformula.clone()),
+                            time,
+                        });
+                    }
+                }
+                // Once decided, they stay decided.
+                PastOperator::Once { .. }
+                | PastOperator::Historically { .. } => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<Function: Clone> PastTime<Function> {
+    pub fn map_function<Result>(
+        &self,
+        f: impl Fn(&Function) -> Result,
+    ) -> PastTime<Result> {
+        PastTime {
+            operators: self
+                .operators
+                .iter()
+                .map(|operator| match operator {
+                    PastOperator::Once { subformula, held } => {
+                        PastOperator::Once {
+                            subformula: subformula.map_function_ref(&f),
+                            held: *held,
+                        }
+                    }
+                    PastOperator::Historically {
+                        subformula,
+                        violation,
+                    } => PastOperator::Historically {
+                        subformula: subformula.map_function_ref(&f),
+                        violation: violation
+                            .as_ref()
+                            .map(|violation| violation.map_function_ref(&f)),
+                    },
+                })
+                .collect(),
+        }
+    }
+}
+
+fn find_once<Function: PartialEq>(
+    operators: &[PastOperator<Function>],
+    subformula: &Formula<Function>,
+) -> Option<bool> {
+    operators.iter().find_map(|operator| match operator {
+        PastOperator::Once {
+            subformula: other,
+            held,
+        } if other == subformula => Some(*held),
+        _ => None,
+    })
+}
+
+fn find_historically<'a, Function: PartialEq>(
+    operators: &'a [PastOperator<Function>],
+    subformula: &Formula<Function>,
+) -> Option<&'a Option<Violation<Function>>> {
+    operators.iter().find_map(|operator| match operator {
+        PastOperator::Historically {
+            subformula: other,
+            violation,
+        } if other == subformula => Some(violation),
+        _ => None,
+    })
+}
+
+fn future_in_past_operator() -> SpecificationError {
+    SpecificationError::OtherError(
+        "`once` and `historically` can only contain formulas decided in a single state, not \
+         ones about the future like `next`, `always` or `eventually`"
+            .to_string(),
+    )
+}
+
+/// A past-time operator that wasn't found in the formula, but created by a thunk while evaluating
+/// it, and so wasn't stepped through the earlier states.
+fn unknown_past_operator() -> SpecificationError {
+    SpecificationError::OtherError(
+        "`once` and `historically` must be part of a property's formula, not returned by a \
+         function while evaluating it"
+            .to_string(),
+    )
+}
+
 pub type EvaluateThunk<'a, Function> =
     &'a mut dyn FnMut(&'_ Function, bool) -> Result<Formula<Function>>;
 
 pub struct Evaluator<'a, Function> {
     evaluate_thunk: EvaluateThunk<'a, Function>,
+    eventually_evidence: bool,
+    past: &'a [PastOperator<Function>],
 }
 
-impl<'a, Function: Clone> Evaluator<'a, Function> {
+impl<'a, Function: Clone + PartialEq> Evaluator<'a, Function> {
     pub fn new(evaluate_thunk: EvaluateThunk<'a, Function>) -> Self {
-        Evaluator { evaluate_thunk }
+        Evaluator {
+            evaluate_thunk,
+            eventually_evidence: false,
+            past: &[],
+        }
+    }
+
+    /// The state of the past-time operators, stepped into the current state, which `once` and
+    /// `historically` are decided by.
+    pub fn with_past_time(mut self, past_time: &'a PastTime<Function>) -> Self {
+        self.past = &past_time.operators;
+        self
+    }
+
+    /// Keeps the most recent violation of each `eventually` subformula, so that a failed
+    /// `eventually` can be explained by its closest miss. Off by default, as the residuals then
+    /// hold on to a violation each.
+    pub fn with_eventually_evidence(
+        mut self,
+        eventually_evidence: bool,
+    ) -> Self {
+        self.eventually_evidence = eventually_evidence;
+        self
     }
 
     pub fn evaluate(
@@ -266,6 +787,16 @@ impl<'a, Function: Clone> Evaluator<'a, Function> {
                 let right = self.evaluate(right.as_ref(), time)?;
                 Ok(self.evaluate_implies(left_formula, &left, &right))
             }
+            Formula::Xor(left_formula, right_formula) => {
+                let left = self.evaluate(left_formula.as_ref(), time)?;
+                let right = self.evaluate(right_formula.as_ref(), time)?;
+                Ok(self.evaluate_xor(left_formula, right_formula, left, right))
+            }
+            Formula::Iff(left_formula, right_formula) => {
+                let left = self.evaluate(left_formula.as_ref(), time)?;
+                let right = self.evaluate(right_formula.as_ref(), time)?;
+                Ok(self.evaluate_iff(left_formula, right_formula, left, right))
+            }
             Formula::Next(formula) => Ok(Value::Residual(Residual::Derived(
                 Derived::Once {
                     start: time,
@@ -273,33 +804,78 @@ impl<'a, Function: Clone> Evaluator<'a, Function> {
                 },
                 Leaning::AssumeTrue, // TODO: expose true/false leaning in TS layer?
             ))),
-            Formula::Always(formula, bound) => {
-                let end = if let Some(duration) = bound {
-                    Some(time.checked_add(*duration).ok_or(
-                        SpecificationError::OtherError(
-                            "failed to add bound to time".to_string(),
-                        ),
-                    )?)
-                } else {
-                    None
+            Formula::NextN(count, formula) => {
+                let subformula = match count {
+                    0 => return self.evaluate(formula, time),
+                    1 => formula.clone(),
+                    _ => Box::new(Formula::NextN(count - 1, formula.clone())),
                 };
+                Ok(Value::Residual(Residual::Derived(
+                    Derived::Once {
+                        start: time,
+                        subformula,
+                    },
+                    Leaning::AssumeTrue,
+                )))
+            }
+            Formula::Always(formula, bound) => {
+                let end = bound
+                    .map(|duration| bound_end(time, duration))
+                    .transpose()?;
                 self.evaluate_always(formula.clone(), time, end, time)
             }
             Formula::Eventually(formula, bound) => {
-                let end = if let Some(duration) = bound {
-                    Some(time.checked_add(*duration).ok_or(
-                        SpecificationError::OtherError(
-                            "failed to add bound to time".to_string(),
-                        ),
-                    )?)
-                } else {
-                    None
-                };
-                self.evaluate_eventually(formula.clone(), time, end, time)
+                let end = bound
+                    .map(|duration| bound_end(time, duration))
+                    .transpose()?;
+                self.evaluate_eventually(formula.clone(), time, end, time, None)
+            }
+            Formula::Once(formula) => self.evaluate_once(formula, time),
+            Formula::Historically(formula) => {
+                self.evaluate_historically(formula)
             }
         }
     }
 
+    /// Evaluates a subformula of a past-time operator, which must be decided in the state, as
+    /// the operator steps on without it.
+    fn evaluate_in_state(
+        &mut self,
+        subformula: &Formula<Function>,
+        time: Time,
+    ) -> Result<Value<Function>> {
+        match self.evaluate(subformula, time)? {
+            Value::Residual(_) => Err(future_in_past_operator()),
+            value => Ok(value),
+        }
+    }
+
+    fn evaluate_once(
+        &mut self,
+        subformula: &Formula<Function>,
+        time: Time,
+    ) -> Result<Value<Function>> {
+        match find_once(self.past, subformula) {
+            Some(true) => Ok(Value::True),
+            Some(false) => Ok(Value::False(Violation::Once {
+                subformula: Box::new(subformula.clone()),
+                time,
+            })),
+            None => Err(unknown_past_operator()),
+        }
+    }
+
+    fn evaluate_historically(
+        &mut self,
+        subformula: &Formula<Function>,
+    ) -> Result<Value<Function>> {
+        match find_historically(self.past, subformula) {
+            Some(None) => Ok(Value::True),
+            Some(Some(violation)) => Ok(Value::False(violation.clone())),
+            None => Err(unknown_past_operator()),
+        }
+    }
+
     fn evaluate_and(
         &mut self,
         left: &Value<Function>,
@@ -390,6 +966,68 @@ impl<'a, Function: Clone> Evaluator<'a, Function> {
         }
     }
 
+    fn evaluate_xor(
+        &mut self,
+        left_formula: &Formula<Function>,
+        right_formula: &Formula<Function>,
+        left: Value<Function>,
+        right: Value<Function>,
+    ) -> Value<Function> {
+        match (left, right) {
+            (Value::True, Value::False(_)) | (Value::False(_), Value::True) => {
+                Value::True
+            }
+            (Value::True, Value::True) => Value::False(Violation::XorBoth {
+                left: left_formula.clone(),
+                right: right_formula.clone(),
+            }),
+            (Value::False(left), Value::False(right)) => {
+                Value::False(Violation::XorNeither {
+                    left: Box::new(left),
+                    right: Box::new(right),
+                })
+            }
+            (left, right) => Value::Residual(Residual::Xor {
+                left_formula: left_formula.clone(),
+                right_formula: right_formula.clone(),
+                left: Box::new(left.into_residual()),
+                right: Box::new(right.into_residual()),
+            }),
+        }
+    }
+
+    fn evaluate_iff(
+        &mut self,
+        left_formula: &Formula<Function>,
+        right_formula: &Formula<Function>,
+        left: Value<Function>,
+        right: Value<Function>,
+    ) -> Value<Function> {
+        match (left, right) {
+            (Value::True, Value::True) | (Value::False(_), Value::False(_)) => {
+                Value::True
+            }
+            (Value::True, Value::False(violation)) => {
+                Value::False(Violation::Iff {
+                    held: left_formula.clone(),
+                    violation: Box::new(violation),
+                })
+            }
+            (Value::False(violation), Value::True) => {
+                Value::False(Violation::Iff {
+                    held: right_formula.clone(),
+                    violation: Box::new(violation),
+                })
+            }
+            (left, right) => Value::Residual(Residual::Iff {
+                left_formula: left_formula.clone(),
+                right_formula: right_formula.clone(),
+                left: Box::new(left.into_residual()),
+                right: Box::new(right.into_residual()),
+            }),
+        }
+    }
+
     fn evaluate_always(
         &mut self,
         subformula: Box<Formula<Function>>,
@@ -498,29 +1136,44 @@ impl<'a, Function: Clone> Evaluator<'a, Function> {
         start: Time,
         end: Option<Time>,
         time: Time,
+        evidence: Option<EventuallyEvidence<Function>>,
     ) -> Result<Value<Function>> {
         if let Some(end) = end
             && end < time
         {
             return Ok(Value::False(Violation::Eventually {
                 subformula: subformula.clone(),
-                reason: EventuallyViolation::TimedOut(time),
+                reason: EventuallyViolation::TimedOut(end),
+                evidence,
             }));
         }
 
+        let value = self.evaluate(&subformula, time)?;
+        let evidence = match &value {
+            Value::False(violation) if self.eventually_evidence => {
+                Some(EventuallyEvidence {
+                    time,
+                    violation: Box::new(violation.clone()),
+                })
+            }
+            _ => evidence,
+        };
+
         let residual = Residual::Derived(
             Derived::Eventually {
                 subformula: subformula.clone(),
                 start,
                 end,
+                evidence: evidence.clone(),
             },
             Leaning::AssumeFalse(Violation::Eventually {
                 subformula: subformula.clone(),
                 reason: EventuallyViolation::TestEnded,
+                evidence,
             }),
         );
 
-        Ok(match self.evaluate(&subformula, time)? {
+        Ok(match value {
             Value::True => Value::True,
             Value::False(_violation) => Value::Residual(residual),
             Value::Residual(left) => Value::Residual(Residual::OrEventually {
@@ -545,21 +1198,47 @@ impl<'a, Function: Clone> Evaluator<'a, Function> {
         if let Some(end) = end
             && end < time
         {
+            // The right side has timed out too, with the more recent evidence.
+            if let Value::False(violation @ Violation::Eventually { .. }) =
+                right
+            {
+                return Ok(Value::False(violation));
+            }
             return Ok(Value::False(Violation::Eventually {
                 subformula,
-                reason: EventuallyViolation::TimedOut(time),
+                reason: EventuallyViolation::TimedOut(end),
+                evidence: None,
             }));
         }
 
         Ok(match (left, right) {
             (Value::True, _) => Value::True,
             (_, Value::True) => Value::True,
-            (Value::False(_), Value::False(right)) => {
+            (Value::False(left), Value::False(right)) => {
                 // NOTE: We ignore the left-side violation in `eventually` in
                 // order to not build up a giant violation tree of all the
                 // non-evidence we've seen (e.g. X was not true in state 1 and
                 // X was not true in state 2 and ...).
-                Value::False(right.clone()) // TODO: should this be wrapped in Violation::Eventually?
+                //
+                // The right side only becomes false once the deadline has
+                // passed, and is then already an `eventually` violation. Any
+                // other violation is framed as this `eventually` timing out,
+                // so that reports always say which deadline was missed.
+                Value::False(match right {
+                    Violation::Eventually { .. } => right,
+                    _ => Violation::Eventually {
+                        subformula,
+                        reason: EventuallyViolation::TimedOut(
+                            end.unwrap_or(time),
+                        ),
+                        evidence: self.eventually_evidence.then(|| {
+                            EventuallyEvidence {
+                                time,
+                                violation: Box::new(left),
+                            }
+                        }),
+                    },
+                })
             }
             (Value::False(_), Value::Residual(residual)) => {
                 Value::Residual(residual.clone())
@@ -606,6 +1285,26 @@ impl<'a, Function: Clone> Evaluator<'a, Function> {
                 let right = self.step(right, time)?;
                 self.evaluate_implies(left_formula, &left, &right)
             }
+            Residual::Xor {
+                left_formula,
+                right_formula,
+                left,
+                right,
+            } => {
+                let left = self.step(left, time)?;
+                let right = self.step(right, time)?;
+                self.evaluate_xor(left_formula, right_formula, left, right)
+            }
+            Residual::Iff {
+                left_formula,
+                right_formula,
+                left,
+                right,
+            } => {
+                let left = self.step(left, time)?;
+                let right = self.step(right, time)?;
+                self.evaluate_iff(left_formula, right_formula, left, right)
+            }
             Residual::Derived(derived, _) => match derived {
                 Derived::Once {
                     start: _,
@@ -628,11 +1327,13 @@ impl<'a, Function: Clone> Evaluator<'a, Function> {
                     start,
                     end: deadline,
                     subformula,
+                    evidence,
                 } => self.evaluate_eventually(
                     subformula.clone(),
                     *start,
                     *deadline,
                     time,
+                    evidence.clone(),
                 )?,
             },
             Residual::OrEventually {
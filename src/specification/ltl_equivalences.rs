@@ -62,9 +62,7 @@ fn syntax() -> BoxedStrategy<Syntax<Thunk>> {
     leaf.prop_recursive(8, 256, 10, |inner| {
         // recursive nodes
         prop_oneof![
-            inner.clone().prop_map(|subformula| {
-                Syntax::Thunk(Thunk::Subformula(Box::new(subformula)))
-            }),
+            thunk(inner.clone()),
             inner
                 .clone()
                 .prop_map(|subformula| { Syntax::Not(Box::new(subformula)) }),
@@ -77,20 +75,114 @@ fn syntax() -> BoxedStrategy<Syntax<Thunk>> {
             (inner.clone(), inner.clone()).prop_map(|(left, right)| {
                 Syntax::Implies(Box::new(left), Box::new(right))
             }),
+            (inner.clone(), inner.clone()).prop_map(|(left, right)| {
+                Syntax::Xor(Box::new(left), Box::new(right))
+            }),
+            (inner.clone(), inner.clone()).prop_map(|(left, right)| {
+                Syntax::Iff(Box::new(left), Box::new(right))
+            }),
             inner
                 .clone()
                 .prop_map(|subformula| { Syntax::Next(Box::new(subformula)) }),
+            (0..4u64, inner.clone()).prop_map(|(count, subformula)| {
+                Syntax::NextN(count, Box::new(subformula))
+            }),
             (inner.clone(), bound()).prop_map(|(subformula, bound)| {
                 Syntax::Always(Box::new(subformula), bound)
             }),
             (inner.clone(), bound()).prop_map(|(subformula, bound)| {
                 Syntax::Eventually(Box::new(subformula), bound)
             }),
+            state_syntax(true)
+                .prop_map(|subformula| Syntax::Once(Box::new(subformula))),
+            state_syntax(true).prop_map(|subformula| {
+                Syntax::Historically(Box::new(subformula))
+            }),
         ]
     })
     .boxed()
 }
 
+/// Thunks returning the subformulas. Past-time operators must be part of a formula rather than
+/// returned by thunks, so subformulas with them are left out.
+fn thunk(
+    subformula: BoxedStrategy<Syntax<Thunk>>,
+) -> impl Strategy<Value = Syntax<Thunk>> {
+    subformula
+        .prop_filter("past-time operators can't be returned by thunks", |s| {
+            !has_past_time(s)
+        })
+        .prop_map(|subformula| {
+            Syntax::Thunk(Thunk::Subformula(Box::new(subformula)))
+        })
+}
+
+fn has_past_time(syntax: &Syntax<Thunk>) -> bool {
+    match syntax {
+        Syntax::Pure { .. } | Syntax::Thunk(Thunk::Atomic(_)) => false,
+        Syntax::Thunk(Thunk::Subformula(subformula))
+        | Syntax::Not(subformula)
+        | Syntax::Next(subformula)
+        | Syntax::NextN(_, subformula)
+        | Syntax::Always(subformula, _)
+        | Syntax::Eventually(subformula, _) => has_past_time(subformula),
+        Syntax::And(left, right)
+        | Syntax::Or(left, right)
+        | Syntax::Implies(left, right)
+        | Syntax::Xor(left, right)
+        | Syntax::Iff(left, right) => {
+            has_past_time(left) || has_past_time(right)
+        }
+        Syntax::Once(_) | Syntax::Historically(_) => true,
+    }
+}
+
+/// Formulas decided in a single state, i.e. without future-time operators, optionally with
+/// past-time operators.
+fn state_syntax(past: bool) -> BoxedStrategy<Syntax<Thunk>> {
+    let leaf = prop_oneof![
+        any::<bool>().prop_map(|value| Syntax::Pure {
+            value,
+            pretty: format!("{}", value)
+        }),
+        variable().prop_map(|value| Syntax::Thunk(Thunk::Atomic(value))),
+    ]
+    .boxed();
+
+    leaf.prop_recursive(4, 64, 10, move |inner| {
+        let connectives = prop_oneof![
+            thunk(inner.clone()),
+            inner
+                .clone()
+                .prop_map(|subformula| { Syntax::Not(Box::new(subformula)) }),
+            (inner.clone(), inner.clone()).prop_map(|(left, right)| {
+                Syntax::And(Box::new(left), Box::new(right))
+            }),
+            (inner.clone(), inner.clone()).prop_map(|(left, right)| {
+                Syntax::Or(Box::new(left), Box::new(right))
+            }),
+            (inner.clone(), inner.clone()).prop_map(|(left, right)| {
+                Syntax::Implies(Box::new(left), Box::new(right))
+            }),
+        ];
+        if past {
+            prop_oneof![
+                connectives,
+                inner
+                    .clone()
+                    .prop_map(|subformula| Syntax::Once(Box::new(subformula))),
+                inner.clone().prop_map(|subformula| {
+                    Syntax::Historically(Box::new(subformula))
+                }),
+            ]
+            .boxed()
+        } else {
+            connectives.boxed()
+        }
+    })
+    .boxed()
+}
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 enum ValueEqMode {
     Strict,
@@ -134,12 +226,29 @@ fn assert_values_eq<Function: Clone + PartialEq + std::fmt::Debug>(
     }
 }
 
+/// The time of the state at the index in a trace.
+fn time_at(index: usize) -> Time {
+    UNIX_EPOCH + Duration::from_millis(index as u64)
+}
+
 fn check_equivalence(
     formula_left: Formula<Thunk>,
     formula_right: Formula<Thunk>,
     trace: Vec<State>,
     mode: ValueEqMode,
 ) {
+    let (value_left, value_right, time) =
+        evaluate_trace(&formula_left, &formula_right, &trace);
+    assert_values_eq(value_left, value_right, time, mode);
+}
+
+/// Evaluates both formulas through the trace, until either is decided, returning their values
+/// and the time of the last state evaluated.
+fn evaluate_trace(
+    formula_left: &Formula<Thunk>,
+    formula_right: &Formula<Thunk>,
+    trace: &[State],
+) -> (Value<Thunk>, Value<Thunk>, Time) {
     let current = RefCell::new(0);
     let mut evaluate_thunk = |thunk: &Thunk, negated| match thunk {
         Thunk::Atomic(variable) => {
@@ -164,28 +273,55 @@ fn check_equivalence(
             Ok(syntax.nnf())
         }
     };
-    let mut evaluator = Evaluator::new(&mut evaluate_thunk);
-
-    let mut time = UNIX_EPOCH;
+    let mut past_left = PastTime::new(formula_left).unwrap();
+    let mut past_right = PastTime::new(formula_right).unwrap();
 
-    let mut value_left = evaluator.evaluate(&formula_left, time).unwrap();
-    let mut value_right = evaluator.evaluate(&formula_right, time).unwrap();
+    let mut time = time_at(0);
+    past_left.step(&mut evaluate_thunk, time).unwrap();
+    past_right.step(&mut evaluate_thunk, time).unwrap();
+    let mut value_left = Evaluator::new(&mut evaluate_thunk)
+        .with_past_time(&past_left)
+        .evaluate(formula_left, time)
+        .unwrap();
+    let mut value_right = Evaluator::new(&mut evaluate_thunk)
+        .with_past_time(&past_right)
+        .evaluate(formula_right, time)
+        .unwrap();
 
-    for _ in 1..trace.len() {
-        *current.borrow_mut() += 1;
-        time = time.checked_add(Duration::from_millis(1)).unwrap();
+    for index in 1..trace.len() {
+        *current.borrow_mut() = index;
+        time = time_at(index);
+        past_left.step(&mut evaluate_thunk, time).unwrap();
+        past_right.step(&mut evaluate_thunk, time).unwrap();
 
         if let Value::Residual(left) = &value_left
             && let Value::Residual(right) = &value_right
         {
-            value_left = evaluator.step(left, time).unwrap();
-            value_right = evaluator.step(right, time).unwrap();
+            value_left = Evaluator::new(&mut evaluate_thunk)
+                .with_past_time(&past_left)
+                .step(left, time)
+                .unwrap();
+            value_right = Evaluator::new(&mut evaluate_thunk)
+                .with_past_time(&past_right)
+                .step(right, time)
+                .unwrap();
         } else {
             break;
         }
     }
 
-    assert_values_eq(value_left, value_right, time, mode);
+    (value_left, value_right, time)
+}
+
+/// Whether the value holds at the end of the test, i.e. with residuals stopped.
+fn holds_at_end(value: Value<Thunk>, time: Time) -> bool {
+    match value {
+        Value::True => true,
+        Value::False(_) => false,
+        Value::Residual(residual) => {
+            matches!(stop_default(&residual, time), Some(StopDefault::True))
+        }
+    }
 }
 
 // Properties organically sourced from: https://en.wikipedia.org/wiki/Linear_temporal_logic
@@ -233,6 +369,50 @@ proptest! {
     }
 }
 
+// Repetition
+proptest! {
+    // Xⁿ φ ⇔ X(X(...(φ)))
+    #[test]
+    fn test_next_n_nested_next(count in 0..6u64, φ in syntax(), trace in trace()) {
+        let formula_left = Syntax::NextN(count, Box::new(φ.clone())).nnf();
+        let formula_right = (0..count)
+            .fold(φ.clone(), |syntax, _| Syntax::Next(Box::new(syntax)))
+            .nnf();
+        check_equivalence(formula_left, formula_right, trace, ValueEqMode::Strict);
+    }
+
+    // Xⁿ(¬φ) ⇔ ¬Xⁿ(φ)
+    #[test]
+    fn test_next_n_self_duality(count in 0..6u64, φ in syntax(), trace in trace()) {
+        let formula_left =
+            Syntax::NextN(count, Box::new(Syntax::Not(Box::new(φ.clone())))).nnf();
+        let formula_right =
+            Syntax::Not(Box::new(Syntax::NextN(count, Box::new(φ.clone())))).nnf();
+        check_equivalence(formula_left, formula_right, trace, ValueEqMode::Strict);
+    }
+}
+
+#[test]
+fn test_next_n_large_count() {
+    let syntax = Syntax::NextN(
+        u64::MAX,
+        Box::new(Syntax::Thunk(Thunk::Atomic(Variable::X))),
+    );
+    let formula = syntax.nnf();
+    assert_eq!(
+        formula,
+        Formula::NextN(
+            u64::MAX,
+            Box::new(Formula::Thunk {
+                function: Thunk::Atomic(Variable::X),
+                negated: false,
+            })
+        )
+    );
+    let trace = (0..100).map(|_| State { x: false, y: false }).collect();
+    check_equivalence(formula.clone(), formula, trace, ValueEqMode::Strict);
+}
+
 // Negation propagation
 proptest! {
     // X(¬φ) ⇔ ¬X(φ)
@@ -255,6 +435,56 @@ proptest! {
         check_equivalence(formula_left, formula_right, trace, ValueEqMode::Strict);
     }
 
+    // ¬(φ ⊕ ψ) ⇔ (φ ⇔ ψ)
+    #[test]
+    fn test_xor_iff_duality(φ in syntax(), ψ in syntax(), trace in trace()) {
+        let formula_left =
+            Syntax::Not(Box::new(Syntax::Xor(Box::new(φ.clone()), Box::new(ψ.clone())))).nnf();
+        let formula_right =
+            Syntax::Iff(Box::new(φ.clone()), Box::new(ψ.clone())).nnf();
+        check_equivalence(formula_left, formula_right, trace, ValueEqMode::Strict);
+    }
+
+    // ¬(φ ⇔ ψ) ⇔ (φ ⊕ ψ)
+    #[test]
+    fn test_iff_xor_duality(φ in syntax(), ψ in syntax(), trace in trace()) {
+        let formula_left =
+            Syntax::Not(Box::new(Syntax::Iff(Box::new(φ.clone()), Box::new(ψ.clone())))).nnf();
+        let formula_right =
+            Syntax::Xor(Box::new(φ.clone()), Box::new(ψ.clone())).nnf();
+        check_equivalence(formula_left, formula_right, trace, ValueEqMode::Strict);
+    }
+
+    // (φ ⊕ ψ) ⇔ (φ ∧ ¬ψ) ∨ (¬φ ∧ ψ)
+    //
+    // Only for formulas decided in a single state, as a `next` pending when the test ends is
+    // assumed to hold whether negated or not, so the negations here would disagree with `xor`.
+    #[test]
+    fn test_xor_definition(φ in state_syntax(true), ψ in state_syntax(true), trace in trace()) {
+        let not = |syntax: &Syntax<Thunk>| Box::new(Syntax::Not(Box::new(syntax.clone())));
+        let formula_left =
+            Syntax::Xor(Box::new(φ.clone()), Box::new(ψ.clone())).nnf();
+        let formula_right = Syntax::Or(
+            Box::new(Syntax::And(Box::new(φ.clone()), not(&ψ))),
+            Box::new(Syntax::And(not(&φ), Box::new(ψ.clone()))),
+        )
+        .nnf();
+        check_equivalence(formula_left, formula_right, trace, ValueEqMode::UpToViolations);
+    }
+
+    // (φ ⇔ ψ) ⇔ (φ ⇒ ψ) ∧ (ψ ⇒ φ)
+    #[test]
+    fn test_iff_definition(φ in syntax(), ψ in syntax(), trace in trace()) {
+        let formula_left =
+            Syntax::Iff(Box::new(φ.clone()), Box::new(ψ.clone())).nnf();
+        let formula_right = Syntax::And(
+            Box::new(Syntax::Implies(Box::new(φ.clone()), Box::new(ψ.clone()))),
+            Box::new(Syntax::Implies(Box::new(ψ.clone()), Box::new(φ.clone()))),
+        )
+        .nnf();
+        check_equivalence(formula_left, formula_right, trace, ValueEqMode::UpToViolations);
+    }
+
     // F(φ) ⇔ F(F(φ))
     #[test]
     fn test_eventually_idempotency(φ in syntax(), trace in trace()) {
@@ -275,3 +505,63 @@ proptest! {
         check_equivalence(formula_left, formula_right, trace, ValueEqMode::UpToViolations);
     }
 }
+
+// Past-time operators
+proptest! {
+    // ¬O(φ) ⇔ H(¬φ)
+    #[test]
+    fn test_once_historically_duality(φ in state_syntax(true), trace in trace()) {
+        let formula_left =
+            Syntax::Not(Box::new(Syntax::Once(Box::new(φ.clone())))).nnf();
+        let formula_right =
+            Syntax::Historically(Box::new(Syntax::Not(Box::new(φ.clone())))).nnf();
+        check_equivalence(formula_left, formula_right, trace, ValueEqMode::Strict);
+    }
+
+    // ¬H(φ) ⇔ O(¬φ)
+    #[test]
+    fn test_historically_once_duality(φ in state_syntax(true), trace in trace()) {
+        let formula_left =
+            Syntax::Not(Box::new(Syntax::Historically(Box::new(φ.clone())))).nnf();
+        let formula_right =
+            Syntax::Once(Box::new(Syntax::Not(Box::new(φ.clone())))).nnf();
+        check_equivalence(formula_left, formula_right, trace, ValueEqMode::Strict);
+    }
+
+    // O(φ) at the end of a trace ⇔ F(φ) at the start of the reversed trace
+    #[test]
+    fn test_once_eventually_reversed(φ in state_syntax(false), trace in trace()) {
+        let once = Syntax::Once(Box::new(φ.clone())).nnf();
+        let eventually = Syntax::Eventually(Box::new(φ.clone()), None).nnf();
+        prop_assert_eq!(
+            holds_at_last_state(&once, &trace),
+            holds_on_reversed(&eventually, trace)
+        );
+    }
+
+    // H(φ) at the end of a trace ⇔ G(φ) at the start of the reversed trace
+    #[test]
+    fn test_historically_always_reversed(φ in state_syntax(false), trace in trace()) {
+        let historically = Syntax::Historically(Box::new(φ.clone())).nnf();
+        let always = Syntax::Always(Box::new(φ.clone()), None).nnf();
+        prop_assert_eq!(
+            holds_at_last_state(&historically, &trace),
+            holds_on_reversed(&always, trace)
+        );
+    }
+}
+
+/// Whether a past-time formula holds in the last state of the trace.
+fn holds_at_last_state(formula: &Formula<Thunk>, trace: &[State]) -> bool {
+    // Deferred with `next` to the last state, so that the earlier states are its history.
+    let last = trace.len() as u64 - 1;
+    let formula = Formula::NextN(last, Box::new(formula.clone()));
+    let (value, _, time) = evaluate_trace(&formula, &formula, trace);
+    holds_at_end(value, time)
+}
+
+fn holds_on_reversed(formula: &Formula<Thunk>, mut trace: Vec<State>) -> bool {
+    trace.reverse();
+    let (value, _, time) = evaluate_trace(formula, formula, &trace);
+    holds_at_end(value, time)
+}
@@ -6,6 +6,7 @@ pub mod resolver;
 pub mod result;
 pub mod stop;
 pub mod syntax;
+pub mod tester;
 pub mod verifier;
 pub mod worker;
 
@@ -119,6 +119,21 @@ export function integers(): IntegerGenerator {
   return new IntegerGenerator();
 }
 
+declare const __bombadil_dictionary: Record<string, string[]> | undefined;
+
+/**
+ * Picks from the strings given for `key` (e.g. an input type like "email") in
+ * the dictionary passed with `--dictionary`, or returns null when there are
+ * none.
+ */
+export function dictionary(key: string): Generator<string> | null {
+  const values =
+    typeof __bombadil_dictionary === "undefined"
+      ? undefined
+      : __bombadil_dictionary[key];
+  return values && values.length > 0 ? new From(values) : null;
+}
+
 export function keycodes(): Generator<number> {
   return from([8, 9, 13, 27]);
 }
@@ -1,4 +1,4 @@
-use std::time::UNIX_EPOCH;
+use std::time::{Duration, UNIX_EPOCH};
 
 use serde::Serialize;
 
@@ -7,84 +7,339 @@ use crate::specification::{
     ltl::{EventuallyViolation, Formula, Time, Violation},
 };
 
+/// How to render the times in a violation.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TimeFormat {
+    /// Milliseconds since the Unix epoch, e.g. for machine-readable logs.
+    Absolute,
+    /// Milliseconds since the given start of the run, e.g. "+1234ms".
+    Relative { start: Time },
+}
+
+/// How to render a violation.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RenderOptions {
+    pub time_format: TimeFormat,
+    /// Lays out nested violations as an indented tree, rather than separated by blank lines.
+    pub indent: bool,
+    /// Highlights failed conditions and dims connectives using ANSI escape codes.
+    pub color: bool,
+}
+
+impl RenderOptions {
+    pub const PLAIN: RenderOptions = RenderOptions {
+        time_format: TimeFormat::Absolute,
+        indent: false,
+        color: false,
+    };
+
+    /// Options for humans reading the output: indented, and colored if the output is a terminal
+    /// and `NO_COLOR` isn't set.
+    pub fn human(time_format: TimeFormat, is_terminal: bool) -> Self {
+        let no_color =
+            std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        RenderOptions {
+            time_format,
+            indent: true,
+            color: is_terminal && !no_color,
+        }
+    }
+}
+
 pub fn render_violation(violation: &Violation<PrettyFunction>) -> String {
-    format!("{}", RenderedViolation(violation))
+    render_violation_with(violation, RenderOptions::PLAIN)
+}
+
+pub fn render_violation_with(
+    violation: &Violation<PrettyFunction>,
+    options: RenderOptions,
+) -> String {
+    format!("{}", RenderedViolation(violation, options))
+}
+
+/// Renders a property's formula, e.g. to list the properties of a specification.
+pub fn render_formula(formula: &Formula<PrettyFunction>) -> String {
+    RenderedFormula(formula).to_string()
+}
+
+/// A one-line summary of when a property was violated, to print before the rendered violation.
+pub fn render_violation_summary(
+    property: &str,
+    states: usize,
+    elapsed: Duration,
+) -> String {
+    format!(
+        "property `{}` violated after {} state{}, {:.1}s",
+        property,
+        states,
+        if states == 1 { "" } else { "s" },
+        elapsed.as_secs_f64()
+    )
 }
 
-struct RenderedViolation<'a>(&'a Violation<PrettyFunction>);
+const ANSI_RED: &str = "\x1b[31m";
+const ANSI_DIM: &str = "\x1b[2m";
+const ANSI_RESET: &str = "\x1b[0m";
+
+struct RenderedViolation<'a>(&'a Violation<PrettyFunction>, RenderOptions);
+
+impl<'a> RenderedViolation<'a> {
+    fn time(&self, time: &Time) -> String {
+        match self.1.time_format {
+            TimeFormat::Absolute => format!("{}ms", time_to_ms(time)),
+            TimeFormat::Relative { start } => {
+                match time.duration_since(start) {
+                    Ok(elapsed) => format!("+{}ms", elapsed.as_millis()),
+                    Err(error) => {
+                        format!("-{}ms", error.duration().as_millis())
+                    }
+                }
+            }
+        }
+    }
+
+    fn styled(&self, style: &str, text: &str) -> String {
+        if self.1.color {
+            format!("{}{}{}", style, text, ANSI_RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn keyword(&self, text: &str) -> String {
+        self.styled(ANSI_DIM, text)
+    }
+
+    /// Renders a nested violation, indented one level deeper when laying out as a tree.
+    fn nested(&self, violation: &Violation<PrettyFunction>) -> String {
+        let rendered = RenderedViolation(violation, self.1).to_string();
+        if self.1.indent {
+            indent(&rendered)
+        } else {
+            rendered
+        }
+    }
+
+    fn nested_formula(&self, formula: &Formula<PrettyFunction>) -> String {
+        let rendered = RenderedFormula(formula).to_string();
+        if self.1.indent {
+            indent(&rendered)
+        } else {
+            rendered
+        }
+    }
+
+    /// Separates nested parts, with blank lines when not laying out as a tree.
+    fn separator(&self) -> &'static str {
+        if self.1.indent { "\n" } else { "\n\n" }
+    }
+}
+
+fn indent(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            if line.is_empty() {
+                String::new()
+            } else {
+                format!("  {}", line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
 
 impl<'a> std::fmt::Display for RenderedViolation<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let separator = self.separator();
         match self.0 {
             Violation::False { condition, .. } => {
-                write!(f, "!({})", condition)?;
+                write!(
+                    f,
+                    "{}",
+                    self.styled(ANSI_RED, &format!("!({})", condition))
+                )?;
             }
-            Violation::Eventually { subformula, reason } => {
-                match reason {
+            Violation::Eventually {
+                subformula,
+                reason,
+                evidence,
+            } => {
+                let reason = match reason {
                     EventuallyViolation::TimedOut(time) => {
-                        write!(f, "timed out at {}ms: ", time_to_ms(time))?
+                        format!("timed out at {}:", self.time(time))
                     }
                     EventuallyViolation::TestEnded => {
-                        write!(f, "failed at test end: ")?
+                        "failed at test end:".to_string()
                     }
+                };
+                write!(
+                    f,
+                    "{} {}",
+                    self.keyword(&reason),
+                    self.styled(
+                        ANSI_RED,
+                        &RenderedFormula((*subformula).as_ref()).to_string()
+                    )
+                )?;
+                if let Some(evidence) = evidence {
+                    write!(
+                        f,
+                        "{}{}{}{}",
+                        separator,
+                        self.keyword(&format!(
+                            "last checked at {}, still false because",
+                            self.time(&evidence.time)
+                        )),
+                        separator,
+                        self.nested(&evidence.violation),
+                    )?;
                 }
-                write!(f, "{}", RenderedFormula((*subformula).as_ref()))?;
+            }
+            Violation::And { left, right } if self.1.indent => {
+                write!(
+                    f,
+                    "{}\n{}\n{}",
+                    self.keyword("all of:"),
+                    self.nested(left),
+                    self.nested(right),
+                )?;
             }
             Violation::And { left, right } => {
                 write!(
                     f,
-                    "{}\n\nand\n\n{}",
-                    RenderedViolation(left),
-                    RenderedViolation(right),
+                    "{}{}{}{}{}",
+                    self.nested(left),
+                    separator,
+                    self.keyword("and"),
+                    separator,
+                    self.nested(right),
+                )?;
+            }
+            Violation::Or { left, right } if self.1.indent => {
+                write!(
+                    f,
+                    "{}\n{}\n{}",
+                    self.keyword("any of:"),
+                    self.nested(left),
+                    self.nested(right),
                 )?;
             }
             Violation::Or { left, right } => {
                 write!(
                     f,
-                    "{} or {}",
-                    RenderedViolation(left),
-                    RenderedViolation(right),
+                    "{} {} {}",
+                    self.nested(left),
+                    self.keyword("or"),
+                    self.nested(right),
                 )?;
             }
             Violation::Implies { left, right } => {
                 write!(
                     f,
-                    "{} since {}",
-                    RenderedViolation(right),
+                    "{} {} {}",
+                    RenderedViolation(right, self.1),
+                    self.keyword("since"),
                     RenderedFormula(left),
                 )?;
             }
+            Violation::XorBoth { left, right } => {
+                write!(
+                    f,
+                    "{}{}{}{}{}{}{}",
+                    self.keyword(
+                        "exactly one of these should hold, but both did:"
+                    ),
+                    separator,
+                    self.nested_formula(left),
+                    separator,
+                    self.keyword("and"),
+                    separator,
+                    self.nested_formula(right),
+                )?;
+            }
+            Violation::XorNeither { left, right } => {
+                write!(
+                    f,
+                    "{}{}{}{}{}{}{}",
+                    self.keyword(
+                        "exactly one of these should hold, but neither did:"
+                    ),
+                    separator,
+                    self.nested(left),
+                    separator,
+                    self.keyword("and"),
+                    separator,
+                    self.nested(right),
+                )?;
+            }
+            Violation::Iff { held, violation } => {
+                write!(
+                    f,
+                    "{} {} {}",
+                    RenderedViolation(violation, self.1),
+                    self.keyword("while"),
+                    RenderedFormula(held),
+                )?;
+            }
             Violation::Always {
                 violation,
                 subformula,
                 start,
-                end: None,
+                end,
                 time,
             } => {
+                let period = match end {
+                    None => format!("as of {}", self.time(start)),
+                    Some(end) => format!(
+                        "as of {} and until {}",
+                        self.time(start),
+                        self.time(end)
+                    ),
+                };
                 write!(
                     f,
-                    "as of {}ms, it should always be the case that\n\n{}\n\nbut at {}ms\n\n{}",
-                    time_to_ms(start),
-                    RenderedFormula((*subformula).as_ref()),
-                    time_to_ms(time),
-                    RenderedViolation(violation),
+                    "{}{}{}{}{}{}{}",
+                    self.keyword(&format!(
+                        "{}, it should always be the case that",
+                        period
+                    )),
+                    separator,
+                    self.nested_formula(subformula),
+                    separator,
+                    self.keyword(&format!("but at {}", self.time(time))),
+                    separator,
+                    self.nested(violation),
                 )?;
             }
-            Violation::Always {
+            Violation::Once { subformula, time } => {
+                write!(
+                    f,
+                    "{} {}",
+                    self.keyword(&format!(
+                        "never held up to {}:",
+                        self.time(time)
+                    )),
+                    self.styled(
+                        ANSI_RED,
+                        &RenderedFormula(subformula).to_string()
+                    )
+                )?;
+            }
+            Violation::Historically {
                 violation,
                 subformula,
-                start,
-                end: Some(end),
                 time,
             } => {
                 write!(
                     f,
-                    "as of {}ms and until {}ms, it should alwaays be the case that\n\n{}\n\nbut at {}ms\n\n{}",
-                    time_to_ms(start),
-                    time_to_ms(end),
-                    RenderedFormula((*subformula).as_ref()),
-                    time_to_ms(time),
-                    RenderedViolation(violation),
+                    "{}{}{}{}{}{}{}",
+                    self.keyword("it should always have been the case that"),
+                    separator,
+                    self.nested_formula(subformula),
+                    separator,
+                    self.keyword(&format!("but at {}", self.time(time))),
+                    separator,
+                    self.nested(violation),
                 )?;
             }
         };
@@ -129,9 +384,28 @@ impl<'a> std::fmt::Display for RenderedFormula<'a> {
                     RenderedFormula(right)
                 )
             }
+            Formula::Xor(left, right) => {
+                write!(
+                    f,
+                    "{}.xor({})",
+                    RenderedFormula(left),
+                    RenderedFormula(right)
+                )
+            }
+            Formula::Iff(left, right) => {
+                write!(
+                    f,
+                    "{}.iff({})",
+                    RenderedFormula(left),
+                    RenderedFormula(right)
+                )
+            }
             Formula::Next(formula) => {
                 write!(f, "next({})", RenderedFormula(formula))
             }
+            Formula::NextN(count, formula) => {
+                write!(f, "next^{}({})", count, RenderedFormula(formula))
+            }
             Formula::Always(formula, None) => {
                 write!(f, "always({})", RenderedFormula(formula))
             }
@@ -154,6 +428,12 @@ impl<'a> std::fmt::Display for RenderedFormula<'a> {
                     bound.as_millis()
                 )
             }
+            Formula::Once(formula) => {
+                write!(f, "once({})", RenderedFormula(formula))
+            }
+            Formula::Historically(formula) => {
+                write!(f, "historically({})", RenderedFormula(formula))
+            }
         }
     }
 }
@@ -184,3 +464,172 @@ impl Violation<RuntimeFunction> {
         self.map_function(|f| PrettyFunction(f.pretty.clone()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_violation_relative_times() {
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let violation = Violation::Eventually {
+            subformula: Box::new(Formula::Pure {
+                value: false,
+                pretty: "loaded".to_string(),
+            }),
+            reason: EventuallyViolation::TimedOut(
+                start + Duration::from_millis(1234),
+            ),
+            evidence: None,
+        };
+        assert_eq!(
+            render_violation_with(
+                &violation,
+                RenderOptions {
+                    time_format: TimeFormat::Relative { start },
+                    indent: false,
+                    color: false,
+                }
+            ),
+            "timed out at +1234ms: loaded"
+        );
+        assert_eq!(
+            render_violation(&violation),
+            "timed out at 1700000001234ms: loaded"
+        );
+    }
+
+    #[test]
+    fn test_render_violation_summary() {
+        assert_eq!(
+            render_violation_summary(
+                "no_errors",
+                37,
+                Duration::from_millis(4210)
+            ),
+            "property `no_errors` violated after 37 states, 4.2s"
+        );
+    }
+
+    #[test]
+    fn test_render_past_time_violations() {
+        let time = UNIX_EPOCH;
+        let subformula = Box::new(Formula::Pure {
+            value: false,
+            pretty: "loggedIn".to_string(),
+        });
+        assert_eq!(
+            render_violation(&Violation::Once {
+                subformula: subformula.clone(),
+                time: time + Duration::from_millis(5),
+            }),
+            "never held up to 5ms: loggedIn"
+        );
+        assert_eq!(
+            render_violation(&Violation::Historically {
+                violation: Box::new(Violation::False {
+                    time,
+                    condition: "loggedIn".to_string(),
+                }),
+                subformula,
+                time,
+            }),
+            "it should always have been the case that\n\nloggedIn\n\nbut at 0ms\n\n!(loggedIn)"
+        );
+    }
+
+    #[test]
+    fn test_render_xor_iff_violations() {
+        let time = UNIX_EPOCH;
+        let formula = |pretty: &str| Formula::Pure {
+            value: false,
+            pretty: pretty.to_string(),
+        };
+        let condition = |condition: &str| Violation::False {
+            time,
+            condition: condition.to_string(),
+        };
+        assert_eq!(
+            render_violation(&Violation::XorBoth {
+                left: formula("a"),
+                right: formula("b"),
+            }),
+            "exactly one of these should hold, but both did:\n\na\n\nand\n\nb"
+        );
+        assert_eq!(
+            render_violation(&Violation::XorNeither {
+                left: Box::new(condition("a")),
+                right: Box::new(condition("b")),
+            }),
+            "exactly one of these should hold, but neither did:\n\n!(a)\n\nand\n\n!(b)"
+        );
+        assert_eq!(
+            render_violation(&Violation::Iff {
+                held: formula("a"),
+                violation: Box::new(condition("b")),
+            }),
+            "!(b) while a"
+        );
+        assert_eq!(
+            render_formula(&Formula::Iff(
+                Box::new(formula("a")),
+                Box::new(Formula::Xor(
+                    Box::new(formula("b")),
+                    Box::new(formula("c"))
+                ))
+            )),
+            "a.iff(b.xor(c))"
+        );
+    }
+
+    #[test]
+    fn test_render_violation_indented() {
+        let time = UNIX_EPOCH;
+        let condition = |condition: &str| Violation::False {
+            time,
+            condition: condition.to_string(),
+        };
+        let violation = Violation::Always {
+            violation: Box::new(Violation::And {
+                left: Box::new(condition("a")),
+                right: Box::new(Violation::And {
+                    left: Box::new(condition("b")),
+                    right: Box::new(condition("c")),
+                }),
+            }),
+            subformula: Box::new(Formula::Pure {
+                value: false,
+                pretty: "a && b && c".to_string(),
+            }),
+            start: time,
+            end: None,
+            time: time + Duration::from_millis(5),
+        };
+        let options = RenderOptions {
+            time_format: TimeFormat::Relative { start: time },
+            indent: true,
+            color: false,
+        };
+        assert_eq!(
+            render_violation_with(&violation, options),
+            "as of +0ms, it should always be the case that
+  a && b && c
+but at +5ms
+  all of:
+    !(a)
+    all of:
+      !(b)
+      !(c)"
+        );
+        assert_eq!(
+            render_violation_with(
+                &condition("a"),
+                RenderOptions {
+                    color: true,
+                    ..options
+                }
+            ),
+            "\x1b[31m!(a)\x1b[0m"
+        );
+    }
+}
@@ -34,6 +34,24 @@ pub fn stop_default<Function: Clone>(
             stop_default(right, time)
                 .map(|s2| stop_implies_default(left_formula, &s1, &s2))
         }),
+        Xor {
+            left_formula,
+            right_formula,
+            left,
+            right,
+        } => stop_default(left, time).and_then(|s1| {
+            stop_default(right, time)
+                .map(|s2| stop_xor_default(left_formula, right_formula, s1, s2))
+        }),
+        Iff {
+            left_formula,
+            right_formula,
+            left,
+            right,
+        } => stop_default(left, time).and_then(|s1| {
+            stop_default(right, time)
+                .map(|s2| stop_iff_default(left_formula, right_formula, s1, s2))
+        }),
         AndAlways {
             subformula,
             start,
@@ -102,6 +120,46 @@ fn stop_implies_default<Function: Clone>(
     }
 }
 
+fn stop_xor_default<Function: Clone>(
+    left_formula: &Formula<Function>,
+    right_formula: &Formula<Function>,
+    left: StopDefault<Function>,
+    right: StopDefault<Function>,
+) -> StopDefault<Function> {
+    use StopDefault::*;
+    match (left, right) {
+        (True, False(_)) | (False(_), True) => True,
+        (True, True) => False(Violation::XorBoth {
+            left: left_formula.clone(),
+            right: right_formula.clone(),
+        }),
+        (False(left), False(right)) => False(Violation::XorNeither {
+            left: Box::new(left),
+            right: Box::new(right),
+        }),
+    }
+}
+
+fn stop_iff_default<Function: Clone>(
+    left_formula: &Formula<Function>,
+    right_formula: &Formula<Function>,
+    left: StopDefault<Function>,
+    right: StopDefault<Function>,
+) -> StopDefault<Function> {
+    use StopDefault::*;
+    match (left, right) {
+        (True, True) | (False(_), False(_)) => True,
+        (True, False(violation)) => False(Violation::Iff {
+            held: left_formula.clone(),
+            violation: Box::new(violation),
+        }),
+        (False(violation), True) => False(Violation::Iff {
+            held: right_formula.clone(),
+            violation: Box::new(violation),
+        }),
+    }
+}
+
 fn stop_and_always_default<Function: Clone>(
     subformula: &Formula<Function>,
     start: Time,
@@ -5,15 +5,24 @@ use crate::specification::ltl::Formula;
 /// A formula in its syntactic form, "parsed" from JavaScript runtime objects.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Syntax<Function> {
-    Pure { value: bool, pretty: String },
+    Pure {
+        value: bool,
+        pretty: String,
+    },
     Thunk(Function),
     Not(Box<Syntax<Function>>),
     And(Box<Syntax<Function>>, Box<Syntax<Function>>),
     Or(Box<Syntax<Function>>, Box<Syntax<Function>>),
     Implies(Box<Syntax<Function>>, Box<Syntax<Function>>),
+    Xor(Box<Syntax<Function>>, Box<Syntax<Function>>),
+    Iff(Box<Syntax<Function>>, Box<Syntax<Function>>),
     Next(Box<Syntax<Function>>),
+    /// `Next` repeated a number of times, without nesting.
+    NextN(u64, Box<Syntax<Function>>),
     Always(Box<Syntax<Function>>, Option<Duration>),
     Eventually(Box<Syntax<Function>>, Option<Duration>),
+    Once(Box<Syntax<Function>>),
+    Historically(Box<Syntax<Function>>),
 }
 
 impl<Function: Clone> Syntax<Function> {
@@ -78,7 +87,33 @@ impl<Function: Clone> Syntax<Function> {
                         )
                     }
                 }
+                Syntax::Xor(left, right) => {
+                    let left = Box::new(go(left, false));
+                    let right = Box::new(go(right, false));
+                    if negated {
+                        //   ¬(l ⊕ r)
+                        // ⇔ l ⇔ r
+                        Formula::Iff(left, right)
+                    } else {
+                        Formula::Xor(left, right)
+                    }
+                }
+                Syntax::Iff(left, right) => {
+                    let left = Box::new(go(left, false));
+                    let right = Box::new(go(right, false));
+                    if negated {
+                        //   ¬(l ⇔ r)
+                        // ⇔ l ⊕ r
+                        Formula::Xor(left, right)
+                    } else {
+                        Formula::Iff(left, right)
+                    }
+                }
                 Syntax::Next(sub) => Formula::Next(Box::new(go(sub, negated))),
+                Syntax::NextN(0, sub) => go(sub, negated),
+                Syntax::NextN(count, sub) => {
+                    Formula::NextN(*count, Box::new(go(sub, negated)))
+                }
                 Syntax::Always(sub, bound) => {
                     if negated {
                         Formula::Eventually(Box::new(go(sub, negated)), *bound)
@@ -93,6 +128,20 @@ impl<Function: Clone> Syntax<Function> {
                         Formula::Eventually(Box::new(go(sub, negated)), *bound)
                     }
                 }
+                Syntax::Once(sub) => {
+                    if negated {
+                        Formula::Historically(Box::new(go(sub, negated)))
+                    } else {
+                        Formula::Once(Box::new(go(sub, negated)))
+                    }
+                }
+                Syntax::Historically(sub) => {
+                    if negated {
+                        Formula::Once(Box::new(go(sub, negated)))
+                    } else {
+                        Formula::Historically(Box::new(go(sub, negated)))
+                    }
+                }
             }
         }
         go(self, false)
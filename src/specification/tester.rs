@@ -0,0 +1,234 @@
+//! Checking a specification's properties against hand-written states, without a browser, so that
+//! properties can be unit tested.
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+use serde_json as json;
+
+use crate::specification::ltl;
+use crate::specification::result::{Result, SpecificationError};
+use crate::specification::verifier::{
+    Snapshot, Specification, Verifier, VerifierOptions,
+};
+use crate::specification::worker::PropertyValue;
+
+/// A state to check properties in, given by the values of the specification's extractors.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TestState {
+    /// When the state was observed. In JSON, as milliseconds since the Unix epoch, where any
+    /// fixed start like 0 works as only the differences between states matter.
+    #[serde(deserialize_with = "deserialize_time")]
+    pub timestamp: ltl::Time,
+    /// The value of each extractor, by name. Extractors assigned to a variable are named after
+    /// it, others can be named with `named`.
+    pub extracted: HashMap<String, json::Value>,
+}
+
+impl TestState {
+    pub fn new(
+        timestamp: ltl::Time,
+        extracted: impl IntoIterator<Item = (String, json::Value)>,
+    ) -> Self {
+        TestState {
+            timestamp,
+            extracted: extracted.into_iter().collect(),
+        }
+    }
+}
+
+fn deserialize_time<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<ltl::Time, D::Error> {
+    Ok(UNIX_EPOCH + Duration::from_millis(u64::deserialize(deserializer)?))
+}
+
+/// Steps a specification's properties through given states, like a test does with the states
+/// of the browser. Every extractor must have a name and a value in every state.
+pub struct SpecTester {
+    verifier: Verifier,
+    extractor_names: Vec<String>,
+}
+
+impl SpecTester {
+    pub async fn new(
+        specification: &Specification,
+        options: VerifierOptions,
+    ) -> Result<Self> {
+        Self::from_bundle(&specification.bundle().await?, options)
+    }
+
+    pub fn from_bundle(
+        bundle_code: &str,
+        options: VerifierOptions,
+    ) -> Result<Self> {
+        let mut verifier = Verifier::new(bundle_code, options)?;
+        let extractor_names = verifier
+            .extractor_names()?
+            .into_iter()
+            .zip(verifier.extractors()?)
+            .map(|(name, pretty)| {
+                name.ok_or(SpecificationError::OtherError(format!(
+                    "extractor {} has no name, give it one with `named` to provide its values",
+                    pretty
+                )))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(SpecTester {
+            verifier,
+            extractor_names,
+        })
+    }
+
+    /// Steps all properties in the state, returning their values sorted by name. States must be
+    /// given in order of their timestamps.
+    pub fn step(
+        &mut self,
+        state: &TestState,
+    ) -> Result<Vec<(String, PropertyValue)>> {
+        if let Some(unknown) = state
+            .extracted
+            .keys()
+            .find(|name| !self.extractor_names.contains(name))
+        {
+            return Err(SpecificationError::OtherError(format!(
+                "state has a value for unknown extractor {}",
+                unknown
+            )));
+        }
+        let snapshots = self
+            .extractor_names
+            .iter()
+            .map(|name| {
+                let value = state.extracted.get(name).ok_or(
+                    SpecificationError::OtherError(format!(
+                        "state has no value for extractor {}",
+                        name
+                    )),
+                )?;
+                Ok(Snapshot {
+                    name: Some(name.clone()),
+                    value: value.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let result = self
+            .verifier
+            .step::<json::Value>(snapshots, state.timestamp)?;
+        let mut properties: Vec<(String, PropertyValue)> = result
+            .properties
+            .iter()
+            .map(|(name, value)| (name.clone(), PropertyValue::from(value)))
+            .collect();
+        properties.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(properties)
+    }
+
+    /// Steps through all the states, returning the property values in each.
+    pub fn run(
+        &mut self,
+        states: &[TestState],
+    ) -> Result<Vec<Vec<(String, PropertyValue)>>> {
+        states.iter().map(|state| self.step(state)).collect()
+    }
+}
+
+/// A timestamp some milliseconds after the Unix epoch, for building [`TestState`]s.
+pub fn time_at(millis: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    fn tester(specification: &str) -> Result<SpecTester> {
+        let mut specification_file = NamedTempFile::with_suffix(".ts").unwrap();
+        specification_file
+            .write_all(specification.as_bytes())
+            .unwrap();
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(SpecTester::new(
+            &Specification {
+                module_specifier: specification_file
+                    .path()
+                    .display()
+                    .to_string(),
+            },
+            VerifierOptions::default(),
+        ))
+    }
+
+    const SPECIFICATION: &str = r#"
+        import { actions, always, extract } from "@antithesishq/bombadil";
+        export const _actions = actions(() => []);
+
+        const count = extract((state) => state.count);
+
+        export const small_count = always(() => count.current < 3);
+        "#;
+
+    fn verdict(values: &[(String, PropertyValue)]) -> &'static str {
+        match &values[0].1 {
+            PropertyValue::True => "true",
+            PropertyValue::False(_) => "false",
+            PropertyValue::Residual => "pending",
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        let states: Vec<TestState> = json::from_value(json::json!([
+            { "timestamp": 0, "extracted": { "count": 1 } },
+            { "timestamp": 10, "extracted": { "count": 2 } },
+            { "timestamp": 20, "extracted": { "count": 3 } },
+        ]))
+        .unwrap();
+        let values = tester(SPECIFICATION).unwrap().run(&states).unwrap();
+        assert_eq!(
+            values
+                .iter()
+                .map(|values| verdict(values))
+                .collect::<Vec<_>>(),
+            vec!["pending", "pending", "false"]
+        );
+        assert_eq!(values[2][0].0, "small_count");
+    }
+
+    #[test]
+    fn test_missing_and_unknown_extractors() {
+        let mut tester = tester(SPECIFICATION).unwrap();
+        assert!(tester.step(&TestState::new(time_at(0), [])).is_err());
+        assert!(
+            tester
+                .step(&TestState::new(
+                    time_at(0),
+                    [
+                        ("count".to_string(), json::json!(1)),
+                        ("total".to_string(), json::json!(1)),
+                    ],
+                ))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_unnamed_extractor() {
+        let result = tester(
+            r#"
+            import { actions, always, extract } from "@antithesishq/bombadil";
+            export const _actions = actions(() => []);
+
+            const cells = [extract((state) => state.count)];
+
+            export const small_count = always(() => cells[0].current < 3);
+            "#,
+        );
+        assert!(result.is_err());
+    }
+}
@@ -1,7 +1,12 @@
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 
+use crate::specification::bundler::bundle;
 use crate::specification::js::{BombadilExports, Extractors, RuntimeFunction};
-use crate::specification::ltl::{Evaluator, Formula, Residual, Violation};
+use crate::specification::ltl::{
+    Evaluator, Formula, PastTime, Residual, Violation,
+};
+use crate::specification::render::PrettyFunction;
 use crate::specification::result::Result;
 use crate::specification::syntax::Syntax;
 use crate::specification::{ltl, result::SpecificationError};
@@ -13,7 +18,7 @@ use boa_engine::{
     object::builtins::{JsArray, JsUint8Array},
     property::PropertyKey,
 };
-use boa_engine::{JsError, JsObject, JsValue};
+use boa_engine::{JsError, JsNativeError, JsObject, JsValue};
 use serde::{Deserialize, Serialize};
 use serde_json as json;
 
@@ -24,6 +29,7 @@ pub struct StepResult<A> {
 }
 
 pub struct Verifier {
+    options: VerifierOptions,
     context: Context,
     bombadil_exports: BombadilExports,
     properties: HashMap<String, Property>,
@@ -37,6 +43,73 @@ pub struct Snapshot {
     pub value: json::Value,
 }
 
+#[derive(Clone, Debug, Default)]
+pub struct VerifierOptions {
+    /// See [`Evaluator::with_eventually_evidence`].
+    pub eventually_evidence: bool,
+    /// Which of the specification's properties to check.
+    pub property_filter: PropertyFilter,
+    /// Fail on exports that are neither properties nor action generators, instead of ignoring
+    /// them as helpers.
+    pub strict_exports: bool,
+    /// Strings to type, by key, e.g. an input type, for action generators to pick from with
+    /// `dictionary`.
+    pub dictionary: HashMap<String, Vec<String>>,
+}
+
+/// Restricts the checked properties to those named, and those with any of the tags given with
+/// `Formula.tag`. All properties are checked when both are empty.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PropertyFilter {
+    pub properties: Vec<String>,
+    pub tags: Vec<String>,
+}
+
+impl PropertyFilter {
+    pub fn is_empty(&self) -> bool {
+        self.properties.is_empty() && self.tags.is_empty()
+    }
+
+    /// Keeps the properties selected by the filter, given their tags. Names and tags that don't
+    /// occur in the specification are an error, as they're most likely typos.
+    fn apply(
+        &self,
+        properties: &mut HashMap<String, Property>,
+        tags: &HashMap<String, Vec<String>>,
+    ) -> Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        for name in &self.properties {
+            if !properties.contains_key(name) {
+                return Err(SpecificationError::OtherError(format!(
+                    "unknown property: {}",
+                    name
+                )));
+            }
+        }
+        for tag in &self.tags {
+            if !tags
+                .values()
+                .any(|property_tags| property_tags.contains(tag))
+            {
+                return Err(SpecificationError::OtherError(format!(
+                    "no property is tagged {}",
+                    tag
+                )));
+            }
+        }
+        properties.retain(|name, _| {
+            self.properties.contains(name)
+                || tags.get(name).is_some_and(|property_tags| {
+                    property_tags.iter().any(|tag| self.tags.contains(tag))
+                })
+        });
+        Ok(())
+    }
+}
+
 const RANDOM_BYTES_COUNT_MAX: usize = 4096;
 
 #[derive(Clone)]
@@ -44,8 +117,52 @@ pub struct Specification {
     pub module_specifier: String,
 }
 
+/// What a specification defines, as found by [`Specification::introspect`].
+#[derive(Debug, Clone)]
+pub struct Introspection {
+    /// Property names, sorted.
+    pub properties: Vec<String>,
+    /// The formula of each property, in the order of `properties`.
+    pub formulas: Vec<Formula<PrettyFunction>>,
+    /// The source of each extractor's function, in definition order.
+    pub extractors: Vec<String>,
+}
+
+impl Specification {
+    /// Lists the properties and extractors of the specification. This bundles the specification
+    /// and evaluates it in a JavaScript context like [`Verifier::new`] does, as that's what
+    /// defines them, but doesn't step anything.
+    pub async fn introspect(&self) -> Result<Introspection> {
+        let bundle_code = self.bundle().await?;
+        let mut verifier =
+            Verifier::new(&bundle_code, VerifierOptions::default())?;
+        let mut properties = verifier.properties();
+        properties.sort();
+        let formulas = properties
+            .iter()
+            .map(|name| {
+                verifier.properties[name].formula.with_pretty_functions()
+            })
+            .collect();
+        Ok(Introspection {
+            properties,
+            formulas,
+            extractors: verifier.extractors()?,
+        })
+    }
+
+    pub(crate) async fn bundle(&self) -> Result<String> {
+        bundle(".", &self.module_specifier).await.map_err(|error| {
+            SpecificationError::OtherError(format!(
+                "Failed to bundle specification: {}",
+                error
+            ))
+        })
+    }
+}
+
 impl Verifier {
-    pub fn new(bundle_code: &str) -> Result<Self> {
+    pub fn new(bundle_code: &str, options: VerifierOptions) -> Result<Self> {
         let mut context = ContextBuilder::default()
             .build()
             .map_err(|error| SpecificationError::JS(error.to_string()))?;
@@ -119,6 +236,21 @@ impl Verifier {
                 ))
             })?;
 
+        let dictionary =
+            JsValue::from_json(&json::json!(options.dictionary), &mut context)?;
+        context
+            .register_global_property(
+                js_string!("__bombadil_dictionary"),
+                dictionary,
+                boa_engine::property::Attribute::all(),
+            )
+            .map_err(|e| {
+                SpecificationError::JS(format!(
+                    "Failed to register dictionary: {}",
+                    e
+                ))
+            })?;
+
         let specification_exports_value =
             context.eval(Source::from_bytes(bundle_code))?;
         let specification_exports_obj = specification_exports_value
@@ -153,23 +285,31 @@ impl Verifier {
             specification_exports_obj.own_property_keys(&mut context)?;
 
         let mut properties: HashMap<String, Property> = HashMap::new();
+        let mut property_tags: HashMap<String, Vec<String>> = HashMap::new();
         let mut action_generators: HashMap<String, ActionGenerator> =
             HashMap::new();
         for key in specification_export_keys {
             let value =
                 specification_exports_obj.get(key.clone(), &mut context)?;
             if value.instance_of(&bombadil_exports.formula, &mut context)? {
-                let syntax = Syntax::from_value(
-                    &value,
-                    &bombadil_exports,
-                    &mut context,
-                )?;
+                let syntax =
+                    Syntax::from_value(&value, &bombadil_exports, &mut context)
+                        .map_err(in_property(&key.to_string()))?;
                 let formula = syntax.nnf();
+                let past_time = PastTime::new(&formula)
+                    .map_err(in_property(&key.to_string()))?;
+                property_tags.insert(
+                    key.to_string(),
+                    formula_tags(&value, &mut context)?,
+                );
                 properties.insert(
                     key.to_string(),
                     Property {
                         name: key.to_string(),
+                        focus: formula_focus(&value, &mut context)?,
+                        formula: formula.clone(),
                         state: PropertyState::Initial(formula),
+                        past_time,
                     },
                 );
             } else if value
@@ -207,16 +347,26 @@ impl Verifier {
             } else if IGNORED_STRING_EXPORTS.contains(&key.to_string().as_str())
             {
                 continue;
-            } else {
+            } else if options.strict_exports {
                 return Err(SpecificationError::OtherError(format!(
                     "export {:?} is of unknown type ({}): {}",
                     key.to_string(),
                     value.type_of(),
                     value.display()
                 )));
+            } else {
+                log::debug!(
+                    "ignoring export {:?} as it's neither a property nor an action generator ({})",
+                    key.to_string(),
+                    value.type_of()
+                );
             }
         }
 
+        options
+            .property_filter
+            .apply(&mut properties, &property_tags)?;
+
         if action_generators.is_empty() {
             return Err(SpecificationError::OtherError(
                 "specification exports no action generators".to_string(),
@@ -248,6 +398,7 @@ impl Verifier {
         }
 
         Ok(Verifier {
+            options,
             context,
             properties,
             action_generators,
@@ -260,6 +411,80 @@ impl Verifier {
         self.properties.keys().cloned().collect()
     }
 
+    /// The selector each focused property is about, by property name.
+    pub fn property_focus(&self) -> HashMap<String, String> {
+        self.properties
+            .values()
+            .filter_map(|property| {
+                Some((property.name.clone(), property.focus.clone()?))
+            })
+            .collect()
+    }
+
+    /// The source of each extractor's function, in definition order.
+    pub fn extractors(&mut self) -> Result<Vec<String>> {
+        self.extractors.pretty(&mut self.context)
+    }
+
+    /// The name of each extractor, if given one with `named`, in definition order.
+    pub fn extractor_names(&mut self) -> Result<Vec<Option<String>>> {
+        self.extractors.names(&mut self.context)
+    }
+
+    /// Captures the evaluation state of all properties, to resume verifying later with
+    /// [`Verifier::restore`], e.g. in another process.
+    ///
+    /// Properties that are pending resume where they left off, unless their residual depends on
+    /// functions created while evaluating, like a thunk returned by another thunk (as in
+    /// `now(() => { const x = ...; return next(() => ...) })`). Such functions can't be found again
+    /// in the specification, so those properties restart from their formula instead. Past-time
+    /// operators keep what they've seen so far, but extractor values of past states aren't
+    /// captured.
+    pub fn checkpoint(&self) -> SerializedState {
+        let mut properties: Vec<PropertyCheckpoint> = self
+            .properties
+            .values()
+            .map(|property| property.checkpoint())
+            .collect();
+        properties.sort_by(|a, b| a.name.cmp(&b.name));
+        SerializedState { properties }
+    }
+
+    /// Creates a verifier like [`Verifier::new`], with property states restored from a
+    /// [`Verifier::checkpoint`] of the same specification. Properties not in the checkpoint start
+    /// from their formula, and properties that changed since the checkpoint are an error.
+    pub fn restore(
+        bundle_code: &str,
+        options: VerifierOptions,
+        state: &SerializedState,
+    ) -> Result<Self> {
+        let mut verifier = Verifier::new(bundle_code, options)?;
+        // Restored violations only need their functions to be rendered, not called.
+        let placeholder: JsObject =
+            NativeFunction::from_fn_ptr(|_this, _args, _context| {
+                Err(JsNativeError::error()
+                    .with_message(
+                        "functions restored from a checkpoint can't be called",
+                    )
+                    .into())
+            })
+            .to_js_function(verifier.context.realm())
+            .into();
+        for checkpoint in &state.properties {
+            let property = verifier
+                .properties
+                .get_mut(&checkpoint.name)
+                .ok_or(SpecificationError::OtherError(format!(
+                    "property {} from the checkpoint is not in the specification",
+                    checkpoint.name
+                )))?;
+            property.restore(checkpoint, &placeholder)?;
+        }
+        Ok(verifier)
+    }
+
+    /// Steps all properties. Properties start in the first stepped state, so states only seen
+    /// through [`Verifier::actions`] before that don't count towards them.
     pub fn step<A: serde::de::DeserializeOwned>(
         &mut self,
         snapshots: Vec<Snapshot>,
@@ -271,7 +496,7 @@ impl Verifier {
             &mut self.context,
         )?;
         let mut result_properties = Vec::with_capacity(self.properties.len());
-        let mut generator_branches: Vec<(u16, Tree<A>)> = Vec::new();
+        let eventually_evidence = self.options.eventually_evidence;
 
         let context = &mut self.context;
         let mut evaluate_thunk = |function: &RuntimeFunction,
@@ -288,16 +513,21 @@ impl Verifier {
             })
             .nnf())
         };
-        let mut evaluator = Evaluator::new(&mut evaluate_thunk);
-
         for property in self.properties.values_mut() {
+            property
+                .past_time
+                .step(&mut evaluate_thunk, time)
+                .map_err(in_property(&property.name))?;
+            let mut evaluator = Evaluator::new(&mut evaluate_thunk)
+                .with_eventually_evidence(eventually_evidence)
+                .with_past_time(&property.past_time);
             let value = match &property.state {
-                PropertyState::Initial(formula) => {
-                    evaluator.evaluate(formula, time)?
-                }
-                PropertyState::Residual(residual) => {
-                    evaluator.step(residual, time)?
-                }
+                PropertyState::Initial(formula) => evaluator
+                    .evaluate(formula, time)
+                    .map_err(in_property(&property.name))?,
+                PropertyState::Residual(residual) => evaluator
+                    .step(residual, time)
+                    .map_err(in_property(&property.name))?,
                 PropertyState::DefinitelyTrue => ltl::Value::True,
                 PropertyState::DefinitelyFalse(violation) => {
                     ltl::Value::False(violation.clone())
@@ -324,18 +554,39 @@ impl Verifier {
             ));
         }
 
+        Ok(StepResult {
+            properties: result_properties,
+            actions: self.generate_actions()?,
+        })
+    }
+
+    /// Generates the actions available in the state described by the snapshots, without stepping
+    /// any properties.
+    pub fn actions<A: serde::de::DeserializeOwned>(
+        &mut self,
+        snapshots: Vec<Snapshot>,
+        time: ltl::Time,
+    ) -> Result<Tree<A>> {
+        self.extractors.update_from_snapshots(
+            snapshots,
+            time,
+            &mut self.context,
+        )?;
+        self.generate_actions()
+    }
+
+    fn generate_actions<A: serde::de::DeserializeOwned>(
+        &mut self,
+    ) -> Result<Tree<A>> {
+        let mut generator_branches: Vec<(u16, Tree<A>)> =
+            Vec::with_capacity(self.action_generators.len());
         for action_generator in self.action_generators.values() {
             // All exported generators are weighted equally.
-            generator_branches.push((1, action_generator.generate(context)?));
+            generator_branches
+                .push((1, action_generator.generate(&mut self.context)?));
         }
-
-        let action_tree = Tree::Branch {
+        Ok(Tree::Branch {
             branches: generator_branches,
-        };
-
-        Ok(StepResult {
-            properties: result_properties,
-            actions: action_tree,
         })
     }
 }
@@ -346,7 +597,214 @@ const IGNORED_STRING_EXPORTS: &[&str] = &["__esModule"];
 #[derive(Debug, Clone)]
 pub struct Property {
     pub name: String,
+    /// The selector of the element the property is about, see `Formula.focus`.
+    pub focus: Option<String>,
+    formula: Formula<RuntimeFunction>,
     state: PropertyState,
+    past_time: PastTime<RuntimeFunction>,
+}
+
+impl Property {
+    fn checkpoint(&self) -> PropertyCheckpoint {
+        let thunks = formula_thunks(&self.formula);
+        let resumable = Cell::new(true);
+        let to_checkpoint = |function: &RuntimeFunction| match thunks
+            .iter()
+            .position(|thunk| thunk == function)
+        {
+            Some(index) => CheckpointFunction::Specification(index),
+            None => {
+                resumable.set(false);
+                CheckpointFunction::Pretty(function.pretty.clone())
+            }
+        };
+        let state = match &self.state {
+            PropertyState::Initial(_) => PropertyCheckpointState::Initial,
+            PropertyState::Residual(residual) => {
+                let residual = residual.map_function(to_checkpoint);
+                if resumable.get() {
+                    PropertyCheckpointState::Residual(residual)
+                } else {
+                    log::warn!(
+                        "property {} depends on functions created while evaluating it, so it will \
+                         restart when resumed from this checkpoint",
+                        self.name
+                    );
+                    PropertyCheckpointState::Initial
+                }
+            }
+            PropertyState::DefinitelyTrue => {
+                PropertyCheckpointState::DefinitelyTrue
+            }
+            PropertyState::DefinitelyFalse(violation) => {
+                PropertyCheckpointState::DefinitelyFalse(
+                    violation.map_function(to_checkpoint),
+                )
+            }
+        };
+        PropertyCheckpoint {
+            name: self.name.clone(),
+            formula: self.formula.map_function(|f| f.pretty.clone()),
+            state,
+            past_time: self.past_time.map_function(to_checkpoint),
+        }
+    }
+
+    fn restore(
+        &mut self,
+        checkpoint: &PropertyCheckpoint,
+        placeholder: &JsObject,
+    ) -> Result<()> {
+        if self.formula.map_function(|f| f.pretty.clone()) != checkpoint.formula
+        {
+            return Err(SpecificationError::OtherError(format!(
+                "property {} has changed since the checkpoint",
+                self.name
+            )));
+        }
+        let thunks = formula_thunks(&self.formula);
+        let from_checkpoint = |function: &CheckpointFunction| match function {
+            CheckpointFunction::Specification(index)
+                if let Some(thunk) = thunks.get(*index) =>
+            {
+                thunk.clone()
+            }
+            CheckpointFunction::Specification(_) => RuntimeFunction {
+                object: placeholder.clone(),
+                pretty: "<unknown>".to_string(),
+            },
+            CheckpointFunction::Pretty(pretty) => RuntimeFunction {
+                object: placeholder.clone(),
+                pretty: pretty.clone(),
+            },
+        };
+        self.state = match &checkpoint.state {
+            PropertyCheckpointState::Initial => {
+                PropertyState::Initial(self.formula.clone())
+            }
+            PropertyCheckpointState::Residual(residual) => {
+                PropertyState::Residual(residual.map_function(from_checkpoint))
+            }
+            PropertyCheckpointState::DefinitelyTrue => {
+                PropertyState::DefinitelyTrue
+            }
+            PropertyCheckpointState::DefinitelyFalse(violation) => {
+                PropertyState::DefinitelyFalse(
+                    violation.map_function(from_checkpoint),
+                )
+            }
+        };
+        self.past_time = checkpoint.past_time.map_function(from_checkpoint);
+        Ok(())
+    }
+}
+
+/// Names the property in an error converting or evaluating it.
+fn in_property(
+    name: &str,
+) -> impl Fn(SpecificationError) -> SpecificationError + '_ {
+    move |error| {
+        SpecificationError::OtherError(format!("property {}: {}", name, error))
+    }
+}
+
+/// The tags of an exported formula, see `Formula.tag`.
+fn formula_tags(value: &JsValue, context: &mut Context) -> Result<Vec<String>> {
+    let tags_value = value
+        .as_object()
+        .ok_or(SpecificationError::OtherError(format!(
+            "formula is not an object: {}",
+            value.display()
+        )))?
+        .get(js_string!("tags"), context)?;
+    if tags_value.is_undefined() {
+        return Ok(vec![]);
+    }
+    let tags_array = JsArray::from_object(tags_value.as_object().ok_or(
+        SpecificationError::OtherError(format!(
+            "tags is not an array, it is {}",
+            tags_value.type_of()
+        )),
+    )?)?;
+    let length = tags_array.length(context)?;
+    let mut tags = Vec::with_capacity(length as usize);
+    for i in 0..length {
+        let tag = tags_array.at(i as i64, context)?;
+        tags.push(
+            tag.as_string()
+                .ok_or(SpecificationError::OtherError(format!(
+                    "tag is not a string, it is {}",
+                    tag.type_of()
+                )))?
+                .to_std_string_escaped(),
+        );
+    }
+    Ok(tags)
+}
+
+/// The focus selector of an exported formula, see `Formula.focus`.
+fn formula_focus(
+    value: &JsValue,
+    context: &mut Context,
+) -> Result<Option<String>> {
+    let focus_value = value
+        .as_object()
+        .ok_or(SpecificationError::OtherError(format!(
+            "formula is not an object: {}",
+            value.display()
+        )))?
+        .get(js_string!("focusSelector"), context)?;
+    if focus_value.is_null_or_undefined() {
+        return Ok(None);
+    }
+    Ok(Some(
+        focus_value
+            .as_string()
+            .ok_or(SpecificationError::OtherError(format!(
+                "focus selector is not a string, it is {}",
+                focus_value.type_of()
+            )))?
+            .to_std_string_escaped(),
+    ))
+}
+
+/// The thunks of a formula in a stable order, so that checkpoints can refer to them by index.
+fn formula_thunks(formula: &Formula<RuntimeFunction>) -> Vec<RuntimeFunction> {
+    let thunks = RefCell::new(vec![]);
+    formula.map_function(|function| thunks.borrow_mut().push(function.clone()));
+    thunks.into_inner()
+}
+
+/// The evaluation state of a verifier's properties, see [`Verifier::checkpoint`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SerializedState {
+    properties: Vec<PropertyCheckpoint>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PropertyCheckpoint {
+    name: String,
+    /// The property's formula, pretty-printed, to detect changes to the specification.
+    formula: Formula<String>,
+    state: PropertyCheckpointState,
+    past_time: PastTime<CheckpointFunction>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum PropertyCheckpointState {
+    Initial,
+    Residual(Residual<CheckpointFunction>),
+    DefinitelyTrue,
+    DefinitelyFalse(Violation<CheckpointFunction>),
+}
+
+/// A function in a checkpoint. The specification's own thunks are found again by their position
+/// in the property's formula. Other functions, created while evaluating, only keep their pretty
+/// form, as needed to render violations.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum CheckpointFunction {
+    Specification(usize),
+    Pretty(String),
 }
 
 #[derive(Debug, Clone)]
@@ -399,27 +857,34 @@ mod tests {
 
     use tempfile::NamedTempFile;
 
+    use crate::specification::render::render_formula;
     use crate::specification::stop::{StopDefault, stop_default};
 
     use super::*;
 
     fn verifier(specification: &str) -> Verifier {
-        use crate::specification::bundler::bundle;
+        verifier_with_options(specification, VerifierOptions::default())
+    }
+
+    fn verifier_with_options(
+        specification: &str,
+        options: VerifierOptions,
+    ) -> Verifier {
+        Verifier::new(&bundle_code(specification), options).unwrap()
+    }
 
+    fn bundle_code(specification: &str) -> String {
         let mut specification_file = NamedTempFile::with_suffix(".ts").unwrap();
         specification_file
             .write_all(specification.as_bytes())
             .unwrap();
 
         let rt = tokio::runtime::Runtime::new().unwrap();
-        let bundle_code = rt
-            .block_on(bundle(
-                ".",
-                &specification_file.path().display().to_string(),
-            ))
-            .unwrap();
-
-        Verifier::new(&bundle_code).unwrap()
+        rt.block_on(bundle(
+            ".",
+            &specification_file.path().display().to_string(),
+        ))
+        .unwrap()
     }
 
     #[test]
@@ -443,6 +908,126 @@ mod tests {
         assert_eq!(verifier.properties(), vec!["max_notifications_shown"]);
     }
 
+    #[test]
+    fn test_property_filter() {
+        let specification = r#"
+            import { actions, always, eventually, extract } from "@antithesishq/bombadil";
+            export const _actions = actions(() => []);
+
+            const title = extract((state) => state.document.title);
+
+            export const titled = always(() => title.current !== "").tag("smoke");
+            export const short_title = always(() => title.current.length < 80);
+            export const loaded = eventually(() => title.current === "Done").tag("smoke", "slow");
+            "#;
+        let bundle_code = bundle_code(specification);
+        let filtered = |properties: &[&str], tags: &[&str]| {
+            Verifier::new(
+                &bundle_code,
+                VerifierOptions {
+                    property_filter: PropertyFilter {
+                        properties: properties
+                            .iter()
+                            .map(|name| name.to_string())
+                            .collect(),
+                        tags: tags.iter().map(|tag| tag.to_string()).collect(),
+                    },
+                    ..VerifierOptions::default()
+                },
+            )
+            .map(|verifier| {
+                let mut properties = verifier.properties();
+                properties.sort();
+                properties
+            })
+        };
+
+        assert_eq!(
+            filtered(&[], &[]).unwrap(),
+            vec!["loaded", "short_title", "titled"]
+        );
+        assert_eq!(
+            filtered(&[], &["smoke"]).unwrap(),
+            vec!["loaded", "titled"]
+        );
+        assert_eq!(
+            filtered(&["short_title"], &["slow"]).unwrap(),
+            vec!["loaded", "short_title"]
+        );
+        assert!(filtered(&["untitled"], &[]).is_err());
+        assert!(filtered(&[], &["fast"]).is_err());
+    }
+
+    #[test]
+    fn test_helper_exports() {
+        let specification = r#"
+            import { actions, always, extract } from "@antithesishq/bombadil";
+            export const _actions = actions(() => []);
+
+            export const threshold = 5;
+
+            const notification_count = extract(
+              (state) => state.document.body.querySelectorAll(".notification").length,
+            );
+
+            export const max_notifications_shown = always(
+              () => notification_count.current <= threshold,
+            );
+            "#;
+        let verifier = verifier(specification);
+        assert_eq!(verifier.properties(), vec!["max_notifications_shown"]);
+
+        let strict = Verifier::new(
+            &bundle_code(specification),
+            VerifierOptions {
+                strict_exports: true,
+                ..VerifierOptions::default()
+            },
+        );
+        assert!(strict.is_err());
+    }
+
+    #[test]
+    fn test_introspect() {
+        let mut specification_file = NamedTempFile::with_suffix(".ts").unwrap();
+        specification_file
+            .write_all(
+                r#"
+                import { actions, always, extract } from "@antithesishq/bombadil";
+                export const _actions = actions(() => []);
+
+                const title = extract((state) => state.document.title);
+
+                export const titled = always(() => title.current !== "");
+                export const short_title = always(() => title.current.length < 80);
+                "#
+                .as_bytes(),
+            )
+            .unwrap();
+        let specification = Specification {
+            module_specifier: specification_file.path().display().to_string(),
+        };
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let introspection = rt.block_on(specification.introspect()).unwrap();
+        assert_eq!(introspection.properties, vec!["short_title", "titled"]);
+        assert_eq!(
+            introspection
+                .formulas
+                .iter()
+                .map(render_formula)
+                .collect::<Vec<_>>(),
+            vec![
+                "always(() => title.current.length < 80)",
+                "always(() => title.current !== \"\")"
+            ]
+        );
+        assert_eq!(
+            introspection.extractors,
+            vec!["(state) => state.document.title"]
+        );
+    }
+
     #[test]
     fn test_property_evaluation_not() {
         let mut verifier = verifier(
@@ -593,69 +1178,193 @@ mod tests {
     }
 
     #[test]
-    fn test_property_evaluation_next() {
+    fn test_property_evaluation_xor() {
         let mut verifier = verifier(
             r#"
-            import { actions, extract, next } from "@antithesishq/bombadil";
+            import { actions, extract, xor } from "@antithesishq/bombadil";
             export const _actions = actions(() => []);
 
             const foo = extract((state) => state.foo);
+            const bar = extract((state) => state.bar);
 
-            export const my_prop = next(() => foo.current === 1);
+            export const my_prop = xor(() => foo.current, () => bar.current);
             "#,
         );
 
-        let time_at = |i: u64| {
-            SystemTime::UNIX_EPOCH
-                .checked_add(Duration::from_millis(i))
-                .unwrap()
-        };
+        let time = SystemTime::UNIX_EPOCH
+            .checked_add(Duration::from_millis(0))
+            .unwrap();
 
-        for i in 0..=1 {
-            let time = time_at(i);
-            let result: StepResult<Snapshot> = verifier
-                .step(
-                    vec![Snapshot {
+        let result: StepResult<Snapshot> = verifier
+            .step(
+                vec![
+                    Snapshot {
                         name: None,
-                        value: json::json!(i),
-                    }],
-                    time,
-                )
-                .unwrap();
-
-            let (name, value) = result.properties.first().unwrap();
-            assert_eq!(*name, "my_prop");
+                        value: json::json!(true),
+                    },
+                    Snapshot {
+                        name: None,
+                        value: json::json!(true),
+                    },
+                ],
+                time,
+            )
+            .unwrap();
 
-            if i == 1 {
-                assert!(matches!(value, ltl::Value::True));
-            } else {
-                match value {
-                    ltl::Value::Residual(residual) => {
-                        match stop_default(residual, time) {
-                            Some(StopDefault::True) => {}
-                            _ => panic!("should have a true stop default"),
-                        }
-                    }
-                    _ => panic!("should be residual but was: {:?}", value),
-                }
-            }
-        }
+        let (name, value) = result.properties.first().unwrap();
+        assert_eq!(*name, "my_prop");
+        assert!(matches!(
+            value,
+            ltl::Value::False(ltl::Violation::XorBoth { .. })
+        ));
     }
 
     #[test]
-    fn test_property_evaluation_always() {
+    fn test_property_evaluation_iff() {
         let mut verifier = verifier(
             r#"
-            import { extract, always, actions } from "@antithesishq/bombadil";
+            import { actions, extract, now } from "@antithesishq/bombadil";
             export const _actions = actions(() => []);
 
             const foo = extract((state) => state.foo);
+            const bar = extract((state) => state.bar);
 
-            export const my_prop = always(() => foo.current < 100);
+            export const my_prop = now(() => foo.current).iff(() => bar.current);
             "#,
         );
 
-        let time_at = |i: u64| {
+        let time = SystemTime::UNIX_EPOCH
+            .checked_add(Duration::from_millis(0))
+            .unwrap();
+
+        let result: StepResult<Snapshot> = verifier
+            .step(
+                vec![
+                    Snapshot {
+                        name: None,
+                        value: json::json!(true),
+                    },
+                    Snapshot {
+                        name: None,
+                        value: json::json!(true),
+                    },
+                ],
+                time,
+            )
+            .unwrap();
+
+        let (name, value) = result.properties.first().unwrap();
+        assert_eq!(*name, "my_prop");
+        assert!(matches!(value, ltl::Value::True));
+    }
+
+    #[test]
+    fn test_property_evaluation_next() {
+        let mut verifier = verifier(
+            r#"
+            import { actions, extract, next } from "@antithesishq/bombadil";
+            export const _actions = actions(() => []);
+
+            const foo = extract((state) => state.foo);
+
+            export const my_prop = next(() => foo.current === 1);
+            "#,
+        );
+
+        let time_at = |i: u64| {
+            SystemTime::UNIX_EPOCH
+                .checked_add(Duration::from_millis(i))
+                .unwrap()
+        };
+
+        for i in 0..=1 {
+            let time = time_at(i);
+            let result: StepResult<Snapshot> = verifier
+                .step(
+                    vec![Snapshot {
+                        name: None,
+                        value: json::json!(i),
+                    }],
+                    time,
+                )
+                .unwrap();
+
+            let (name, value) = result.properties.first().unwrap();
+            assert_eq!(*name, "my_prop");
+
+            if i == 1 {
+                assert!(matches!(value, ltl::Value::True));
+            } else {
+                match value {
+                    ltl::Value::Residual(residual) => {
+                        match stop_default(residual, time) {
+                            Some(StopDefault::True) => {}
+                            _ => panic!("should have a true stop default"),
+                        }
+                    }
+                    _ => panic!("should be residual but was: {:?}", value),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_property_evaluation_next_n() {
+        let mut verifier = verifier(
+            r#"
+            import { actions, extract, nextN } from "@antithesishq/bombadil";
+            export const _actions = actions(() => []);
+
+            const foo = extract((state) => state.foo);
+
+            export const my_prop = nextN(3, () => foo.current === 3);
+            "#,
+        );
+
+        let time_at = |i: u64| {
+            SystemTime::UNIX_EPOCH
+                .checked_add(Duration::from_millis(i))
+                .unwrap()
+        };
+
+        for i in 0..=3 {
+            let result: StepResult<Snapshot> = verifier
+                .step(
+                    vec![Snapshot {
+                        name: None,
+                        value: json::json!(i),
+                    }],
+                    time_at(i),
+                )
+                .unwrap();
+
+            let (_, value) = result.properties.first().unwrap();
+            if i == 3 {
+                assert!(matches!(value, ltl::Value::True));
+            } else {
+                assert!(
+                    matches!(value, ltl::Value::Residual(_)),
+                    "should be residual but was: {:?}",
+                    value
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_property_evaluation_always() {
+        let mut verifier = verifier(
+            r#"
+            import { extract, always, actions } from "@antithesishq/bombadil";
+            export const _actions = actions(() => []);
+
+            const foo = extract((state) => state.foo);
+
+            export const my_prop = always(() => foo.current < 100);
+            "#,
+        );
+
+        let time_at = |i: u64| {
             SystemTime::UNIX_EPOCH
                 .checked_add(Duration::from_millis(i))
                 .unwrap()
@@ -699,6 +1408,75 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_property_evaluation_once() {
+        let specification = r#"
+            import { actions, always, extract, now, once } from "@antithesishq/bombadil";
+            export const _actions = actions(() => []);
+
+            const page = extract((state) => state.page);
+
+            export const logout_after_login = always(
+              now(() => page.current.logout).implies(once(() => page.current.loggedIn)),
+            );
+            "#;
+        let time_at = |i: u64| {
+            SystemTime::UNIX_EPOCH
+                .checked_add(Duration::from_millis(i))
+                .unwrap()
+        };
+        let run = |states: &[(bool, bool)]| {
+            let mut verifier = verifier(specification);
+            states
+                .iter()
+                .enumerate()
+                .map(|(i, (logged_in, logout))| {
+                    let result: StepResult<Snapshot> = verifier
+                        .step(
+                            vec![Snapshot {
+                                name: None,
+                                value: json::json!({
+                                    "loggedIn": logged_in,
+                                    "logout": logout,
+                                }),
+                            }],
+                            time_at(i as u64),
+                        )
+                        .unwrap();
+                    !matches!(result.properties[0].1, ltl::Value::False(_))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Logging in, then out, still shows the button as logged in before.
+        assert_eq!(
+            run(&[(false, false), (true, true), (false, true)]),
+            vec![true, true, true]
+        );
+        assert_eq!(run(&[(false, false), (false, true)]), vec![true, false]);
+    }
+
+    #[test]
+    fn test_past_time_rejects_future_operators() {
+        let result = Verifier::new(
+            &bundle_code(
+                r#"
+                import { actions, eventually, once } from "@antithesishq/bombadil";
+                export const _actions = actions(() => []);
+
+                export const my_prop = once(eventually(() => true));
+                "#,
+            ),
+            VerifierOptions::default(),
+        );
+        let Err(error) = result else {
+            panic!("should reject `eventually` in `once`");
+        };
+        let message = error.to_string();
+        assert!(message.contains("my_prop"), "{}", message);
+        assert!(message.contains("about the future"), "{}", message);
+    }
+
     #[test]
     fn test_property_evaluation_always_bounded() {
         let mut verifier = verifier(
@@ -848,4 +1626,448 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_property_on_last_action() {
+        let mut verifier = verifier(
+            r#"
+            import { actions, actionKind, always, eventually, extract, now } from "@antithesishq/bombadil";
+            export const _actions = actions(() => []);
+
+            const lastActionKind = extract((state) =>
+                state.lastAction === null ? null : actionKind(state.lastAction),
+            );
+            const loading = extract((state) =>
+                state.document.querySelector(".loading") !== null,
+            );
+
+            export const clicksShowLoading = always(
+                now(() => lastActionKind.current === "Click").implies(
+                    eventually(() => loading.current).within(5, "milliseconds"),
+                ),
+            );
+            "#,
+        );
+
+        let time_at = |i: u64| {
+            SystemTime::UNIX_EPOCH
+                .checked_add(Duration::from_millis(i))
+                .unwrap()
+        };
+        let states = [
+            (json::json!(null), false),
+            (json::json!("Click"), false),
+            (json::json!("Back"), true),
+            (json::json!("Click"), false),
+            (json::json!("Back"), false),
+            (json::json!("Back"), false),
+        ];
+        let mut values = vec![];
+        for (i, (kind, loading)) in states.into_iter().enumerate() {
+            let result: StepResult<Snapshot> = verifier
+                .step(
+                    vec![
+                        Snapshot {
+                            name: Some("lastActionKind".to_string()),
+                            value: kind,
+                        },
+                        Snapshot {
+                            name: Some("loading".to_string()),
+                            value: json::json!(loading),
+                        },
+                    ],
+                    time_at(i as u64 * 3),
+                )
+                .unwrap();
+            let (_, value) = result.properties.first().unwrap();
+            values.push(matches!(value, ltl::Value::False(_)));
+        }
+        assert_eq!(values, vec![false, false, false, false, false, true]);
+    }
+
+    #[test]
+    fn test_bound_longer_than_maximum() {
+        let result = Verifier::new(
+            &bundle_code(
+                r#"
+                import { actions, eventually } from "@antithesishq/bombadil";
+                export const _actions = actions(() => []);
+
+                export const my_prop = eventually(() => true).within(18446744073709551000, "milliseconds");
+                "#,
+            ),
+            VerifierOptions::default(),
+        );
+        let Err(error) = result else {
+            panic!("should reject the bound");
+        };
+        let message = error.to_string();
+        assert!(message.contains("my_prop"), "{}", message);
+        assert!(message.contains("maximum"), "{}", message);
+    }
+
+    #[test]
+    fn test_property_evaluation_eventually_nested_timed_out() {
+        use crate::specification::render::render_violation;
+
+        let mut verifier = verifier(
+            r#"
+            import { actions, extract, always, eventually } from "@antithesishq/bombadil";
+            export const _actions = actions(() => []);
+
+            const foo = extract((state) => state.foo);
+
+            export const my_prop = always(
+              eventually(() => foo.current === 9).within(3, "milliseconds"),
+            );
+            "#,
+        );
+
+        let time_at = |i: u64| {
+            SystemTime::UNIX_EPOCH
+                .checked_add(Duration::from_millis(i))
+                .unwrap()
+        };
+
+        for i in 0..10 {
+            let result: StepResult<Snapshot> = verifier
+                .step(
+                    vec![Snapshot {
+                        name: None,
+                        value: json::json!(i),
+                    }],
+                    time_at(i),
+                )
+                .unwrap();
+
+            let (name, value) = result.properties.first().unwrap();
+            assert_eq!(*name, "my_prop");
+
+            if let ltl::Value::False(violation) = value {
+                let rendered =
+                    render_violation(&violation.with_pretty_functions());
+                assert!(
+                    rendered.contains("timed out at 3ms"),
+                    "should report the deadline, but was: {}",
+                    rendered
+                );
+                return;
+            }
+        }
+        panic!("should have been violated");
+    }
+
+    #[test]
+    fn test_property_evaluation_eventually_evidence() {
+        use crate::specification::render::render_violation;
+
+        let mut verifier = verifier_with_options(
+            r#"
+            import { actions, extract, eventually } from "@antithesishq/bombadil";
+            export const _actions = actions(() => []);
+
+            const foo = extract((state) => state.foo);
+
+            export const my_prop = eventually(() => foo.current === 99).within(3, "milliseconds");
+            "#,
+            VerifierOptions {
+                eventually_evidence: true,
+                ..VerifierOptions::default()
+            },
+        );
+
+        let time_at = |i: u64| {
+            SystemTime::UNIX_EPOCH
+                .checked_add(Duration::from_millis(i))
+                .unwrap()
+        };
+
+        for i in 0..10 {
+            let result: StepResult<Snapshot> = verifier
+                .step(
+                    vec![Snapshot {
+                        name: None,
+                        value: json::json!(i),
+                    }],
+                    time_at(i),
+                )
+                .unwrap();
+
+            let (_, value) = result.properties.first().unwrap();
+            if let ltl::Value::False(violation) = value {
+                let rendered =
+                    render_violation(&violation.with_pretty_functions());
+                assert!(
+                    rendered.contains("last checked at 3ms, still false"),
+                    "should show the last evidence, but was: {}",
+                    rendered
+                );
+                return;
+            }
+        }
+        panic!("should have been violated");
+    }
+
+    #[test]
+    fn test_property_evaluation_stable() {
+        const TRACE_LENGTH: usize = 4;
+        const TRACES: usize = 1 << TRACE_LENGTH;
+
+        // Checks every boolean trace of the given length at once, with a pair of properties
+        // per trace, in a single verifier.
+        let mut specification = r#"
+            import { actions, extract, always, eventually, stable } from "@antithesishq/bombadil";
+            export const _actions = actions(() => []);
+
+            const bits = extract((state) => state.foo);
+            "#
+        .to_string();
+        for trace in 0..TRACES {
+            specification.push_str(&format!(
+                "export const stable_{trace} = stable(() => bits.current[{trace}]);\n\
+                 export const manual_{trace} = eventually(always(() => bits.current[{trace}]));\n"
+            ));
+        }
+        let mut verifier = verifier(&specification);
+
+        // Outcome of a property, where residuals are resolved as if the test stopped.
+        let outcome = |value: &ltl::Value<RuntimeFunction>, time| match value {
+            ltl::Value::True => "true",
+            ltl::Value::False(_) => "false",
+            ltl::Value::Residual(residual) => {
+                match stop_default(residual, time) {
+                    Some(StopDefault::True) => "residual (true at stop)",
+                    Some(StopDefault::False(_)) => "residual (false at stop)",
+                    None => panic!("residual should have a stop default"),
+                }
+            }
+        };
+
+        for i in 0..TRACE_LENGTH {
+            let time = SystemTime::UNIX_EPOCH
+                .checked_add(Duration::from_millis(i as u64))
+                .unwrap();
+            let bits: Vec<bool> =
+                (0..TRACES).map(|trace| trace >> i & 1 == 1).collect();
+            let result: StepResult<Snapshot> = verifier
+                .step(
+                    vec![Snapshot {
+                        name: None,
+                        value: json::json!(bits),
+                    }],
+                    time,
+                )
+                .unwrap();
+
+            let outcomes: HashMap<&str, &str> = result
+                .properties
+                .iter()
+                .map(|(name, value)| (name.as_str(), outcome(value, time)))
+                .collect();
+            for (trace, bit) in bits.iter().enumerate() {
+                let stable = outcomes[format!("stable_{trace}").as_str()];
+                let manual = outcomes[format!("manual_{trace}").as_str()];
+                assert_eq!(stable, manual, "trace {trace:04b} at state {i}");
+                // It only holds at stop if it holds in the last state.
+                assert_eq!(
+                    stable,
+                    if *bit {
+                        "residual (true at stop)"
+                    } else {
+                        "residual (false at stop)"
+                    },
+                    "trace {trace:04b} at state {i}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_actions_without_stepping() {
+        let mut verifier = verifier(
+            r#"
+            import { actions, extract, always } from "@antithesishq/bombadil";
+
+            const foo = extract((state) => state.foo);
+
+            export const _actions = actions(() => foo.current ? ["Reload"] : []);
+            export const my_prop = always(() => foo.current);
+            "#,
+        );
+
+        let time = SystemTime::UNIX_EPOCH;
+
+        let actions: Tree<json::Value> = verifier
+            .actions(
+                vec![Snapshot {
+                    name: None,
+                    value: json::json!(false),
+                }],
+                time,
+            )
+            .unwrap();
+        assert!(actions.prune().is_none());
+
+        let result: StepResult<json::Value> = verifier
+            .step(
+                vec![Snapshot {
+                    name: None,
+                    value: json::json!(true),
+                }],
+                time,
+            )
+            .unwrap();
+        let (_, value) = result.properties.first().unwrap();
+        assert!(matches!(value, ltl::Value::Residual(_)));
+        assert_eq!(
+            result.actions.prune().unwrap().flatten(),
+            vec![(1.0, &json::json!("Reload"))]
+        );
+    }
+
+    #[test]
+    fn test_dictionary() {
+        let mut verifier = Verifier::new(
+            &bundle_code(
+                r#"
+            import { actions, dictionary } from "@antithesishq/bombadil";
+
+            export const _actions = actions(() => {
+                const emails = dictionary("email");
+                return emails && dictionary("date") === null
+                    ? [{ TypeText: { text: emails.generate(), delayMillis: 0 } }]
+                    : [];
+            });
+            "#,
+            ),
+            VerifierOptions {
+                dictionary: HashMap::from([(
+                    "email".to_string(),
+                    vec!["jane@example.com".to_string()],
+                )]),
+                ..VerifierOptions::default()
+            },
+        )
+        .unwrap();
+
+        let actions: Tree<json::Value> =
+            verifier.actions(vec![], SystemTime::UNIX_EPOCH).unwrap();
+        assert_eq!(
+            actions.prune().unwrap().flatten(),
+            vec![(
+                1.0,
+                &json::json!({ "TypeText": { "text": "jane@example.com", "delayMillis": 0 } })
+            )]
+        );
+    }
+
+    fn step_foo(
+        verifier: &mut Verifier,
+        value: json::Value,
+        time: SystemTime,
+    ) -> ltl::Value<RuntimeFunction> {
+        let result: StepResult<json::Value> = verifier
+            .step(vec![Snapshot { name: None, value }], time)
+            .unwrap();
+        result.properties.into_iter().next().unwrap().1
+    }
+
+    fn restored(bundle_code: &str, verifier: &Verifier) -> Verifier {
+        let checkpoint = json::to_string(&verifier.checkpoint()).unwrap();
+        Verifier::restore(
+            bundle_code,
+            VerifierOptions::default(),
+            &json::from_str(&checkpoint).unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_checkpoint_resumes_residual() {
+        let bundle_code = bundle_code(
+            r#"
+            import { extract, always, actions } from "@antithesishq/bombadil";
+            export const _actions = actions(() => []);
+
+            const foo = extract((state) => state.foo);
+
+            export const my_prop = always(() => foo.current < 100);
+            "#,
+        );
+        let mut verifier =
+            Verifier::new(&bundle_code, VerifierOptions::default()).unwrap();
+        let start = SystemTime::UNIX_EPOCH;
+        let later = start + Duration::from_millis(1);
+
+        step_foo(&mut verifier, json::json!(1), start);
+        let mut verifier = restored(&bundle_code, &verifier);
+
+        // The violation's start shows that the `always` wasn't restarted.
+        match step_foo(&mut verifier, json::json!(100), later) {
+            ltl::Value::False(Violation::Always { start: actual, .. }) => {
+                assert_eq!(actual, start)
+            }
+            value => panic!("expected always violation, got {:?}", value),
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_resumes_past_time() {
+        let bundle_code = bundle_code(
+            r#"
+            import { extract, always, actions, now, once } from "@antithesishq/bombadil";
+            export const _actions = actions(() => []);
+
+            const foo = extract((state) => state.foo);
+
+            export const my_prop = always(
+              now(() => foo.current === 2).implies(once(() => foo.current === 1)),
+            );
+            "#,
+        );
+        let mut verifier =
+            Verifier::new(&bundle_code, VerifierOptions::default()).unwrap();
+        let start = SystemTime::UNIX_EPOCH;
+
+        step_foo(&mut verifier, json::json!(1), start);
+        let mut verifier = restored(&bundle_code, &verifier);
+
+        // The `once` still knows of the state before the checkpoint.
+        let value = step_foo(
+            &mut verifier,
+            json::json!(2),
+            start + Duration::from_millis(1),
+        );
+        assert!(matches!(value, ltl::Value::Residual(_)), "{:?}", value);
+    }
+
+    #[test]
+    fn test_checkpoint_restarts_nested_thunks() {
+        let bundle_code = bundle_code(
+            r#"
+            import { extract, now, next, actions } from "@antithesishq/bombadil";
+            export const _actions = actions(() => []);
+
+            const foo = extract((state) => state.foo);
+
+            export const my_prop = now(() => {
+              const current = foo.current;
+              return next(() => foo.current === current);
+            });
+            "#,
+        );
+        let mut verifier =
+            Verifier::new(&bundle_code, VerifierOptions::default()).unwrap();
+        let start = SystemTime::UNIX_EPOCH;
+        let later = start + Duration::from_millis(1);
+
+        step_foo(&mut verifier, json::json!(1), start);
+        let mut verifier = restored(&bundle_code, &verifier);
+
+        // Resumed, this would be a violation, but the property restarts instead.
+        assert!(matches!(
+            step_foo(&mut verifier, json::json!(2), later),
+            ltl::Value::Residual(_)
+        ));
+    }
 }
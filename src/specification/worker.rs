@@ -1,24 +1,35 @@
 use serde::de::DeserializeOwned;
 use serde_json as json;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use tokio::sync::{mpsc, oneshot};
 
 use crate::specification::js::RuntimeFunction;
 use crate::specification::ltl::{self};
 use crate::specification::render::PrettyFunction;
 use crate::specification::result::SpecificationError;
-use crate::specification::verifier::{Snapshot, Specification, Verifier};
+use crate::specification::verifier::{
+    Snapshot, Specification, Verifier, VerifierOptions,
+};
 use crate::tree::Tree;
 
 enum Command {
     GetProperties {
         reply: oneshot::Sender<Vec<String>>,
     },
+    GetPropertyFocus {
+        reply: oneshot::Sender<HashMap<String, String>>,
+    },
     Step {
         snapshots: Vec<Snapshot>,
         time: ltl::Time,
         reply: oneshot::Sender<Result<RawStepResult, SpecificationError>>,
     },
+    Actions {
+        snapshots: Vec<Snapshot>,
+        time: ltl::Time,
+        reply: oneshot::Sender<Result<Tree<json::Value>, SpecificationError>>,
+    },
 }
 
 struct RawStepResult {
@@ -62,18 +73,16 @@ impl VerifierWorker {
     /// Call this once at startup and share the handle as needed.
     pub async fn start(
         specification: Specification,
+        options: VerifierOptions,
     ) -> Result<Arc<Self>, SpecificationError> {
-        use crate::specification::bundler::bundle;
-
-        let bundle_code = bundle(".", &specification.module_specifier)
-            .await
-            .map_err(|e| {
-                SpecificationError::OtherError(format!(
-                    "Failed to bundle specification: {}",
-                    e
-                ))
-            })?;
+        let bundle_code = specification.bundle().await?;
+        Self::start_from_bundle(bundle_code, options).await
+    }
 
+    async fn start_from_bundle(
+        bundle_code: String,
+        options: VerifierOptions,
+    ) -> Result<Arc<Self>, SpecificationError> {
         let (ready_tx, ready_rx) =
             oneshot::channel::<Result<(), SpecificationError>>();
 
@@ -81,7 +90,7 @@ impl VerifierWorker {
         let handle = Arc::new(VerifierWorker { tx });
 
         let _worker_thread = std::thread::spawn(move || {
-            let mut verifier = match Verifier::new(&bundle_code) {
+            let mut verifier = match Verifier::new(&bundle_code, options) {
                 Ok(verifier) => {
                     let _ = ready_tx.send(Ok(()));
                     verifier
@@ -96,6 +105,9 @@ impl VerifierWorker {
                     Command::GetProperties { reply } => {
                         let _ = reply.send(verifier.properties());
                     }
+                    Command::GetPropertyFocus { reply } => {
+                        let _ = reply.send(verifier.property_focus());
+                    }
                     Command::Step {
                         snapshots,
                         time,
@@ -119,6 +131,15 @@ impl VerifierWorker {
                             ),
                         );
                     }
+                    Command::Actions {
+                        snapshots,
+                        time,
+                        reply,
+                    } => {
+                        let _ = reply.send(
+                            verifier.actions::<json::Value>(snapshots, time),
+                        );
+                    }
                 }
             }
         });
@@ -141,6 +162,18 @@ impl VerifierWorker {
         reply_rx.await.map_err(|_| WorkerError::WorkerGone)
     }
 
+    /// See [`Verifier::property_focus`].
+    pub async fn property_focus(
+        &self,
+    ) -> Result<HashMap<String, String>, WorkerError> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.tx
+            .send(Command::GetPropertyFocus { reply: reply_tx })
+            .await
+            .map_err(|_| WorkerError::WorkerGone)?;
+        reply_rx.await.map_err(|_| WorkerError::WorkerGone)
+    }
+
     pub async fn step<A: DeserializeOwned>(
         &self,
         snapshots: Vec<Snapshot>,
@@ -159,18 +192,114 @@ impl VerifierWorker {
             .await
             .map_err(|_| WorkerError::WorkerGone)?
             .map_err(WorkerError::SpecificationError)?;
-        let actions = result.actions.try_map(&mut |v| {
-            json::from_value(v).map_err(|e| {
-                WorkerError::SpecificationError(SpecificationError::OtherError(
-                    format!("failed to deserialize action: {}", e),
-                ))
-            })
-        })?;
         Ok(StepResult {
             properties: result.properties,
-            actions,
+            actions: deserialize_actions(result.actions)?,
         })
     }
+
+    /// Generates the actions available in the state described by the snapshots, without stepping
+    /// any properties.
+    pub async fn actions<A: DeserializeOwned>(
+        &self,
+        snapshots: Vec<Snapshot>,
+        time: ltl::Time,
+    ) -> Result<Tree<A>, WorkerError> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.tx
+            .send(Command::Actions {
+                reply: reply_tx,
+                snapshots,
+                time,
+            })
+            .await
+            .map_err(|_| WorkerError::WorkerGone)?;
+        let actions = reply_rx
+            .await
+            .map_err(|_| WorkerError::WorkerGone)?
+            .map_err(WorkerError::SpecificationError)?;
+        deserialize_actions(actions)
+    }
+}
+
+/// Identifies a trace (e.g. one browser's run) verified by a [`VerifierWorkerPool`].
+pub type TraceId = u64;
+
+/// A fixed number of verifier workers for the same specification, each with its own thread and
+/// JavaScript context, so that multiple traces can be verified concurrently.
+///
+/// A worker keeps the property state of the trace it steps, so each trace is pinned to one
+/// worker. New traces are assigned to workers round-robin, which means that the pool must be at
+/// least as large as the number of concurrent traces for them not to share state.
+pub struct VerifierWorkerPool {
+    workers: Vec<Arc<VerifierWorker>>,
+    assignments: Mutex<PoolAssignments>,
+}
+
+#[derive(Default)]
+struct PoolAssignments {
+    next: usize,
+    pinned: HashMap<TraceId, usize>,
+}
+
+impl VerifierWorkerPool {
+    /// Starts `size` workers, bundling the specification only once.
+    pub async fn start(
+        specification: Specification,
+        size: usize,
+        options: VerifierOptions,
+    ) -> Result<Self, SpecificationError> {
+        if size == 0 {
+            return Err(SpecificationError::OtherError(
+                "verifier worker pool must have at least one worker"
+                    .to_string(),
+            ));
+        }
+        let bundle_code = specification.bundle().await?;
+        let mut workers = Vec::with_capacity(size);
+        for _ in 0..size {
+            workers.push(
+                VerifierWorker::start_from_bundle(
+                    bundle_code.clone(),
+                    options.clone(),
+                )
+                .await?,
+            );
+        }
+        Ok(VerifierWorkerPool {
+            workers,
+            assignments: Mutex::new(PoolAssignments::default()),
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Returns the worker pinned to the trace, assigning one if it's a new trace.
+    pub fn worker(&self, trace_id: TraceId) -> Arc<VerifierWorker> {
+        let mut assignments =
+            self.assignments.lock().expect("pool assignments poisoned");
+        let PoolAssignments { next, pinned } = &mut *assignments;
+        let index = *pinned.entry(trace_id).or_insert_with(|| {
+            let index = *next;
+            *next = (index + 1) % self.workers.len();
+            index
+        });
+        self.workers[index].clone()
+    }
+}
+
+fn deserialize_actions<A: DeserializeOwned>(
+    actions: Tree<json::Value>,
+) -> Result<Tree<A>, WorkerError> {
+    actions.try_map(&mut |v| {
+        json::from_value(v).map_err(|e| {
+            WorkerError::SpecificationError(SpecificationError::OtherError(
+                format!("failed to deserialize action: {}", e),
+            ))
+        })
+    })
 }
 
 #[derive(Debug)]
@@ -191,3 +320,65 @@ impl std::fmt::Display for WorkerError {
 }
 
 impl std::error::Error for WorkerError {}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::time::SystemTime;
+
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pool_workers_are_independent() {
+        let mut specification_file = NamedTempFile::with_suffix(".ts").unwrap();
+        specification_file
+            .write_all(
+                br#"
+                import { actions, extract, always } from "@antithesishq/bombadil";
+                export const _actions = actions(() => []);
+
+                const foo = extract((state) => state.foo);
+
+                export const my_prop = always(() => foo.current);
+                "#,
+            )
+            .unwrap();
+        let pool = VerifierWorkerPool::start(
+            Specification {
+                module_specifier: specification_file
+                    .path()
+                    .display()
+                    .to_string(),
+            },
+            2,
+            VerifierOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        let first = pool.worker(1);
+        let second = pool.worker(2);
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert!(Arc::ptr_eq(&first, &pool.worker(1)));
+
+        let step = async |worker: &VerifierWorker, value: bool| {
+            let result: StepResult<json::Value> = worker
+                .step(
+                    vec![Snapshot {
+                        name: None,
+                        value: json::json!(value),
+                    }],
+                    SystemTime::now(),
+                )
+                .await
+                .unwrap();
+            result.properties.into_iter().next().unwrap().1
+        };
+
+        assert!(matches!(step(&first, false).await, PropertyValue::False(_)));
+        assert!(matches!(step(&second, true).await, PropertyValue::Residual));
+        assert!(matches!(step(&first, true).await, PropertyValue::False(_)));
+    }
+}
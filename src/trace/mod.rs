@@ -1,15 +1,30 @@
-use std::{path::PathBuf, time::SystemTime};
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 
 use serde::Serialize;
+use serde_json as json;
 use url::Url;
 
 use crate::{
-    browser::actions::BrowserAction,
-    specification::{ltl, render},
+    browser::{actions::BrowserAction, state::DomChanges},
+    specification::{ltl, render, verifier::Snapshot},
 };
 
+pub mod summary;
 pub mod writer;
 
+/// The subdirectory of a trace's directory that screenshots are stored in.
+pub const SCREENSHOTS_DIRECTORY: &str = "screenshots";
+
+/// Where a screenshot of a trace is, given the trace's directory. Screenshot paths are relative
+/// to it, except in traces written by earlier versions, whose paths start with the output path.
+/// Absolute ones are kept as they are.
+pub fn resolve_screenshot(root_path: &Path, screenshot: &Path) -> PathBuf {
+    root_path.join(screenshot)
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct TraceEntry {
     pub timestamp: SystemTime,
@@ -17,12 +32,75 @@ pub struct TraceEntry {
     pub hash_previous: Option<u64>,
     pub hash_current: Option<u64>,
     pub action: Option<BrowserAction>,
-    pub screenshot: PathBuf,
+    /// Relative to the trace's directory, see [`resolve_screenshot`]. Missing when taking the
+    /// screenshot timed out.
+    pub screenshot: Option<PathBuf>,
     pub violations: Vec<PropertyViolation>,
+    /// The values of the specification's extractors, when recording them, so that
+    /// specifications can be replayed against the trace.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshots: Option<Vec<TraceSnapshot>>,
+    /// How the DOM changed since the previous state, when recording it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dom_changes: Option<DomChanges>,
+    /// Labels of the state from the runner's state classifier, when it has one.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+}
+
+/// The value of an extractor in a state, as fed to the verifier.
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceSnapshot {
+    /// The extractor's position in the specification's definition order.
+    pub id: u64,
+    /// The extractor's name, e.g. the variable it's assigned to.
+    pub name: Option<String>,
+    pub value: json::Value,
+}
+
+impl TraceSnapshot {
+    pub fn from_snapshots(snapshots: Vec<Snapshot>) -> Vec<TraceSnapshot> {
+        snapshots
+            .into_iter()
+            .enumerate()
+            .map(|(id, snapshot)| TraceSnapshot {
+                id: id as u64,
+                name: snapshot.name,
+                value: snapshot.value,
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct PropertyViolation {
     pub name: String,
     pub violation: ltl::Violation<render::PrettyFunction>,
+    /// The selector of the element the property focuses on, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub focus: Option<String>,
+    /// A screenshot of the focused element, or of the whole page when the property has no focus
+    /// or the element wasn't found. Set when writing the trace.
+    pub screenshot: Option<PathBuf>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_screenshot() {
+        let root = Path::new("/home/user/moved-trace");
+        assert_eq!(
+            resolve_screenshot(root, Path::new("screenshots/1000.webp")),
+            Path::new("/home/user/moved-trace/screenshots/1000.webp")
+        );
+        assert_eq!(
+            resolve_screenshot(
+                root,
+                Path::new("/tmp/trace/screenshots/1000.webp")
+            ),
+            Path::new("/tmp/trace/screenshots/1000.webp")
+        );
+    }
 }
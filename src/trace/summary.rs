@@ -0,0 +1,90 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use serde::Serialize;
+use url::Url;
+
+use crate::browser::instrumentation::CoverageStats;
+
+/// The outcome of a whole run, written once at shutdown for tools like CI to consume.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunSummary {
+    pub outcome: RunOutcome,
+    /// Set when the outcome is an error.
+    pub error: Option<String>,
+    pub steps: usize,
+    pub duration_ms: u64,
+    /// The verdict of each property as of the last state.
+    pub properties: BTreeMap<String, Verdict>,
+    pub coverage_edges: usize,
+    /// Scripts and documents that couldn't be instrumented, and have no coverage.
+    pub instrumentation_failures: usize,
+    /// How much of the app's code was instrumented, see [`CoverageStats`].
+    pub coverage_stats: CoverageStats,
+    /// Each violated property, in the order they were first violated.
+    pub violations: Vec<FirstViolation>,
+    /// The trace containing violations, if there are any.
+    pub violation_trace: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FirstViolation {
+    pub property: String,
+    /// The number of the state it was first violated in, counting from 1.
+    pub step: usize,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunOutcome {
+    Success,
+    Violation,
+    Error,
+    /// The run was interrupted (e.g. with Ctrl-C) before it ended.
+    Interrupted,
+    /// The run ended with less coverage than required with `--min-coverage`.
+    InsufficientCoverage,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    True,
+    False,
+    /// Neither true nor false yet when the run ended.
+    Pending,
+}
+
+/// The outcomes of testing several origins in turn, in the order they were given.
+#[derive(Debug, Clone, Serialize)]
+pub struct OriginsSummary {
+    /// The worst outcome of any origin: an error, then a violation, then insufficient coverage,
+    /// then an interruption.
+    pub outcome: RunOutcome,
+    pub origins: Vec<OriginSummary>,
+}
+
+impl OriginsSummary {
+    pub fn new(origins: Vec<OriginSummary>) -> Self {
+        let outcomes: Vec<RunOutcome> = origins
+            .iter()
+            .map(|origin| origin.summary.outcome)
+            .collect();
+        let outcome = [
+            RunOutcome::Error,
+            RunOutcome::Violation,
+            RunOutcome::InsufficientCoverage,
+            RunOutcome::Interrupted,
+        ]
+        .into_iter()
+        .find(|outcome| outcomes.contains(outcome))
+        .unwrap_or(RunOutcome::Success);
+        OriginsSummary { outcome, origins }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OriginSummary {
+    pub origin: Url,
+    #[serde(flatten)]
+    pub summary: RunSummary,
+}
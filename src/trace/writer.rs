@@ -1,68 +1,135 @@
-use std::{path::PathBuf, time::UNIX_EPOCH};
+use std::{
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
 
 use anyhow::Result;
 use serde_json as json;
 use tokio::{fs::File, io::AsyncWriteExt};
 
 use crate::{
-    browser::{actions::BrowserAction, state::BrowserState},
-    trace::{PropertyViolation, TraceEntry},
+    browser::{
+        actions::BrowserAction,
+        state::{BrowserState, StateHash},
+    },
+    specification::verifier::Snapshot,
+    trace::{
+        PropertyViolation, SCREENSHOTS_DIRECTORY, TraceEntry, TraceSnapshot,
+    },
 };
 
 pub struct TraceWriter {
-    screenshots_path: PathBuf,
+    root_path: PathBuf,
+    trace_path: PathBuf,
     trace_file: File,
+    state_hash: StateHash,
     last_transition_hash: Option<u64>,
 }
 
 impl TraceWriter {
-    pub async fn initialize(root_path: PathBuf) -> Result<Self> {
+    pub async fn initialize(
+        root_path: PathBuf,
+        state_hash: StateHash,
+    ) -> Result<Self> {
         log::info!(
             "storing trace in {}",
             &root_path
                 .to_str()
                 .expect("states directory path is not valid unicode")
         );
-        let screenshots_path = root_path.join("screenshots");
-        tokio::fs::create_dir_all(&screenshots_path).await?;
+        tokio::fs::create_dir_all(root_path.join(SCREENSHOTS_DIRECTORY))
+            .await?;
+        let trace_path = root_path.join("trace.jsonl");
         let trace_file = File::options()
             .append(true)
             .create(true)
-            .open(root_path.join("trace.jsonl"))
+            .open(&trace_path)
             .await?;
         Ok(TraceWriter {
-            screenshots_path,
+            root_path,
+            trace_path,
             trace_file,
+            state_hash,
             last_transition_hash: None,
         })
     }
+
+    pub fn trace_path(&self) -> &Path {
+        &self.trace_path
+    }
+
+    /// Writes a screenshot, returning its path relative to the trace's root, so that the trace
+    /// stays valid when its directory is moved.
+    async fn write_screenshot(
+        &self,
+        name: &str,
+        data: &[u8],
+    ) -> Result<PathBuf> {
+        let path = Path::new(SCREENSHOTS_DIRECTORY).join(name);
+        File::create_new(self.root_path.join(&path))
+            .await?
+            .write_all(data)
+            .await?;
+        Ok(path)
+    }
+
     pub async fn write(
         &mut self,
         last_action: Option<BrowserAction>,
         state: BrowserState,
-        violations: Vec<PropertyViolation>,
+        mut violations: Vec<PropertyViolation>,
+        snapshots: Option<Vec<Snapshot>>,
+        labels: Vec<String>,
     ) -> Result<()> {
-        let screenshot_path = self.screenshots_path.join(format!(
-            "{}.{}",
-            state.timestamp.duration_since(UNIX_EPOCH)?.as_micros(),
-            &state.screenshot.format.extension()
-        ));
-        File::create_new(&screenshot_path)
-            .await?
-            .write_all(&state.screenshot.data)
-            .await?;
+        let micros = state.timestamp.duration_since(UNIX_EPOCH)?.as_micros();
+        let screenshot_path = match &state.screenshot {
+            Some(screenshot) => Some(
+                self.write_screenshot(
+                    &format!("{}.{}", micros, &screenshot.format.extension()),
+                    &screenshot.data,
+                )
+                .await?,
+            ),
+            None => None,
+        };
 
+        for violation in &mut violations {
+            let element_screenshot = violation
+                .focus
+                .as_ref()
+                .and_then(|selector| state.element_screenshots.get(selector));
+            violation.screenshot = match element_screenshot {
+                Some(screenshot) => Some(
+                    self.write_screenshot(
+                        &format!(
+                            "{}-{}.{}",
+                            micros,
+                            violation.name,
+                            &screenshot.format.extension()
+                        ),
+                        &screenshot.data,
+                    )
+                    .await?,
+                ),
+                None => screenshot_path.clone(),
+            };
+        }
+
+        let transition_hash = state.hash(self.state_hash);
         let entry = TraceEntry {
             timestamp: state.timestamp,
             url: state.url,
             hash_previous: self.last_transition_hash,
-            hash_current: state.transition_hash,
+            hash_current: transition_hash,
             action: last_action,
             screenshot: screenshot_path,
             violations,
+            snapshots: snapshots.map(TraceSnapshot::from_snapshots),
+            dom_changes: state.dom_changes,
+            labels,
         };
 
-        self.last_transition_hash = state.transition_hash;
+        self.last_transition_hash = transition_hash;
 
         self.trace_file
             .write_all(json::to_string(&entry)?.as_bytes())
@@ -71,4 +138,10 @@ impl TraceWriter {
 
         Ok(())
     }
+
+    /// Waits for pending writes to the trace file to complete.
+    pub async fn flush(&mut self) -> Result<()> {
+        self.trace_file.flush().await?;
+        Ok(())
+    }
 }
@@ -1,3 +1,5 @@
+use std::convert::Infallible;
+
 use anyhow::{Result, bail};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
@@ -27,6 +29,26 @@ impl<T> Tree<T> {
         }
     }
 
+    pub fn map<U>(self, f: &mut impl FnMut(T) -> U) -> Tree<U> {
+        let Ok(tree) = self.try_map(&mut |value| Ok::<_, Infallible>(f(value)));
+        tree
+    }
+
+    /// Iterates over all leaves from left to right, including those that can't be picked because
+    /// of zero weights.
+    pub fn leaves(&self) -> impl Iterator<Item = &T> {
+        Leaves { stack: vec![self] }
+    }
+
+    /// The number of leaves.
+    pub fn len(&self) -> usize {
+        self.leaves().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves().next().is_none()
+    }
+
     pub fn filter(self, predicate: &impl Fn(&T) -> bool) -> Self {
         match self {
             Tree::Leaf { value } => {
@@ -90,11 +112,63 @@ impl<T> Tree<T> {
             }
         }
     }
+
+    /// Lists all leaves together with the probability of [`Tree::pick`] returning them. On trees
+    /// with empty branches, the probabilities sum to less than one, so prune first.
+    pub fn flatten(&self) -> Vec<(f64, &T)> {
+        let mut leaves = Vec::new();
+        self.flatten_into(1.0, &mut leaves);
+        leaves
+    }
+
+    fn flatten_into<'a>(
+        &'a self,
+        probability: f64,
+        leaves: &mut Vec<(f64, &'a T)>,
+    ) {
+        match self {
+            Tree::Leaf { value } => leaves.push((probability, value)),
+            Tree::Branch { branches } => {
+                let total: u64 = branches.iter().map(|(w, _)| *w as u64).sum();
+                if total == 0 {
+                    return;
+                }
+                for (weight, subtree) in branches {
+                    subtree.flatten_into(
+                        probability * (*weight as f64 / total as f64),
+                        leaves,
+                    );
+                }
+            }
+        }
+    }
+}
+
+struct Leaves<'a, T> {
+    stack: Vec<&'a Tree<T>>,
+}
+
+impl<'a, T> Iterator for Leaves<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(tree) = self.stack.pop() {
+            match tree {
+                Tree::Leaf { value } => return Some(value),
+                Tree::Branch { branches } => self
+                    .stack
+                    .extend(branches.iter().rev().map(|(_, subtree)| subtree)),
+            }
+        }
+        None
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::Tree::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
 
     #[test]
     fn test_prune_non_empty() {
@@ -151,6 +225,117 @@ mod tests {
         assert_eq!(actual, None);
     }
 
+    #[test]
+    fn test_prune_nested_empty_branches() {
+        let actual = Branch {
+            branches: vec![
+                (
+                    1,
+                    Branch {
+                        branches: vec![(
+                            1,
+                            Branch {
+                                branches: vec![(
+                                    1,
+                                    Branch { branches: vec![] },
+                                )],
+                            },
+                        )],
+                    },
+                ),
+                (1, Leaf { value: 1 }),
+            ],
+        }
+        .prune()
+        .unwrap();
+        assert_eq!(
+            actual,
+            Branch {
+                branches: vec![(1, Leaf { value: 1 })],
+            }
+        );
+    }
+
+    #[test]
+    fn test_leaves() {
+        let tree = Branch {
+            branches: vec![
+                (1, Leaf { value: 1 }),
+                (
+                    3,
+                    Branch {
+                        branches: vec![
+                            (1, Leaf { value: 2 }),
+                            (1, Branch { branches: vec![] }),
+                            (0, Leaf { value: 3 }),
+                        ],
+                    },
+                ),
+                (1, Leaf { value: 4 }),
+            ],
+        };
+        assert_eq!(tree.leaves().collect::<Vec<_>>(), vec![&1, &2, &3, &4]);
+        assert_eq!(tree.len(), 4);
+        assert!(!tree.is_empty());
+        assert!(
+            Branch::<()> {
+                branches: vec![(1, Branch { branches: vec![] })],
+            }
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_map() {
+        let tree = Branch {
+            branches: vec![
+                (1, Leaf { value: 1 }),
+                (
+                    2,
+                    Branch {
+                        branches: vec![(3, Leaf { value: 2 })],
+                    },
+                ),
+            ],
+        };
+        let expected = Branch {
+            branches: vec![
+                (1, Leaf { value: 10 }),
+                (
+                    2,
+                    Branch {
+                        branches: vec![(3, Leaf { value: 20 })],
+                    },
+                ),
+            ],
+        };
+        assert_eq!(tree.map(&mut |x| x * 10), expected);
+    }
+
+    #[test]
+    fn test_flatten() {
+        let tree = Branch {
+            branches: vec![
+                (1, Leaf { value: 1 }),
+                (
+                    3,
+                    Branch {
+                        branches: vec![
+                            (1, Leaf { value: 2 }),
+                            (2, Leaf { value: 3 }),
+                            (1, Branch { branches: vec![] }),
+                        ],
+                    },
+                ),
+                (0, Leaf { value: 4 }),
+            ],
+        };
+        assert_eq!(
+            tree.flatten(),
+            vec![(0.25, &1), (0.1875, &2), (0.375, &3), (0.0, &4)]
+        );
+    }
+
     #[test]
     fn test_filter() {
         let tree = Branch {
@@ -234,4 +419,51 @@ mod tests {
         // With 1000:1 ratio, heavy should dominate
         assert!(heavy_count > 80);
     }
+
+    #[test]
+    fn test_pick_distribution_matches_flatten() {
+        let tree = Branch {
+            branches: vec![
+                (1, Leaf { value: 0 }),
+                (
+                    3,
+                    Branch {
+                        branches: vec![
+                            (1, Leaf { value: 1 }),
+                            (2, Leaf { value: 2 }),
+                            (1, Branch { branches: vec![] }),
+                        ],
+                    },
+                ),
+                (
+                    2,
+                    Branch {
+                        branches: vec![(
+                            1,
+                            Branch {
+                                branches: vec![(5, Leaf { value: 3 })],
+                            },
+                        )],
+                    },
+                ),
+                (0, Leaf { value: 4 }),
+            ],
+        }
+        .prune()
+        .unwrap();
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let samples = 100_000;
+        let mut counts = [0usize; 5];
+        for _ in 0..samples {
+            counts[*tree.pick(&mut rng).unwrap()] += 1;
+        }
+        for (probability, value) in tree.flatten() {
+            let frequency = counts[*value] as f64 / samples as f64;
+            assert!(
+                (frequency - probability).abs() < 0.01,
+                "leaf {value} picked with frequency {frequency}, expected {probability}"
+            );
+        }
+        assert_eq!(counts[4], 0);
+    }
 }
@@ -1,9 +1,57 @@
+use std::{fmt::Display, str::FromStr};
+
 use anyhow::{Result, anyhow};
-use url::Url;
+use serde::Deserialize;
+use url::{Host, Url};
 
+/// Whether the URL is on the domain's host and port. Documents created by a page, like generated
+/// previews, are judged by where they come from: `blob:` URLs by the URL of the document that
+/// created them, and `data:` URLs, whose origin is opaque, are never within the domain. Other
+/// URLs without a host, like `file:` URLs or `about:blank`, are always within it.
+///
+/// Hosts are compared as parsed, so case and IDN spelling (Unicode or punycode) don't matter,
+/// and neither does a trailing dot. Subdomains, including `www.`, are other hosts. All loopback
+/// hosts (`localhost`, `127.0.0.0/8`, and `::1`) are the same host. Ports are compared after
+/// leaving out the scheme's default, so `http://example.com:80` is `http://example.com`, and
+/// `http://example.com` is within `https://example.com`, but not within
+/// `http://example.com:8080`.
 pub fn is_within_domain(uri: &Url, domain: &Url) -> bool {
-    (uri.host().is_none() || uri.host() == domain.host())
-        && (uri.port().is_none() || uri.port() == domain.port())
+    match uri.scheme() {
+        "blob" => Url::parse(uri.path()).is_ok_and(|creator| {
+            creator.scheme() != "blob" && is_within_domain(&creator, domain)
+        }),
+        "data" => false,
+        _ => match (uri.host(), domain.host()) {
+            (None, _) => true,
+            (Some(host), Some(domain_host)) => {
+                same_host(&host, &domain_host) && uri.port() == domain.port()
+            }
+            (Some(_), None) => false,
+        },
+    }
+}
+
+fn same_host(a: &Host<&str>, b: &Host<&str>) -> bool {
+    let (a, b) = (without_trailing_dot(a), without_trailing_dot(b));
+    a == b || (is_loopback(&a) && is_loopback(&b))
+}
+
+fn without_trailing_dot<'a>(host: &Host<&'a str>) -> Host<&'a str> {
+    match host {
+        Host::Domain(domain) => {
+            Host::Domain(domain.strip_suffix('.').unwrap_or(domain))
+        }
+        Host::Ipv4(address) => Host::Ipv4(*address),
+        Host::Ipv6(address) => Host::Ipv6(*address),
+    }
+}
+
+fn is_loopback(host: &Host<&str>) -> bool {
+    match host {
+        Host::Domain(domain) => domain.eq_ignore_ascii_case("localhost"),
+        Host::Ipv4(address) => address.is_loopback(),
+        Host::Ipv6(address) => address.is_loopback(),
+    }
 }
 
 #[allow(unused, reason = "porting this to js scripts")]
@@ -11,6 +59,127 @@ pub fn parse_browser_url(string: &str, context: &Url) -> Result<Url> {
     context.join(string).map_err(|err| anyhow!(err))
 }
 
+/// A URL pattern using the same wildcard syntax as the Chrome DevTools
+/// Protocol: `*` matches zero or more characters, `?` matches exactly one,
+/// and a backslash escapes the next character.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct UrlPattern(String);
+
+impl UrlPattern {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Matches greedily, backtracking only to the last `*`, so that matching takes at most time
+    /// proportional to the lengths of the pattern and URL multiplied, however many `*`s there
+    /// are.
+    pub fn matches(&self, url: &str) -> bool {
+        let pattern: Vec<char> = self.0.chars().collect();
+        let input: Vec<char> = url.chars().collect();
+        let (mut p, mut i) = (0, 0);
+        // Where to resume after the last `*`: the pattern after it, and the input it matched up
+        // to.
+        let mut backtrack: Option<(usize, usize)> = None;
+        while i < input.len() {
+            match pattern.get(p) {
+                Some('*') => {
+                    p += 1;
+                    backtrack = Some((p, i));
+                    continue;
+                }
+                Some('?') => {
+                    p += 1;
+                    i += 1;
+                    continue;
+                }
+                Some('\\') if pattern.get(p + 1) == Some(&input[i]) => {
+                    p += 2;
+                    i += 1;
+                    continue;
+                }
+                Some('\\') if p + 1 < pattern.len() => {}
+                Some(c) if *c == input[i] => {
+                    p += 1;
+                    i += 1;
+                    continue;
+                }
+                _ => {}
+            }
+            match backtrack {
+                Some((star_p, star_i)) => {
+                    // Let the last `*` match one more character.
+                    backtrack = Some((star_p, star_i + 1));
+                    p = star_p;
+                    i = star_i + 1;
+                }
+                None => return false,
+            }
+        }
+        pattern[p..].iter().all(|c| *c == '*')
+    }
+}
+
+impl TryFrom<String> for UrlPattern {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl FromStr for UrlPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err("URL pattern can't be empty".to_string());
+        }
+        Ok(UrlPattern(s.to_string()))
+    }
+}
+
+impl Display for UrlPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Restricts which URLs a test may explore, in addition to staying within the origin's domain.
+/// A URL is in scope if it matches some `allow` pattern (or there are none) and no `deny`
+/// pattern. URLs outside the domain may only be explored if they match an `external` pattern,
+/// e.g. for signing in through another site, and no `deny` pattern.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UrlScope {
+    pub allow: Vec<UrlPattern>,
+    pub deny: Vec<UrlPattern>,
+    pub external: Vec<UrlPattern>,
+}
+
+impl UrlScope {
+    pub fn contains(&self, url: &str) -> bool {
+        (self.allow.is_empty()
+            || self.allow.iter().any(|pattern| pattern.matches(url)))
+            && !self.deny.iter().any(|pattern| pattern.matches(url))
+    }
+
+    /// Whether a test may explore the URL: within the origin's domain and in scope, or an
+    /// allowed external URL.
+    pub fn permits(&self, url: &Url, origin: &Url) -> bool {
+        if is_within_domain(url, origin) {
+            self.contains(url.as_str())
+        } else {
+            self.is_allowed_external(url.as_str())
+        }
+    }
+
+    fn is_allowed_external(&self, url: &str) -> bool {
+        self.external.iter().any(|pattern| pattern.matches(url))
+            && !self.deny.iter().any(|pattern| pattern.matches(url))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -77,4 +246,170 @@ mod tests {
         .unwrap();
         assert_eq!(url.to_string(), "mailto:me@example.com");
     }
+
+    #[test]
+    fn test_is_within_domain_blob() {
+        let domain = Url::parse("https://example.com/app/").unwrap();
+        let within =
+            |url: &str| is_within_domain(&Url::parse(url).unwrap(), &domain);
+        assert!(within("blob:https://example.com/0b1e2f6a"));
+        assert!(!within("blob:https://other.example.com/0b1e2f6a"));
+        assert!(!within("blob:null/0b1e2f6a"));
+        assert!(!within("blob:blob:https://example.com/0b1e2f6a"));
+    }
+
+    #[test]
+    fn test_is_within_domain_data() {
+        let domain = Url::parse("https://example.com/app/").unwrap();
+        assert!(!is_within_domain(
+            &Url::parse("data:application/pdf;base64,JVBERi0=").unwrap(),
+            &domain
+        ));
+        assert!(is_within_domain(
+            &Url::parse("about:blank").unwrap(),
+            &domain
+        ));
+    }
+
+    #[test]
+    fn test_is_within_domain_hosts() {
+        let within = |url: &str, domain: &str| {
+            is_within_domain(
+                &Url::parse(url).unwrap(),
+                &Url::parse(domain).unwrap(),
+            )
+        };
+        assert!(within("https://EXAMPLE.com/a", "https://example.com"));
+        assert!(within("https://example.com./a", "https://example.com"));
+        assert!(within("https://example.com/a", "https://example.com."));
+        assert!(within(
+            "https://bücher.example/a",
+            "https://xn--bcher-kva.example"
+        ));
+        assert!(!within("https://www.example.com/a", "https://example.com"));
+        assert!(!within("https://example.com/a", "https://www.example.com"));
+        assert!(!within("https://example.com.evil/a", "https://example.com"));
+        assert!(within("http://127.0.0.1:3000/a", "http://localhost:3000"));
+        assert!(within("http://localhost:3000/a", "http://127.0.0.1:3000"));
+        assert!(within("http://[::1]:3000/a", "http://localhost:3000"));
+        assert!(!within("http://10.0.0.1:3000/a", "http://localhost:3000"));
+        assert!(within("http://10.0.0.1/a", "http://10.0.0.1"));
+        assert!(!within("http://10.0.0.2/a", "http://10.0.0.1"));
+        assert!(!within("https://example.com/a", "file:///app/index.html"));
+        assert!(within("file:///app/other.html", "https://example.com"));
+    }
+
+    #[test]
+    fn test_is_within_domain_ports() {
+        let within = |url: &str, domain: &str| {
+            is_within_domain(
+                &Url::parse(url).unwrap(),
+                &Url::parse(domain).unwrap(),
+            )
+        };
+        assert!(within("http://example.com:80/a", "http://example.com"));
+        assert!(within("http://example.com/a", "http://example.com:80"));
+        assert!(within("https://example.com:443/a", "https://example.com"));
+        assert!(within("http://example.com/a", "https://example.com"));
+        assert!(within("http://localhost:3000/a", "http://localhost:3000"));
+        assert!(!within("http://localhost/a", "http://localhost:3000"));
+        assert!(!within("http://localhost:3001/a", "http://localhost:3000"));
+        assert!(!within("http://localhost:3000/a", "http://localhost"));
+    }
+
+    fn url_pattern(string: &str) -> UrlPattern {
+        string.parse().unwrap()
+    }
+
+    #[test]
+    fn test_url_pattern_exact() {
+        let pattern = url_pattern("https://example.com/a.js");
+        assert!(pattern.matches("https://example.com/a.js"));
+        assert!(!pattern.matches("https://example.com/a.jsx"));
+    }
+
+    #[test]
+    fn test_url_pattern_wildcards() {
+        let pattern = url_pattern("*://*.analytics.com/*");
+        assert!(pattern.matches("https://www.analytics.com/track?id=1"));
+        assert!(!pattern.matches("https://analytics.com/track"));
+
+        let pattern = url_pattern("https://example.com/api/v?/*");
+        assert!(pattern.matches("https://example.com/api/v1/users"));
+        assert!(!pattern.matches("https://example.com/api/v10/users"));
+    }
+
+    #[test]
+    fn test_url_pattern_escape() {
+        let pattern = url_pattern(r"https://example.com/\*");
+        assert!(pattern.matches("https://example.com/*"));
+        assert!(!pattern.matches("https://example.com/foo"));
+    }
+
+    #[test]
+    fn test_url_pattern_backtracking() {
+        let pattern = url_pattern("*/a*b?c");
+        assert!(pattern.matches("https://example.com/a/abxc/abyc"));
+        assert!(!pattern.matches("https://example.com/a/abxc/abc"));
+        assert!(url_pattern("**").matches(""));
+        assert!(url_pattern(r"*\\").matches(r"https://example.com/\\"));
+    }
+
+    #[test]
+    fn test_url_pattern_many_wildcards() {
+        // Would take exponential time when trying every split of the input between the `*`s.
+        let pattern = url_pattern(&format!("{}b", "*a".repeat(20)));
+        let url = "a".repeat(1000);
+        assert!(!pattern.matches(&url));
+        assert!(pattern.matches(&format!("{}b", url)));
+    }
+
+    #[test]
+    fn test_url_scope_default_allows_all() {
+        assert!(UrlScope::default().contains("https://example.com/anything"));
+    }
+
+    #[test]
+    fn test_url_scope_deny_wins() {
+        let scope = UrlScope {
+            allow: vec![url_pattern("https://example.com/app/*")],
+            deny: vec![url_pattern("*/logout*")],
+            external: vec![],
+        };
+        assert!(scope.contains("https://example.com/app/settings"));
+        assert!(!scope.contains("https://example.com/app/logout"));
+        assert!(!scope.contains("https://example.com/about"));
+    }
+
+    #[test]
+    fn test_url_scope_permits() {
+        let origin = Url::parse("http://localhost:8000").unwrap();
+        let scope = UrlScope {
+            allow: vec![],
+            deny: vec![url_pattern("*/logout*")],
+            external: vec![],
+        };
+        let permits =
+            |url: &str| scope.permits(&Url::parse(url).unwrap(), &origin);
+        assert!(permits("http://localhost:8000/app"));
+        assert!(!permits("http://localhost:8001/app"));
+        assert!(!permits("http://localhost:8000/logout"));
+    }
+
+    #[test]
+    fn test_url_scope_permits_external() {
+        let origin = Url::parse("http://localhost:8000").unwrap();
+        let scope = UrlScope {
+            allow: vec![url_pattern("http://localhost:8000/app/*")],
+            deny: vec![url_pattern("*/logout*")],
+            external: vec![url_pattern("https://sso.example.com/*")],
+        };
+        let permits =
+            |url: &str| scope.permits(&Url::parse(url).unwrap(), &origin);
+        assert!(permits("https://sso.example.com/login"));
+        assert!(!permits("https://sso.example.com/logout"));
+        assert!(!permits("https://example.com/login"));
+        assert!(!permits("http://localhost:8000/about"));
+        assert!(!permits("http://localhost:8000/sso.example.com/x"));
+    }
 }
@@ -1,5 +1,9 @@
 use anyhow::anyhow;
 use axum::Router;
+use axum::extract::Path;
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::Redirect;
+use axum::routing::get;
 use std::io::Write;
 use std::{fmt::Display, sync::Once, time::Duration};
 use tempfile::{NamedTempFile, TempDir};
@@ -12,8 +16,9 @@ use bombadil::{
         Browser, BrowserOptions, DebuggerOptions, Emulation, LaunchOptions,
         actions::BrowserAction,
     },
-    runner::{RunEvent, Runner, RunnerOptions},
+    runner::{RunEvent, Runner, RunnerOptions, StartCondition},
     specification::{render::render_violation, verifier::Specification},
+    url::{UrlPattern, UrlScope},
 };
 
 enum Expect {
@@ -48,6 +53,104 @@ fn setup() {
     });
 }
 
+/// The runner options of the tests, which stop on the first violation and have no fallback
+/// action.
+fn runner_options() -> RunnerOptions {
+    RunnerOptions {
+        stop_on_violation: true,
+        fallback_action: None,
+        ..Default::default()
+    }
+}
+
+/// The browser options of the tests, with a smaller viewport and without the limits and probes
+/// that could make slow CI runs flaky.
+fn browser_options() -> BrowserOptions {
+    BrowserOptions {
+        emulation: Emulation {
+            width: 800,
+            height: 600,
+            device_scale_factor: 2.0,
+        },
+        max_script_size: None,
+        redirect_loop_threshold: None,
+        cdp_timeout: None,
+        responsiveness_probe: None,
+        ..Default::default()
+    }
+}
+
+/// Serves the `tests` directory on a random port, returning the URL of the path on it.
+async fn serve_tests(path: &str) -> Url {
+    setup();
+    let app = Router::new().fallback_service(ServeDir::new("./tests"));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let url =
+        Url::parse(&format!("http://localhost:{}/{}", port, path)).unwrap();
+    log::info!("running test server on {}", &url);
+    url
+}
+
+/// Options for launching a headless browser with a fresh profile in the returned directory,
+/// which must be kept until the browser is terminated.
+fn debugger_options() -> (DebuggerOptions, TempDir) {
+    let user_data_directory = TempDir::new().unwrap();
+    let debugger_options = DebuggerOptions::Managed {
+        launch_options: LaunchOptions {
+            headless: true,
+            no_sandbox: true,
+            user_data_directory: user_data_directory.path().to_path_buf(),
+            executable: None,
+            extra_args: vec![],
+            launch_attempts: 1,
+            launch_backoff: Duration::from_millis(500),
+        },
+    };
+    (debugger_options, user_data_directory)
+}
+
+/// Serves the `tests` directory and launches a browser at the path on it, for tests driving
+/// the browser directly.
+async fn launch_browser(
+    path: &str,
+    browser_options: BrowserOptions,
+) -> (Browser, TempDir) {
+    let origin = serve_tests(path).await;
+    let (debugger_options, user_data_directory) = debugger_options();
+    let browser = Browser::new(
+        origin,
+        UrlScope::default(),
+        browser_options,
+        debugger_options,
+    )
+    .await
+    .unwrap();
+    (browser, user_data_directory)
+}
+
+/// Redirects to the path on the server at the next port, like a sign-in through another site.
+async fn redirect_other_domain(
+    Path(path): Path<String>,
+    headers: HeaderMap,
+) -> Redirect {
+    let host = Url::parse(&format!(
+        "http://{}",
+        headers[header::HOST].to_str().unwrap()
+    ))
+    .unwrap();
+    Redirect::to(&format!(
+        "http://localhost:{}/{}",
+        host.port().unwrap() + 1,
+        path
+    ))
+}
+
 /// These tests are pretty heavy, and running too many parallel risks one browser get stuck and
 /// causing a timeout, so we limit parallelism.
 static TEST_SEMAPHORE: Semaphore = Semaphore::const_new(2);
@@ -56,7 +159,8 @@ const TEST_TIMEOUT_SECONDS: u64 = 120;
 /// Run a named browser test with a given expectation.
 ///
 /// Spins up two web servers: one on a random port P, and one on port P + 1, in order to
-/// facitiliate multi-domain tests.
+/// facitiliate multi-domain tests. Requests to `/redirect-other-domain/{path}` are redirected
+/// to the path on the other port.
 ///
 /// The test starts at:
 ///
@@ -68,11 +172,35 @@ async fn run_browser_test(
     expect: Expect,
     timeout: Duration,
     specification: Option<&str>,
+) {
+    run_browser_test_with_options(
+        name,
+        expect,
+        timeout,
+        specification,
+        runner_options(),
+    )
+    .await
+}
+
+/// Like [`run_browser_test`], but with custom runner options.
+async fn run_browser_test_with_options(
+    name: &str,
+    expect: Expect,
+    timeout: Duration,
+    specification: Option<&str>,
+    runner_options: RunnerOptions,
 ) {
     setup();
     let _permit = TEST_SEMAPHORE.acquire().await.unwrap();
     log::info!("starting browser test");
-    let app = Router::new().fallback_service(ServeDir::new("./tests"));
+    let app = Router::new()
+        .route(
+            "/server-error",
+            get(|| async { StatusCode::INTERNAL_SERVER_ERROR }),
+        )
+        .route("/redirect-other-domain/{*path}", get(redirect_other_domain))
+        .fallback_service(ServeDir::new("./tests"));
     let app_other = app.clone();
 
     let (listener, listener_other, port) = loop {
@@ -102,7 +230,7 @@ async fn run_browser_test(
 
     let origin =
         Url::parse(&format!("http://localhost:{}/{}", port, name,)).unwrap();
-    let user_data_directory = TempDir::new().unwrap();
+    let (debugger_options, _user_data_directory) = debugger_options();
 
     let mut specification_file = NamedTempFile::with_suffix(".ts").unwrap();
     let specification = match specification {
@@ -123,25 +251,12 @@ async fn run_browser_test(
     let runner = Runner::new(
         origin,
         specification,
-        RunnerOptions {
-            stop_on_violation: true,
-        },
+        runner_options,
         BrowserOptions {
-            create_target: true,
-            emulation: Emulation {
-                width: 800,
-                height: 600,
-                device_scale_factor: 2.0,
-            },
-            instrumentation: Default::default(),
-        },
-        DebuggerOptions::Managed {
-            launch_options: LaunchOptions {
-                headless: true,
-                no_sandbox: true,
-                user_data_directory: user_data_directory.path().to_path_buf(),
-            },
+            redirect_loop_threshold: Some(20),
+            ..browser_options()
         },
+        debugger_options,
     )
     .await
     .expect("run_test failed");
@@ -167,6 +282,8 @@ async fn run_browser_test(
                         ));
                     }
                 }
+                Ok(Some(RunEvent::ActionFailed { .. })) => {}
+                Ok(Some(RunEvent::Plateaued { .. })) => {}
                 Ok(None) => break events.shutdown().await,
                 Err(err) => {
                     log::error!("next event error: {}", err);
@@ -231,6 +348,36 @@ async fn test_console_error() {
     .await;
 }
 
+#[tokio::test]
+async fn test_console_error_object() {
+    run_browser_test(
+        "console-error-object",
+        Expect::Error {
+            substring: "noAuthErrors",
+        },
+        Duration::from_secs(TEST_TIMEOUT_SECONDS),
+        Some(
+            r#"
+import { extract, always } from "@antithesishq/bombadil";
+export { clicks } from "@antithesishq/bombadil/defaults";
+
+const consoleErrors = extract((state) =>
+  state.console.filter((entry) => entry.level === "error"),
+);
+
+export const noAuthErrors = always(() =>
+  consoleErrors.current.every(
+    (entry) =>
+      !(entry.args[0]?.code === "AUTH" &&
+        entry.args[0]?.details?.retries?.length === 2),
+  ),
+);
+"#,
+        ),
+    )
+    .await;
+}
+
 #[tokio::test]
 async fn test_links() {
     run_browser_test(
@@ -244,6 +391,37 @@ async fn test_links() {
     .await;
 }
 
+#[tokio::test]
+async fn test_empty_title() {
+    run_browser_test(
+        "empty-title",
+        Expect::Error {
+            substring: "noEmptyTitles",
+        },
+        Duration::from_secs(TEST_TIMEOUT_SECONDS),
+        Some(
+            r#"
+export { noEmptyTitles, noErrorPages } from "@antithesishq/bombadil/defaults/properties";
+export { clicks } from "@antithesishq/bombadil/defaults/actions";
+"#,
+        ),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_network_error() {
+    run_browser_test(
+        "network-error",
+        Expect::Error {
+            substring: "noNetworkErrors",
+        },
+        Duration::from_secs(TEST_TIMEOUT_SECONDS),
+        None,
+    )
+    .await;
+}
+
 #[tokio::test]
 async fn test_uncaught_exception() {
     run_browser_test(
@@ -285,6 +463,75 @@ async fn test_other_domain() {
     .await;
 }
 
+#[tokio::test]
+async fn test_url_scope_deny() {
+    run_browser_test_with_options(
+        "url-scope",
+        Expect::Success,
+        Duration::from_secs(10),
+        None,
+        RunnerOptions {
+            url_scope: UrlScope {
+                allow: vec![],
+                deny: vec![
+                    "*/url-scope/logout.html".parse::<UrlPattern>().unwrap(),
+                ],
+                ..UrlScope::default()
+            },
+            ..runner_options()
+        },
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_external_redirect() {
+    run_browser_test(
+        "external-redirect",
+        Expect::Success,
+        Duration::from_secs(10),
+        None,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_external_redirect_allowed() {
+    run_browser_test_with_options(
+        "external-redirect",
+        Expect::Error {
+            substring: "clicked on the other domain",
+        },
+        Duration::from_secs(TEST_TIMEOUT_SECONDS),
+        None,
+        RunnerOptions {
+            url_scope: UrlScope {
+                external: vec![
+                    "http://localhost:*/external-redirect/*"
+                        .parse::<UrlPattern>()
+                        .unwrap(),
+                ],
+                ..UrlScope::default()
+            },
+            ..runner_options()
+        },
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_redirect_loop() {
+    run_browser_test(
+        "redirect-loop",
+        Expect::Error {
+            substring: "redirect loop detected",
+        },
+        Duration::from_secs(TEST_TIMEOUT_SECONDS),
+        None,
+    )
+    .await;
+}
+
 #[tokio::test]
 async fn test_action_within_iframe() {
     run_browser_test(
@@ -309,6 +556,49 @@ async fn test_no_action_available() {
     .await;
 }
 
+#[tokio::test]
+async fn test_no_action_available_fallback() {
+    run_browser_test_with_options(
+        "no-action-available",
+        Expect::Success,
+        Duration::from_secs(5),
+        None,
+        RunnerOptions {
+            fallback_action: Some(BrowserAction::Reload),
+            ..runner_options()
+        },
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_warmup_steps() {
+    run_browser_test_with_options(
+        "counter-state-machine",
+        Expect::Success,
+        Duration::from_secs(TEST_TIMEOUT_SECONDS),
+        Some(
+            r#"
+import { extract, now } from "@antithesishq/bombadil";
+export { clicks } from "@antithesishq/bombadil/defaults";
+
+const states = extract((state) => {
+  state.window.__states = (state.window.__states ?? 0) + 1;
+  return state.window.__states;
+});
+
+// Only holds once the warm-up is over.
+export const warmed_up = now(() => states.current > 3);
+"#,
+        ),
+        RunnerOptions {
+            warmup_steps: 3,
+            ..runner_options()
+        },
+    )
+    .await;
+}
+
 #[tokio::test]
 async fn test_back_from_non_html() {
     run_browser_test(
@@ -340,54 +630,123 @@ export const navigates_back_from_non_html = eventually(
 
 #[tokio::test]
 async fn test_browser_lifecycle() {
-    setup();
-    let app = Router::new().fallback_service(ServeDir::new("./tests"));
+    let (mut browser, _user_data_directory) =
+        launch_browser("console-error", browser_options()).await;
 
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
-    let addr = listener.local_addr().unwrap();
-    let port = addr.port();
-    tokio::spawn(async move {
-        axum::serve(listener, app).await.unwrap();
-    });
+    browser.initiate().await.unwrap();
 
-    let origin =
-        Url::parse(&format!("http://localhost:{}/console-error", port,))
-            .unwrap();
-    log::info!("running test server on {}", &origin);
-    let user_data_directory = TempDir::new().unwrap();
+    match browser.next_event().await.unwrap() {
+        bombadil::browser::BrowserEvent::StateChanged(state) => {
+            assert_eq!(state.title, "Console Error");
+        }
+        bombadil::browser::BrowserEvent::ActionFailed(action, error) => {
+            panic!("unexpected failure of {:?}: {}", action, error)
+        }
+        bombadil::browser::BrowserEvent::Error(error) => {
+            panic!("unexpected browser error: {}", error)
+        }
+    }
+
+    browser
+        .apply(BrowserAction::Reload, Duration::from_millis(500))
+        .unwrap();
+
+    match browser.next_event().await.unwrap() {
+        bombadil::browser::BrowserEvent::StateChanged(state) => {
+            assert_eq!(state.title, "Console Error");
+        }
+        bombadil::browser::BrowserEvent::ActionFailed(action, error) => {
+            panic!("unexpected failure of {:?}: {}", action, error)
+        }
+        bombadil::browser::BrowserEvent::Error(error) => {
+            panic!("unexpected browser error: {}", error)
+        }
+    }
+
+    log::info!("just changing for CI");
+    browser.terminate().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_runner_step_once() {
+    let origin = serve_tests("links/a.html").await;
+    let (debugger_options, _user_data_directory) = debugger_options();
 
-    let mut browser = Browser::new(
+    let mut runner = Runner::new(
         origin,
-        BrowserOptions {
-            create_target: true,
-            emulation: Emulation {
-                width: 800,
-                height: 600,
-                device_scale_factor: 2.0,
-            },
-            instrumentation: Default::default(),
-        },
-        DebuggerOptions::Managed {
-            launch_options: LaunchOptions {
-                headless: true,
-                no_sandbox: true,
-                user_data_directory: user_data_directory.path().to_path_buf(),
-            },
+        Specification {
+            module_specifier: "@antithesishq/bombadil/defaults".to_string(),
         },
+        runner_options().with_state_classifier(|state| {
+            state
+                .url
+                .path_segments()
+                .and_then(|mut segments| segments.next_back())
+                .map(|file| vec![file.to_string()])
+                .unwrap_or_default()
+        }),
+        browser_options(),
+        debugger_options,
     )
     .await
     .unwrap();
 
+    for i in 0..3 {
+        let step = runner.step_once().await.unwrap();
+        assert!(!step.done);
+        match step.events.last() {
+            Some(RunEvent::NewState {
+                state,
+                last_action,
+                labels,
+                ..
+            }) => {
+                assert_eq!(last_action.is_some(), i > 0);
+                assert_eq!(
+                    labels,
+                    &vec![
+                        state
+                            .url
+                            .path_segments()
+                            .unwrap()
+                            .next_back()
+                            .unwrap()
+                            .to_string()
+                    ]
+                );
+            }
+            other => panic!("expected a new state, got {:?}", other),
+        }
+    }
+
+    runner.terminate().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_dom_hash() {
+    let (mut browser, _user_data_directory) = launch_browser(
+        "console-error",
+        BrowserOptions {
+            dom_hash: true,
+            ..browser_options()
+        },
+    )
+    .await;
+
     browser.initiate().await.unwrap();
 
-    match browser.next_event().await.unwrap() {
+    let dom_hash = match browser.next_event().await.unwrap() {
         bombadil::browser::BrowserEvent::StateChanged(state) => {
-            assert_eq!(state.title, "Console Error");
+            assert!(state.dom_hash.is_some());
+            state.dom_hash
+        }
+        bombadil::browser::BrowserEvent::ActionFailed(action, error) => {
+            panic!("unexpected failure of {:?}: {}", action, error)
         }
         bombadil::browser::BrowserEvent::Error(error) => {
             panic!("unexpected browser error: {}", error)
         }
-    }
+    };
 
     browser
         .apply(BrowserAction::Reload, Duration::from_millis(500))
@@ -395,14 +754,57 @@ async fn test_browser_lifecycle() {
 
     match browser.next_event().await.unwrap() {
         bombadil::browser::BrowserEvent::StateChanged(state) => {
-            assert_eq!(state.title, "Console Error");
+            // The same structure after reloading, regardless of coverage.
+            assert_eq!(state.dom_hash, dom_hash);
+        }
+        bombadil::browser::BrowserEvent::ActionFailed(action, error) => {
+            panic!("unexpected failure of {:?}: {}", action, error)
         }
         bombadil::browser::BrowserEvent::Error(error) => {
             panic!("unexpected browser error: {}", error)
         }
     }
 
-    log::info!("just changing for CI");
+    browser.terminate().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_action_failed() {
+    let (mut browser, _user_data_directory) =
+        launch_browser("console-error", browser_options()).await;
+
+    browser.initiate().await.unwrap();
+
+    match browser.next_event().await.unwrap() {
+        bombadil::browser::BrowserEvent::StateChanged(_) => {}
+        event => panic!("unexpected browser event: {:?}", event),
+    }
+
+    // There's no key with code 0, so applying this fails. The timeout is
+    // long enough that the test only passes if the failure cuts it short.
+    browser
+        .apply(BrowserAction::PressKey { code: 0 }, Duration::from_secs(60))
+        .unwrap();
+
+    let events = async {
+        match browser.next_event().await.unwrap() {
+            bombadil::browser::BrowserEvent::ActionFailed(action, error) => {
+                assert!(matches!(action, BrowserAction::PressKey { code: 0 }));
+                assert!(error.to_string().contains("unknown key"));
+            }
+            event => panic!("expected action failure, got: {:?}", event),
+        }
+        match browser.next_event().await.unwrap() {
+            bombadil::browser::BrowserEvent::StateChanged(state) => {
+                assert_eq!(state.title, "Console Error");
+            }
+            event => panic!("expected new state, got: {:?}", event),
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(10), events)
+        .await
+        .expect("timed out waiting for a new state after the failed action");
+
     browser.terminate().await.unwrap();
 }
 
@@ -518,3 +920,117 @@ const bar = extract((state) => foo.current);
     )
     .await;
 }
+
+#[tokio::test]
+async fn test_extract_html() {
+    run_browser_test(
+        "counter-state-machine",
+        Expect::Success,
+        Duration::from_secs(3),
+        Some(
+            r#"
+import { now, next, always } from "@antithesishq/bombadil";
+import { extractHtml } from "@antithesishq/bombadil/dom";
+export { clicks } from "@antithesishq/bombadil/defaults";
+
+const buttons = extractHtml(".buttons");
+const counter = extractHtml("\#counter", { maxLength: 10 });
+
+export const buttonsUnchanged = always(() => {
+  const html = buttons.current?.html;
+  return next(() => buttons.current?.html === html);
+});
+
+export const buttonsNormalized = always(() =>
+  buttons.current?.html ===
+    '<div class="buttons"><button id="decrement">-</button><button id="increment">+</button></div>',
+);
+
+export const counterTruncated = always(() =>
+  counter.current?.truncated === true &&
+    counter.current.html === '<div id="c',
+);
+"#,
+        ),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_stop_after_no_new_coverage() {
+    run_browser_test_with_options(
+        "console-error",
+        Expect::Success,
+        Duration::from_secs(TEST_TIMEOUT_SECONDS),
+        Some(
+            r#"
+import { always } from "@antithesishq/bombadil";
+export { clicks } from "@antithesishq/bombadil/defaults";
+
+// Never definite, so that only the plateau ends the run.
+export const pending = always(() => true);
+"#,
+        ),
+        RunnerOptions {
+            stop_after_no_new_coverage: Some(5),
+            ..runner_options()
+        },
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_exclude_selectors() {
+    run_browser_test_with_options(
+        "exclude-selectors",
+        Expect::Success,
+        Duration::from_secs(TEST_TIMEOUT_SECONDS),
+        None,
+        RunnerOptions {
+            exclude_selectors: vec![".danger".to_string()],
+            ..runner_options()
+        },
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_start_condition() {
+    run_browser_test_with_options(
+        "start-condition",
+        Expect::Success,
+        Duration::from_secs(5),
+        None,
+        RunnerOptions {
+            start_condition: Some(StartCondition::SelectorVisible(
+                "#app".to_string(),
+            )),
+            ..runner_options()
+        },
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_dom_flood() {
+    run_browser_test(
+        "dom-flood",
+        Expect::Success,
+        Duration::from_secs(20),
+        Some(
+            r#"
+import { extract, eventually } from "@antithesishq/bombadil";
+export { clicks } from "@antithesishq/bombadil/defaults";
+
+const clickCount = extract((state) =>
+  Number(state.document.querySelector("\#clicks")?.textContent ?? 0)
+);
+
+export const keeps_clicking = eventually(
+  () => clickCount.current >= 3
+).within(15, "seconds");
+"#,
+        ),
+    )
+    .await;
+}